@@ -0,0 +1,116 @@
+//! JavaScript API for using the analysis without the UI.
+//!
+//! Errors are thrown as JavaScript strings.
+
+use js_sys::{Object, Reflect, Uint8Array};
+use strum::{EnumCount, IntoEnumIterator};
+use wasm_bindgen::prelude::*;
+
+use crate::qoi::{qoi_analyze, QoiChunk};
+
+/// Analysis result returned to JavaScript.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct ImageSummary {
+    filesize_qoi: usize,
+    filesize_orig: usize,
+    histogram: [usize; QoiChunk::COUNT],
+    pixel_count: usize,
+    chunks: Vec<QoiChunk>,
+}
+
+impl ImageSummary {
+    fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        let img = image::load_from_memory(data)?.to_rgba8();
+        let res = qoi_analyze(&img);
+
+        Ok(Self {
+            filesize_qoi: res.filesize,
+            filesize_orig: data.len(),
+            histogram: res.histogram,
+            pixel_count: res.chunks.len(),
+            chunks: res.chunks,
+        })
+    }
+
+    /// Returns `{ filesize_qoi, filesize_orig, histogram: { QOI_INDEX: N, ... }, pixel_count }`.
+    fn to_js(&self) -> Result<JsValue, JsValue> {
+        let histogram = Object::new();
+        for chunk in QoiChunk::iter() {
+            let count = self.histogram[chunk as usize] as f64;
+            Reflect::set(&histogram, &chunk.name().into(), &count.into())?;
+        }
+
+        let obj = Object::new();
+        Reflect::set(
+            &obj,
+            &"filesize_qoi".into(),
+            &(self.filesize_qoi as f64).into(),
+        )?;
+        Reflect::set(
+            &obj,
+            &"filesize_orig".into(),
+            &(self.filesize_orig as f64).into(),
+        )?;
+        Reflect::set(&obj, &"histogram".into(), &histogram)?;
+        Reflect::set(
+            &obj,
+            &"pixel_count".into(),
+            &(self.pixel_count as f64).into(),
+        )?;
+
+        Ok(obj.into())
+    }
+
+    /// Returns `QoiChunk::to_nibble()` of each pixel, one per byte.
+    fn chunk_nibbles(&self) -> Vec<u8> {
+        self.chunks.iter().map(|chunk| chunk.to_nibble()).collect()
+    }
+}
+
+/// Analyzes an image file in any supported format.
+#[wasm_bindgen]
+pub fn analyze_image_bytes(data: &[u8]) -> Result<JsValue, JsValue> {
+    ImageSummary::from_bytes(data)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?
+        .to_js()
+}
+
+/// Returns the chunk type of each pixel of an image file as `QoiChunk::to_nibble()`, one per
+/// byte.
+#[wasm_bindgen]
+pub fn get_chunk_sequence(data: &[u8]) -> Result<Uint8Array, JsValue> {
+    let summary = ImageSummary::from_bytes(data).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(Uint8Array::from(summary.chunk_nibbles().as_slice()))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use crate::static_image::encode_png;
+
+    use super::*;
+
+    #[test]
+    fn test_image_summary() {
+        let img = RgbaImage::from_fn(3, 1, |x, _| Rgba([0, 0, (100 * x) as u8, 255]));
+        let png = encode_png(&img).unwrap();
+
+        let summary = ImageSummary::from_bytes(&png).unwrap();
+        assert_eq!(summary.filesize_orig, png.len());
+        assert_eq!(summary.filesize_qoi, qoi_analyze(&img).filesize);
+        assert_eq!(summary.pixel_count, 3);
+        assert_eq!(summary.histogram.iter().sum::<usize>(), 3);
+        assert_eq!(
+            summary.chunk_nibbles(),
+            [
+                QoiChunk::Run8.to_nibble(),
+                QoiChunk::Color1.to_nibble(),
+                QoiChunk::Color1.to_nibble()
+            ]
+        );
+
+        assert!(ImageSummary::from_bytes(b"not an image").is_err());
+    }
+}
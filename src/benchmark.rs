@@ -0,0 +1,75 @@
+use std::fmt;
+
+use image::RgbaImage;
+
+use crate::qoi::qoi_analyze;
+use crate::util;
+
+/// Number of untimed runs before measurement.
+const WARMUP_ITERATIONS: u32 = 2;
+
+/// Timing of repeated `qoi_analyze()` runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BenchResult {
+    pub iterations: u32,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub mean_ms: f64,
+    /// Based on `mean_ms`.
+    pub mpixels_per_sec: f64,
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} Mpixels/s (mean over {} runs, min {:.1} ms, max {:.1} ms)",
+            self.mpixels_per_sec, self.iterations, self.min_ms, self.max_ms
+        )
+    }
+}
+
+/// Analyzes `img` `iterations` times (at least once) after a warm-up, and returns the timing.
+pub fn run_benchmark(img: &RgbaImage, iterations: u32) -> BenchResult {
+    let iterations = iterations.max(1);
+
+    for _ in 0..WARMUP_ITERATIONS {
+        qoi_analyze(img);
+    }
+
+    let times: Vec<f64> = (0..iterations)
+        .map(|_| {
+            let start = util::now_ms();
+            qoi_analyze(img);
+            util::now_ms() - start
+        })
+        .collect();
+
+    let min_ms = times.iter().copied().fold(f64::INFINITY, f64::min);
+    let max_ms = times.iter().copied().fold(0., f64::max);
+    let mean_ms = times.iter().sum::<f64>() / f64::from(iterations);
+    let pixel_count = f64::from(img.width()) * f64::from(img.height());
+
+    BenchResult {
+        iterations,
+        min_ms,
+        max_ms,
+        mean_ms,
+        mpixels_per_sec: pixel_count / 1000. / mean_ms.max(f64::EPSILON),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_benchmark() {
+        let img = RgbaImage::new(64, 64);
+        let res = run_benchmark(&img, 0);
+
+        assert_eq!(res.iterations, 1);
+        assert!(res.min_ms <= res.mean_ms && res.mean_ms <= res.max_ms);
+        assert!(res.mpixels_per_sec > 0.);
+    }
+}
@@ -0,0 +1,240 @@
+//! QOI encoder and decoder.
+//!
+//! The encoder chooses chunks exactly as `QoiAnalyzer` does, so `encode(img, _).len()` equals
+//! the analyzed file size. Output images always have 4 channels.
+
+use anyhow::{anyhow, ensure};
+use image::{Rgba, RgbaImage};
+
+use crate::pixel::{PixelDict, PixelDiff, QoiPixel, QOI_INITIAL_PIXEL};
+use crate::qoi::{
+    parse_qoi_header, qoi_header_bytes, QoiChunk, QoiHeader, QOI_END_MARKER, QOI_HEADER_LEN,
+    RUN8_MAX, RUN_MAX,
+};
+
+/// Colorspace field of the QOI header. It is informative only and does not affect chunks.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum QoiColorspace {
+    /// sRGB with linear alpha.
+    Srgb = 0,
+    /// All channels linear.
+    Linear = 1,
+}
+
+impl QoiColorspace {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Srgb => "sRGB",
+            Self::Linear => "linear",
+        }
+    }
+}
+
+/// Encodes `img` into a QOI file.
+pub fn encode(img: &RgbaImage, colorspace: QoiColorspace) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(QOI_HEADER_LEN + 5 * img.as_raw().len() / 4);
+
+    buf.extend_from_slice(&qoi_header_bytes(
+        img.width(),
+        img.height(),
+        4,
+        colorspace as u8,
+    ));
+
+    let mut px_prev = QOI_INITIAL_PIXEL;
+    let mut dict = PixelDict::new();
+    let mut run = 0;
+    for &Rgba(rgba) in img.pixels() {
+        let px = QoiPixel::from(rgba);
+
+        if px == px_prev {
+            run += 1;
+            if run == RUN_MAX {
+                write_run(&mut buf, run);
+                run = 0;
+            }
+            continue;
+        }
+
+        write_run(&mut buf, run);
+        run = 0;
+
+        let slot = PixelDict::hash(px);
+        if dict[slot] == px {
+            buf.push(opcode(QoiChunk::Index, slot));
+        } else {
+            dict[slot] = px;
+            px.sub(px_prev).write_to(px, &mut buf);
+        }
+
+        px_prev = px;
+    }
+    write_run(&mut buf, run);
+
+    buf.extend_from_slice(&QOI_END_MARKER);
+
+    buf
+}
+
+fn opcode(chunk: QoiChunk, data: u8) -> u8 {
+    chunk
+        .encode_byte(data)
+        .expect("data should fit in the first byte of the chunk")
+}
+
+fn write_run(buf: &mut Vec<u8>, run: u16) {
+    match run {
+        0 => {}
+        1..=32 => buf.push(opcode(QoiChunk::Run8, (run - 1) as u8)),
+        _ => {
+            let [hi, lo] = (run - (RUN8_MAX as u16 + 1)).to_be_bytes();
+            buf.extend_from_slice(&[opcode(QoiChunk::Run16, hi), lo]);
+        }
+    }
+}
+
+/// Decodes a QOI file.
+///
+/// The colorspace is validated but otherwise ignored.
+pub fn decode(data: &[u8]) -> anyhow::Result<RgbaImage> {
+    let QoiHeader { width, height, .. } = parse_qoi_header(data)?;
+    let body = &data[QOI_HEADER_LEN..];
+
+    // each 2 bytes cover at most `RUN_MAX` pixels. this also rejects absurd dimensions.
+    let pixel_count = u64::from(width) * u64::from(height);
+    ensure!(
+        pixel_count <= (body.len() as u64) * u64::from(RUN_MAX),
+        "image size {}x{} does not match the data",
+        width,
+        height
+    );
+    let pixel_count = usize::try_from(pixel_count)
+        .ok()
+        .filter(|n| n.checked_mul(4).is_some())
+        .ok_or_else(|| anyhow!("image size {}x{} is too large", width, height))?;
+
+    // don't trust the header for the up-front allocation.
+    let mut buf = Vec::with_capacity(4 * pixel_count.min(body.len()));
+    let mut bytes = body.iter();
+    let mut next = || {
+        bytes
+            .next()
+            .copied()
+            .ok_or_else(|| anyhow!("unexpected end of data"))
+    };
+
+    let mut px = QOI_INITIAL_PIXEL;
+    let mut dict = PixelDict::new();
+    let mut decoded = 0;
+    while decoded < pixel_count {
+        let b0 = next()?;
+
+        let run = match b0 {
+            0x40..=0x5F => Some(usize::from(b0 & 0x1F) + 1),
+            0x60..=0x7F => {
+                let run = u16::from_be_bytes([b0 & 0x1F, next()?]);
+                Some(usize::from(run) + RUN8_MAX + 1)
+            }
+            _ => None,
+        };
+        if let Some(run) = run {
+            ensure!(
+                decoded + run <= pixel_count,
+                "run exceeds the image at pixel {}",
+                decoded
+            );
+            for _ in 0..run {
+                buf.extend_from_slice(&rgba_of(px));
+            }
+            decoded += run;
+            continue;
+        }
+
+        px = match b0 {
+            0x00..=0x3F => dict[b0],
+            0x80..=0xBF => PixelDiff::from_encoded_byte(b0)?.apply(px),
+            0xC0..=0xDF => PixelDiff::from_encoded_bytes_16(b0, next()?)?.apply(px),
+            0xE0..=0xEF => PixelDiff::from_encoded_bytes_24(b0, next()?, next()?)?.apply(px),
+            0xF0..=0xFF => {
+                let mut px = px;
+                if b0 & 0b1000 != 0 {
+                    px = px.with_r(next()?);
+                }
+                if b0 & 0b0100 != 0 {
+                    px = px.with_g(next()?);
+                }
+                if b0 & 0b0010 != 0 {
+                    px = px.with_b(next()?);
+                }
+                if b0 & 0b0001 != 0 {
+                    px = px.with_a(next()?);
+                }
+                px
+            }
+            _ => unreachable!("run chunks should be handled above"),
+        };
+        dict[PixelDict::hash(px)] = px;
+        buf.extend_from_slice(&rgba_of(px));
+        decoded += 1;
+    }
+
+    ensure!(bytes.as_slice() == QOI_END_MARKER, "invalid end marker");
+
+    RgbaImage::from_vec(width, height, buf)
+        .ok_or_else(|| anyhow!("image size {}x{} does not match the data", width, height))
+}
+
+fn rgba_of(px: QoiPixel) -> [u8; 4] {
+    [px.r(), px.g(), px.b(), px.a()]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::qoi::qoi_analyze;
+    use crate::static_image::StaticImage;
+
+    use super::*;
+
+    fn assert_roundtrip(img: &RgbaImage) {
+        let data = encode(img, QoiColorspace::Srgb);
+        assert_eq!(data.len(), qoi_analyze(img).filesize);
+        assert_eq!(&decode(&data).unwrap(), img);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        assert_roundtrip(StaticImage::default().image());
+
+        let px = Rgba([12, 34, 56, 78]);
+        assert_roundtrip(&RgbaImage::from_pixel(1, 1, px));
+        assert_roundtrip(&RgbaImage::from_fn(1, 50, |_, y| {
+            Rgba([0, 5 * y as u8, 0, 255])
+        }));
+        assert_roundtrip(&RgbaImage::from_fn(50, 1, |x, _| {
+            Rgba([x as u8, 0, 0, x as u8])
+        }));
+        // runs longer than `RUN_MAX`.
+        assert_roundtrip(&RgbaImage::from_pixel(100, 100, px));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let img = RgbaImage::from_pixel(2, 2, Rgba([1, 2, 3, 4]));
+        let data = encode(&img, QoiColorspace::Linear);
+
+        assert!(decode(&data[..data.len() - 1]).is_err());
+        assert!(decode(&data[..QOI_HEADER_LEN - 1]).is_err());
+
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'x';
+        assert!(decode(&bad_magic).is_err());
+
+        let mut bad_size = data.clone();
+        bad_size[7] = 3;
+        assert!(decode(&bad_size).is_err());
+
+        let mut huge = data;
+        huge[4..12].fill(0xFF);
+        assert!(decode(&huge).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Chunk sequence export formats.
+//!
+//! The compact binary format packs one chunk per nibble (`QoiChunk::to_nibble()`), two chunks
+//! per byte, high nibble first. If the number of chunks is odd, the last low nibble is
+//! `PADDING_NIBBLE`.
+//!
+//! The CSV format has one row per pixel with its color and the chunk covering it.
+//!
+//! `serde_nibble` serializes a single chunk as its nibble.
+//!
+//! `rle_compress_chunks()` estimates how well the chunk sequence itself compresses.
+
+use std::io::{self, Read, Write};
+
+use crate::qoi::{encoded_chunks, QoiChunk, QOI_HEADER_LEN};
+use crate::static_image::StaticImage;
+
+const PADDING_NIBBLE: u8 = 0xF;
+
+/// Default maximum number of CSV rows (excluding the header).
+pub const CSV_ROW_MAX: usize = 100_000;
+
+const CSV_HEADER: &str = "x,y,r,g,b,a,chunk_type,chunk_family,chunk_bytes,byte_offset";
+
+/// Run-length encodes `chunks` into `(chunk, count)` pairs. Adjacent pairs have different chunks.
+pub fn rle_compress_chunks(chunks: &[QoiChunk]) -> Vec<(QoiChunk, usize)> {
+    let mut runs = Vec::<(QoiChunk, usize)>::new();
+    for &chunk in chunks {
+        match runs.last_mut() {
+            Some((last, count)) if *last == chunk => *count += 1,
+            _ => runs.push((chunk, 1)),
+        }
+    }
+    runs
+}
+
+/// Serializes `QoiChunk` as `QoiChunk::to_nibble()`, for `#[serde(with = "export::serde_nibble")]`.
+#[allow(dead_code)]
+pub mod serde_nibble {
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::qoi::QoiChunk;
+
+    pub fn serialize<S: Serializer>(chunk: &QoiChunk, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(chunk.to_nibble())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<QoiChunk, D::Error> {
+        let n = u8::deserialize(deserializer)?;
+        QoiChunk::from_nibble(n).ok_or_else(|| D::Error::custom(format!("invalid chunk: {}", n)))
+    }
+}
+
+/// Writes `chunks` in the compact binary format.
+pub fn write_compact_sequence(chunks: &[QoiChunk], mut w: impl Write) -> io::Result<()> {
+    let buf: Vec<u8> = chunks
+        .chunks(2)
+        .map(|pair| {
+            let hi = pair[0].to_nibble();
+            let lo = pair
+                .get(1)
+                .map_or(PADDING_NIBBLE, |chunk| chunk.to_nibble());
+            (hi << 4) | lo
+        })
+        .collect();
+
+    w.write_all(&buf)
+}
+
+/// Reads chunks in the compact binary format.
+#[allow(dead_code)]
+pub fn read_compact_sequence(mut r: impl Read) -> io::Result<Vec<QoiChunk>> {
+    let mut buf = Vec::<u8>::new();
+    r.read_to_end(&mut buf)?;
+
+    let mut chunks = Vec::<QoiChunk>::with_capacity(2 * buf.len());
+    for (i, &b) in buf.iter().enumerate() {
+        let is_last = i + 1 == buf.len();
+        for n in [b >> 4, b & 0xF] {
+            if is_last && n == PADDING_NIBBLE && chunks.len() % 2 == 1 {
+                break;
+            }
+            let chunk = QoiChunk::from_nibble(n).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid chunk nibble {:#X} at byte {}", n, i),
+                )
+            })?;
+            chunks.push(chunk);
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Returns the per-pixel CSV of `img`, capped at `CSV_ROW_MAX` rows.
+#[allow(dead_code)]
+pub fn export_csv(img: &StaticImage) -> String {
+    let mut buf = Vec::<u8>::new();
+    write_csv(img, Some(CSV_ROW_MAX), &mut buf).expect("writing into memory should not fail");
+    String::from_utf8(buf).expect("CSV should be UTF-8")
+}
+
+/// Writes the per-pixel CSV of `img` into `w`, at most `row_max` rows if given.
+///
+/// `chunk_bytes` and `byte_offset` are of the chunk covering the pixel, so pixels in a run share
+/// them. Returns whether rows were omitted.
+pub fn write_csv(img: &StaticImage, row_max: Option<usize>, mut w: impl Write) -> io::Result<bool> {
+    let row_max = row_max.unwrap_or(usize::MAX);
+    let width = img.width() as usize;
+
+    writeln!(w, "{}", CSV_HEADER)?;
+
+    let mut offset = QOI_HEADER_LEN;
+    for (chunk, pixels) in encoded_chunks(img.chunks()) {
+        for idx in pixels {
+            if idx >= row_max {
+                return Ok(true);
+            }
+            let (x, y) = (idx % width, idx / width);
+            let [r, g, b, a] = img.image().get_pixel(x as u32, y as u32).0;
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{},{},{},{}",
+                x,
+                y,
+                r,
+                g,
+                b,
+                a,
+                chunk.name(),
+                chunk.family().name(),
+                chunk.byte_len(),
+                offset
+            )?;
+        }
+        offset += chunk.byte_len();
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    #[test]
+    fn test_compact_sequence_roundtrip() {
+        let all: Vec<_> = QoiChunk::iter().collect();
+        for len in 0..=all.len() {
+            let chunks = &all[..len];
+
+            let mut buf = Vec::<u8>::new();
+            write_compact_sequence(chunks, &mut buf).unwrap();
+            assert_eq!(buf.len(), len.div_ceil(2));
+
+            assert_eq!(read_compact_sequence(buf.as_slice()).unwrap(), chunks);
+        }
+
+        assert!(read_compact_sequence([0x0A].as_slice()).is_err());
+        assert!(read_compact_sequence([0xF0].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let buf = [[100, 0, 0, 255], [100, 0, 0, 255], [100, 0, 0, 255]].concat();
+        let img = image::RgbaImage::from_vec(3, 1, buf).unwrap();
+        let img = StaticImage::new("test", img, "", 0);
+
+        let csv = export_csv(&img);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "0,0,100,0,0,255,QOI_COLOR (2-Bytes),Color,2,14");
+        assert_eq!(lines[2], "1,0,100,0,0,255,QOI_RUN_8,Run,1,16");
+        assert_eq!(lines[3], "2,0,100,0,0,255,QOI_RUN_8,Run,1,16");
+
+        let mut buf = Vec::<u8>::new();
+        assert!(write_csv(&img, Some(2), &mut buf).unwrap());
+        assert_eq!(buf.iter().filter(|&&b| b == b'\n').count(), 3);
+    }
+
+    #[test]
+    fn test_rle_compress_chunks() {
+        use QoiChunk::*;
+
+        assert_eq!(rle_compress_chunks(&[]), []);
+        assert_eq!(
+            rle_compress_chunks(&[Run8, Run8, Index, Run8, Run8, Run8]),
+            [(Run8, 2), (Index, 1), (Run8, 3)]
+        );
+    }
+
+    #[test]
+    fn test_serde_nibble() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Compact(#[serde(with = "serde_nibble")] QoiChunk);
+
+        for chunk in QoiChunk::iter() {
+            let json = serde_json::to_string(&Compact(chunk)).unwrap();
+            assert_eq!(json, chunk.to_nibble().to_string());
+            assert_eq!(
+                serde_json::from_str::<Compact>(&json).unwrap(),
+                Compact(chunk)
+            );
+        }
+
+        assert!(serde_json::from_str::<Compact>("10").is_err());
+    }
+}
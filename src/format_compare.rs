@@ -0,0 +1,71 @@
+use image::codecs::png::PngEncoder;
+use image::{ColorType, Rgba, RgbaImage};
+
+use crate::stats::entropy;
+
+/// Encoded sizes of an image in several formats.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FormatSizes {
+    pub png: usize,
+    pub qoi: usize,
+    /// Rough estimate, not an actual encoding.
+    pub webp_estimate: usize,
+}
+
+impl FormatSizes {
+    /// Returns whether QOI is not larger than any other format.
+    pub fn qoi_wins(&self) -> bool {
+        self.qoi <= self.png && self.qoi <= self.webp_estimate
+    }
+}
+
+/// Returns the sizes of `img`, given its QOI file size `qoi` (see `qoi_analyze()`).
+pub fn estimate_sizes(img: &RgbaImage, qoi: usize) -> FormatSizes {
+    let png = png_size(img);
+
+    let webp_estimate = webp_size_estimate(img);
+
+    FormatSizes {
+        png,
+        qoi,
+        webp_estimate,
+    }
+}
+
+fn png_size(img: &RgbaImage) -> usize {
+    let mut buf = Vec::<u8>::new();
+    PngEncoder::new(&mut buf)
+        .encode(img, img.width(), img.height(), ColorType::Rgba8)
+        .expect("PNG encoding into memory should not fail");
+
+    buf.len()
+}
+
+/// Estimates lossless WebP size from the entropy of left-prediction residuals.
+///
+/// WebP lossless uses spatial prediction and entropy coding, so the order-0 entropy of
+/// residuals is a reasonable (though crude) approximation.
+fn webp_size_estimate(img: &RgbaImage) -> usize {
+    // container and header overhead.
+    const OVERHEAD: usize = 32;
+
+    let mut histograms = [[0_usize; 256]; 4];
+    for row in img.rows() {
+        let mut prev = [0_u8; 4];
+        for &Rgba(rgba) in row {
+            for (ch, histogram) in histograms.iter_mut().enumerate() {
+                let residual = rgba[ch].wrapping_sub(prev[ch]);
+                histogram[usize::from(residual)] += 1;
+            }
+            prev = rgba;
+        }
+    }
+
+    let pixel_count = (img.width() as usize) * (img.height() as usize);
+    let bits: f64 = histograms
+        .iter()
+        .map(|histogram| entropy(histogram) * pixel_count as f64)
+        .sum();
+
+    OVERHEAD + (bits / 8.).ceil() as usize
+}
@@ -1,6 +1,19 @@
+pub mod api;
+mod benchmark;
+mod codec;
+mod export;
+mod format_compare;
+mod optimize;
+mod palette;
 mod pixel;
+mod preprocess;
 mod qoi;
+mod report;
+mod sensitivity;
+mod session;
 mod static_image;
+mod stats;
+mod trace;
 mod util;
 mod vis;
 mod web;
@@ -0,0 +1,104 @@
+use image::RgbaImage;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::qoi::qoi_analyze;
+
+/// Order in which pixels are fed to the encoder.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter)]
+pub enum ScanOrder {
+    /// Row by row, as in the spec.
+    Horizontal,
+    /// Column by column.
+    Vertical,
+    /// Odd rows first, then even rows (0-based).
+    RowInterleaved,
+}
+
+impl ScanOrder {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Horizontal => "horizontal",
+            Self::Vertical => "vertical",
+            Self::RowInterleaved => "row-interleaved",
+        }
+    }
+}
+
+/// Returns an image of the same dimensions whose row-major pixels are those of `img` in `order`.
+///
+/// The pixel layout of the result is different from `img` (except for `Horizontal`).
+pub fn reorder(img: &RgbaImage, order: ScanOrder) -> RgbaImage {
+    let (w, h) = img.dimensions();
+    let coords: Box<dyn Iterator<Item = (u32, u32)>> = match order {
+        ScanOrder::Horizontal => Box::new((0..h).flat_map(|y| (0..w).map(move |x| (x, y)))),
+        ScanOrder::Vertical => Box::new((0..w).flat_map(|x| (0..h).map(move |y| (x, y)))),
+        ScanOrder::RowInterleaved => Box::new(
+            (1..h)
+                .step_by(2)
+                .chain((0..h).step_by(2))
+                .flat_map(|y| (0..w).map(move |x| (x, y))),
+        ),
+    };
+
+    let buf: Vec<u8> = coords.flat_map(|(x, y)| img.get_pixel(x, y).0).collect();
+
+    RgbaImage::from_vec(w, h, buf).expect("buffer size should be equal to `4 * width * height`")
+}
+
+/// Returns the scan order giving the smallest QOI file size, and the size.
+///
+/// Ties are broken in favor of the spec order.
+fn best_scan_order(img: &RgbaImage) -> (ScanOrder, usize) {
+    ScanOrder::iter()
+        .map(|order| (order, qoi_analyze(&reorder(img, order)).filesize))
+        .min_by_key(|&(_, filesize)| filesize)
+        .expect("there should be scan orders")
+}
+
+/// Experimental: returns `img` reordered in the scan order giving the smallest QOI file size,
+/// with the order.
+///
+/// This is not a lossless transform of `img`. The result has a different pixel layout.
+pub fn reorder_pixels_for_compression(img: &RgbaImage) -> (ScanOrder, RgbaImage) {
+    let (order, _) = best_scan_order(img);
+    (order, reorder(img, order))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn test_reorder() {
+        // 2x3 image with pixel values 0..6 in row-major order.
+        let img = RgbaImage::from_fn(2, 3, |x, y| Rgba([(2 * y + x) as u8, 0, 0, 255]));
+        let order_of = |img: &RgbaImage| img.pixels().map(|px| px.0[0]).collect::<Vec<_>>();
+
+        assert_eq!(
+            order_of(&reorder(&img, ScanOrder::Horizontal)),
+            [0, 1, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            order_of(&reorder(&img, ScanOrder::Vertical)),
+            [0, 2, 4, 1, 3, 5]
+        );
+        assert_eq!(
+            order_of(&reorder(&img, ScanOrder::RowInterleaved)),
+            [2, 3, 0, 1, 4, 5]
+        );
+    }
+
+    #[test]
+    fn test_best_scan_order() {
+        // vertical stripes: columns are runs.
+        let img = RgbaImage::from_fn(8, 8, |x, _| Rgba([(40 * x) as u8, 0, 0, 255]));
+        let (order, filesize) = best_scan_order(&img);
+        assert_eq!(order, ScanOrder::Vertical);
+        let (order_reordered, img_reordered) = reorder_pixels_for_compression(&img);
+        assert_eq!(order_reordered, order);
+        assert_eq!(filesize, qoi_analyze(&img_reordered).filesize);
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use strum::{EnumCount, IntoEnumIterator};
+use strum_macros::EnumIter;
+
+use crate::qoi::QoiChunk;
+use crate::vis::color_of_chunk;
+
+/// Sets of chunk display colors.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum PaletteSet {
+    Default,
+    /// Based on the Okabe-Ito palette, distinguishable with common color vision deficiencies.
+    Colorblind,
+    /// Grays with distinct lightness, for grayscale printing.
+    Print,
+    HighContrast,
+}
+
+impl PaletteSet {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Colorblind => "colorblind",
+            Self::Print => "print",
+            Self::HighContrast => "high contrast",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::iter().find(|set| set.name() == name)
+    }
+}
+
+/// Returns the display colors of chunks in `set`, indexed by `QoiChunk as usize`.
+///
+/// Black is not used since it means invisible chunks.
+pub fn chunk_colors(set: PaletteSet) -> [[u8; 3]; QoiChunk::COUNT] {
+    match set {
+        PaletteSet::Default => {
+            let mut colors = [[0; 3]; QoiChunk::COUNT];
+            for chunk in QoiChunk::iter() {
+                colors[chunk as usize] = color_of_chunk(chunk);
+            }
+            colors
+        }
+        PaletteSet::Colorblind => [
+            [0xF0, 0xE4, 0x42], // Index: yellow
+            [0x56, 0xB4, 0xE9], // Run8: sky blue
+            [0x00, 0x72, 0xB2], // Run16: blue
+            [0x00, 0x9E, 0x73], // Diff8: bluish green
+            [0xE6, 0x9F, 0x00], // Diff16: orange
+            [0xCC, 0x79, 0xA7], // Diff24: reddish purple
+            [0xD5, 0x5E, 0x00], // Color1: vermillion, darker for larger chunks
+            [0xA0, 0x46, 0x00], // Color2
+            [0x6B, 0x2F, 0x00], // Color3
+            [0x3A, 0x1A, 0x00], // Color4
+        ],
+        // cheaper chunks are lighter.
+        PaletteSet::Print => [
+            [0xCC, 0xCC, 0xCC], // Index
+            [0xFF, 0xFF, 0xFF], // Run8
+            [0xE6, 0xE6, 0xE6], // Run16
+            [0xB3, 0xB3, 0xB3], // Diff8
+            [0x99, 0x99, 0x99], // Diff16
+            [0x80, 0x80, 0x80], // Diff24
+            [0x66, 0x66, 0x66], // Color1
+            [0x4D, 0x4D, 0x4D], // Color2
+            [0x33, 0x33, 0x33], // Color3
+            [0x1A, 0x1A, 0x1A], // Color4
+        ],
+        PaletteSet::HighContrast => [
+            [0xFF, 0xFF, 0x00], // Index
+            [0xFF, 0xFF, 0xFF], // Run8
+            [0x00, 0xFF, 0x00], // Run16
+            [0x00, 0xFF, 0xFF], // Diff8
+            [0x00, 0x00, 0xFF], // Diff16
+            [0xFF, 0x00, 0xFF], // Diff24
+            [0xFF, 0x80, 0x00], // Color1
+            [0xFF, 0x00, 0x00], // Color2
+            [0x80, 0x00, 0x00], // Color3
+            [0x80, 0x80, 0x80], // Color4
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_chunk_colors() {
+        for set in PaletteSet::iter() {
+            let colors = chunk_colors(set);
+            let distinct: HashSet<_> = colors.iter().collect();
+            assert_eq!(distinct.len(), QoiChunk::COUNT, "{:?}", set);
+            assert!(!distinct.contains(&[0, 0, 0]), "{:?}", set);
+
+            assert_eq!(PaletteSet::from_name(set.name()), Some(set));
+        }
+    }
+}
@@ -1,7 +1,19 @@
+use std::cmp::Ordering;
 use std::ops::RangeInclusive;
 
+use anyhow::ensure;
+use image::Rgba;
+
+use crate::qoi::QoiChunk;
+
+/// Previous pixel at the start of an image, as defined by the QOI spec.
+pub const QOI_INITIAL_PIXEL: QoiPixel = QoiPixel::new(0, 0, 0, 255);
+
 /// RGBA8 pixel.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+///
+/// `Eq` and `Hash` compare the raw bits, which is what the QOI dict needs. `Ord` is by R, G, B,
+/// A in that priority and has no perceptual meaning; use `ByLuminance` to sort colors for display.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct QoiPixel(u32);
 
 impl QoiPixel {
@@ -34,6 +46,73 @@ impl QoiPixel {
         self.0 as u8
     }
 
+    /// Returns this pixel with the red channel replaced by `r`.
+    pub const fn with_r(self, r: u8) -> Self {
+        Self((self.0 & 0x00FF_FFFF) | ((r as u32) << 24))
+    }
+
+    /// Returns this pixel with the green channel replaced by `g`.
+    pub const fn with_g(self, g: u8) -> Self {
+        Self((self.0 & 0xFF00_FFFF) | ((g as u32) << 16))
+    }
+
+    /// Returns this pixel with the blue channel replaced by `b`.
+    pub const fn with_b(self, b: u8) -> Self {
+        Self((self.0 & 0xFFFF_00FF) | ((b as u32) << 8))
+    }
+
+    /// Returns this pixel with the alpha channel replaced by `a`.
+    pub const fn with_a(self, a: u8) -> Self {
+        Self((self.0 & 0xFFFF_FF00) | a as u32)
+    }
+
+    /// Returns the Rec. 601 luma scaled by 1000 (`0..=255_000`). Alpha is ignored.
+    pub fn luminance(self) -> u32 {
+        299 * u32::from(self.r()) + 587 * u32::from(self.g()) + 114 * u32::from(self.b())
+    }
+
+    /// Returns the sum of absolute differences of all channels.
+    pub fn manhattan_distance(self, other: Self) -> u32 {
+        [
+            (self.r(), other.r()),
+            (self.g(), other.g()),
+            (self.b(), other.b()),
+            (self.a(), other.a()),
+        ]
+        .iter()
+        .map(|&(x, y)| u32::from(x.abs_diff(y)))
+        .sum()
+    }
+
+    /// Returns CSS color string like `"rgba(255, 0, 0, 50.2%)"`.
+    pub fn to_css_string(self) -> String {
+        let alpha = (1000. * f64::from(self.a()) / 255.).round() / 10.;
+        format!("rgba({}, {}, {}, {}%)", self.r(), self.g(), self.b(), alpha)
+    }
+
+    /// Parses CSS hex color `"#RRGGBB"` (alpha is 255) or `"#RRGGBBAA"`.
+    ///
+    /// Both uppercase and lowercase are accepted. Leading `'#'` may be omitted.
+    pub fn from_css_hex(s: &str) -> Option<Self> {
+        let s = s.strip_prefix('#').unwrap_or(s);
+        if !(s.len() == 6 || s.len() == 8) || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&s[2 * i..2 * i + 2], 16).ok();
+        let a = if s.len() == 8 { channel(3)? } else { 255 };
+
+        Some(Self::new(channel(0)?, channel(1)?, channel(2)?, a))
+    }
+
+    /// Returns the mask of channels differing from `prev`, where bits 3, 2, 1, 0 are R, G, B, A.
+    pub const fn channels_changed(self, prev: Self) -> u8 {
+        (((self.r() != prev.r()) as u8) << 3)
+            | (((self.g() != prev.g()) as u8) << 2)
+            | (((self.b() != prev.b()) as u8) << 1)
+            | ((self.a() != prev.a()) as u8)
+    }
+
     /// Returns `self - rhs`.
     pub const fn sub(self, rhs: Self) -> DiffOrColor {
         const fn in_bounds(range: RangeInclusive<i8>, value: i8) -> bool {
@@ -64,10 +143,7 @@ impl QoiPixel {
 
         // If only one component differ, QOI_COLOR is better than QOI_DIFF_24.
 
-        let mask = (((dr != 0) as u8) << 3)
-            | (((dg != 0) as u8) << 2)
-            | (((db != 0) as u8) << 1)
-            | ((da != 0) as u8);
+        let mask = self.channels_changed(rhs);
 
         if mask.count_ones() >= 2
             && in_bounds(DIFF_RANGE_5, dr)
@@ -82,12 +158,50 @@ impl QoiPixel {
     }
 }
 
+/// Returns `QOI_INITIAL_PIXEL`.
+impl Default for QoiPixel {
+    fn default() -> Self {
+        QOI_INITIAL_PIXEL
+    }
+}
+
 impl From<[u8; 4]> for QoiPixel {
     fn from(rgba: [u8; 4]) -> Self {
         Self::new(rgba[0], rgba[1], rgba[2], rgba[3])
     }
 }
 
+impl From<Rgba<u8>> for QoiPixel {
+    fn from(Rgba(rgba): Rgba<u8>) -> Self {
+        Self::from(rgba)
+    }
+}
+
+impl From<QoiPixel> for Rgba<u8> {
+    fn from(px: QoiPixel) -> Self {
+        Rgba([px.r(), px.g(), px.b(), px.a()])
+    }
+}
+
+/// `QoiPixel` ordered by luminance, darkest first.
+///
+/// Pixels with equal luminance fall back to the natural order, so this is consistent with the
+/// bitwise `Eq` of `QoiPixel`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ByLuminance(pub QoiPixel);
+
+impl Ord for ByLuminance {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.0.luminance(), self.0).cmp(&(other.0.luminance(), other.0))
+    }
+}
+
+impl PartialOrd for ByLuminance {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 const DIFF_RANGE_2: RangeInclusive<i8> = -2..=1;
 const DIFF_RANGE_4: RangeInclusive<i8> = -8..=7;
 const DIFF_RANGE_5: RangeInclusive<i8> = -16..=15;
@@ -98,6 +212,24 @@ pub enum DiffOrColor {
     Color(u8), // mask: 0b0000rgba (same as QOI_COLOR)
 }
 
+impl DiffOrColor {
+    /// Appends the chunk encoding `px`, where `self` is `px.sub(px_prev)`, to `buf`.
+    pub fn write_to(self, px: QoiPixel, buf: &mut Vec<u8>) {
+        match self {
+            Self::Diff(diff) => diff.write_to(buf),
+            Self::Color(mask) => {
+                buf.push(encode_tag(QoiChunk::of_diff_or_color(self), mask));
+                let channels = [px.r(), px.g(), px.b(), px.a()];
+                for (i, &c) in channels.iter().enumerate() {
+                    if mask & (0b1000 >> i) != 0 {
+                        buf.push(c);
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Pixel differences (QOI_DIFF_8, QOI_DIFF_16, QOI_DIFF_24).
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum PixelDiff {
@@ -159,33 +291,291 @@ impl PixelDiff {
 
         Self::diff24_from_biased(r, g, b, a)
     }
+
+    /// Returns the channel differences (RGBA) represented by this diff.
+    pub const fn deltas(self) -> (i8, i8, i8, i8) {
+        const fn unbias(biased: u16, bits: u32) -> i8 {
+            let mask = (1 << bits) - 1;
+            ((biased & mask) as i8) - (1 << (bits - 1))
+        }
+
+        match self {
+            Self::Diff8(v) => {
+                let v = v as u16;
+                (unbias(v >> 4, 2), unbias(v >> 2, 2), unbias(v, 2), 0)
+            }
+            Self::Diff16(v) => (unbias(v >> 8, 5), unbias(v >> 4, 4), unbias(v, 4), 0),
+            Self::Diff24 { diff_r, diff_gba } => (
+                unbias(diff_r as u16, 5),
+                unbias(diff_gba >> 10, 5),
+                unbias(diff_gba >> 5, 5),
+                unbias(diff_gba, 5),
+            ),
+        }
+    }
+
+    /// Appends the chunk of this diff to `buf`.
+    pub fn write_to(self, buf: &mut Vec<u8>) {
+        let chunk = QoiChunk::of_diff_or_color(DiffOrColor::Diff(self));
+        match self {
+            Self::Diff8(v) => buf.push(encode_tag(chunk, v)),
+            Self::Diff16(v) => {
+                let [hi, lo] = v.to_be_bytes();
+                buf.extend_from_slice(&[encode_tag(chunk, hi), lo]);
+            }
+            Self::Diff24 { diff_r, diff_gba } => {
+                // 1110rrrr rgggggbb bbbaaaaa
+                let v = (u32::from(diff_r) << 15) | u32::from(diff_gba);
+                let [_, b0, b1, b2] = v.to_be_bytes();
+                buf.extend_from_slice(&[encode_tag(chunk, b0), b1, b2]);
+            }
+        }
+    }
+
+    /// Parses a QOI_DIFF_8 chunk. Inverse of `write_to()`.
+    pub fn from_encoded_byte(b: u8) -> anyhow::Result<Self> {
+        Ok(Self::Diff8(decode_tag(QoiChunk::Diff8, b)?))
+    }
+
+    /// Parses a QOI_DIFF_16 chunk. Inverse of `write_to()`.
+    pub fn from_encoded_bytes_16(b1: u8, b2: u8) -> anyhow::Result<Self> {
+        let hi = decode_tag(QoiChunk::Diff16, b1)?;
+
+        Ok(Self::Diff16(u16::from_be_bytes([hi, b2])))
+    }
+
+    /// Parses a QOI_DIFF_24 chunk. Inverse of `write_to()`.
+    pub fn from_encoded_bytes_24(b1: u8, b2: u8, b3: u8) -> anyhow::Result<Self> {
+        let b0 = decode_tag(QoiChunk::Diff24, b1)?;
+        let v = u32::from_be_bytes([0, b0, b2, b3]);
+
+        Ok(Self::Diff24 {
+            diff_r: (v >> 15) as u8,
+            diff_gba: (v & 0x7FFF) as u16,
+        })
+    }
+
+    /// Returns the pixel obtained by adding this diff to `px_prev` (with wrapping).
+    pub const fn apply(self, px_prev: QoiPixel) -> QoiPixel {
+        let (dr, dg, db, da) = self.deltas();
+
+        QoiPixel::new(
+            px_prev.r().wrapping_add(dr as u8),
+            px_prev.g().wrapping_add(dg as u8),
+            px_prev.b().wrapping_add(db as u8),
+            px_prev.a().wrapping_add(da as u8),
+        )
+    }
+
+    /// Returns the number of bits carrying channel differences.
+    pub const fn data_bits(self) -> usize {
+        match self {
+            Self::Diff8(_) => 6,
+            Self::Diff16(_) => 13,
+            Self::Diff24 { .. } => 20,
+        }
+    }
+
+    /// Returns the number of tag bits in the first byte.
+    pub const fn wasted_bits(self) -> usize {
+        match self {
+            Self::Diff8(_) => 2,
+            Self::Diff16(_) => 3,
+            Self::Diff24 { .. } => 4,
+        }
+    }
+
+    /// Returns the maximum absolute channel differences (RGBA) representable by this diff type.
+    ///
+    /// Ranges are asymmetric (e.g. `-2..=1`), so this is the magnitude of the lower bound.
+    pub const fn max_abs_delta(self) -> (u8, u8, u8, u8) {
+        match self {
+            Self::Diff8(_) => (2, 2, 2, 0),
+            Self::Diff16(_) => (16, 8, 8, 0),
+            Self::Diff24 { .. } => (16, 16, 16, 16),
+        }
+    }
+
+    /// Returns whether the channel differences fit in this diff type.
+    ///
+    /// Only ranges are checked. The encoder may still prefer other chunk types.
+    pub const fn would_encode(self, dr: i8, dg: i8, db: i8, da: i8) -> bool {
+        const fn in_bounds(range: RangeInclusive<i8>, value: i8) -> bool {
+            *range.start() <= value && value <= *range.end()
+        }
+
+        match self {
+            Self::Diff8(_) => {
+                da == 0
+                    && in_bounds(DIFF_RANGE_2, dr)
+                    && in_bounds(DIFF_RANGE_2, dg)
+                    && in_bounds(DIFF_RANGE_2, db)
+            }
+            Self::Diff16(_) => {
+                da == 0
+                    && in_bounds(DIFF_RANGE_5, dr)
+                    && in_bounds(DIFF_RANGE_4, dg)
+                    && in_bounds(DIFF_RANGE_4, db)
+            }
+            Self::Diff24 { .. } => {
+                in_bounds(DIFF_RANGE_5, dr)
+                    && in_bounds(DIFF_RANGE_5, dg)
+                    && in_bounds(DIFF_RANGE_5, db)
+                    && in_bounds(DIFF_RANGE_5, da)
+            }
+        }
+    }
+}
+
+/// Returns the bits after the tag of the first byte `b` of `chunk`. Inverse of `encode_tag()`.
+fn decode_tag(chunk: QoiChunk, b: u8) -> anyhow::Result<u8> {
+    let (prefix, mask) = chunk.opcode_prefix();
+    ensure!(
+        b & mask == prefix,
+        "byte 0x{:02X} is not {}",
+        b,
+        chunk.name()
+    );
+
+    Ok(b & !mask)
 }
 
-#[derive(Debug)]
-pub struct PixelDict([QoiPixel; 64]);
+/// Returns the first byte of `chunk` carrying `data`.
+fn encode_tag(chunk: QoiChunk, data: u8) -> u8 {
+    chunk
+        .encode_byte(data)
+        .expect("data should fit in the first byte of the chunk")
+}
+
+/// QOI pixel dict, which also records when each slot was written.
+///
+/// Equality ignores the write times, since they don't affect encoding.
+#[derive(Clone, Debug)]
+pub struct PixelDict {
+    slots: Vec<QoiPixel>,
+    /// Time of the last `write()` to each slot. 0 means never written.
+    last_write_time: Vec<usize>,
+}
 
 impl PixelDict {
-    pub const fn new() -> Self {
-        Self([QoiPixel::new(0, 0, 0, 0); 64])
+    /// Maximum number of slots of `with_size()`.
+    pub const SIZE_MAX: usize = 256;
+
+    /// Creates the spec dict of 64 slots.
+    pub fn new() -> Self {
+        Self::with_size(64)
+    }
+
+    /// Creates a dict of `size` slots. This is not in the spec, only for experiments.
+    ///
+    /// Panics unless `size` is a power of 2 up to `SIZE_MAX`.
+    pub fn with_size(size: usize) -> Self {
+        assert!(
+            size.is_power_of_two() && size <= Self::SIZE_MAX,
+            "invalid dict size: {}",
+            size
+        );
+        Self {
+            slots: vec![QoiPixel::new(0, 0, 0, 0); size],
+            last_write_time: vec![0; size],
+        }
+    }
+
+    /// Returns the number of slots.
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the bytes allocated on the heap, i.e. excluding `size_of::<PixelDict>()`.
+    pub fn heap_bytes(&self) -> usize {
+        self.slots.capacity() * std::mem::size_of::<QoiPixel>()
+            + self.last_write_time.capacity() * std::mem::size_of::<usize>()
+    }
+
+    /// Resets all slots to the initial state `(0, 0, 0, 0)`.
+    pub fn clear(&mut self) {
+        *self = Self::with_size(self.size());
     }
 
+    /// Returns the slot of `px` in the spec dict.
     pub const fn hash(px: QoiPixel) -> u8 {
         (px.r() ^ px.g() ^ px.b() ^ px.a()) & 0x3F
     }
+
+    /// Returns the slot of `px` in this dict, i.e. `hash()` masked to `size()`.
+    pub fn slot(&self, px: QoiPixel) -> u8 {
+        (px.r() ^ px.g() ^ px.b() ^ px.a()) & (self.size() - 1) as u8
+    }
+
+    /// Stores `px` into its slot, recording `time` (should be positive and increasing) as the
+    /// write time.
+    pub fn write(&mut self, px: QoiPixel, time: usize) {
+        let i = usize::from(self.slot(px));
+        self.slots[i] = px;
+        self.last_write_time[i] = time;
+    }
+
+    /// Returns the `write()` time of slot `i`, or `None` if it is never written.
+    pub fn last_write_time(&self, i: u8) -> Option<usize> {
+        Some(self.last_write_time[usize::from(i)]).filter(|&time| time > 0)
+    }
+
+    /// Returns the slots written by `write()` and their pixels, most recently written first.
+    pub fn lru_order(&self) -> impl Iterator<Item = (u8, QoiPixel)> + '_ {
+        let mut slots: Vec<u8> = (0..self.size())
+            .map(|i| i as u8)
+            .filter(|&i| self.last_write_time(i).is_some())
+            .collect();
+        slots.sort_unstable_by_key(|&i| std::cmp::Reverse(self.last_write_time[usize::from(i)]));
+        slots.into_iter().map(move |i| (i, self[i]))
+    }
+}
+
+impl PartialEq for PixelDict {
+    fn eq(&self, other: &Self) -> bool {
+        self.slots == other.slots
+    }
 }
 
+impl Eq for PixelDict {}
+
 impl std::ops::Index<u8> for PixelDict {
     type Output = QoiPixel;
 
     fn index(&self, i: u8) -> &Self::Output {
-        &self.0[usize::from(i)]
+        &self.slots[usize::from(i)]
     }
 }
 
 impl std::ops::IndexMut<u8> for PixelDict {
     fn index_mut(&mut self, i: u8) -> &mut Self::Output {
-        &mut self.0[usize::from(i)]
+        &mut self.slots[usize::from(i)]
+    }
+}
+
+/// Number of pixels hashed together by `batch_hash()`, matching a 128-bit SIMD register of
+/// hash bytes.
+const BATCH_HASH_LANES: usize = 16;
+
+/// Returns `PixelDict::hash()` of each pixel.
+///
+/// Pixels are processed in blocks of `BATCH_HASH_LANES` so that the block body can be replaced
+/// with a SIMD version.
+pub fn batch_hash(pixels: &[QoiPixel]) -> Vec<u8> {
+    let mut hashes = Vec::with_capacity(pixels.len());
+
+    let blocks = pixels.chunks_exact(BATCH_HASH_LANES);
+    let rest = blocks.remainder();
+    for block in blocks {
+        let mut lanes = [0; BATCH_HASH_LANES];
+        for (lane, &px) in lanes.iter_mut().zip(block) {
+            *lane = PixelDict::hash(px);
+        }
+        hashes.extend_from_slice(&lanes);
     }
+    hashes.extend(rest.iter().map(|&px| PixelDict::hash(px)));
+
+    hashes
 }
 
 #[cfg(test)]
@@ -224,6 +614,121 @@ mod tests {
         assert_eq!(px.a(), 4);
 
         assert_eq!(px, QoiPixel::from([1, 2, 3, 4]));
+        assert_eq!(px, QoiPixel::from(Rgba([1, 2, 3, 4])));
+        assert_eq!(Rgba::from(px), Rgba([1, 2, 3, 4]));
+
+        assert_eq!(px.with_r(10), QoiPixel::new(10, 2, 3, 4));
+        assert_eq!(px.with_g(20), QoiPixel::new(1, 20, 3, 4));
+        assert_eq!(px.with_b(30), QoiPixel::new(1, 2, 30, 4));
+        assert_eq!(px.with_a(40), QoiPixel::new(1, 2, 3, 40));
+        assert_eq!(
+            px.with_r(0xFF).with_a(0xFF),
+            QoiPixel::new(0xFF, 2, 3, 0xFF)
+        );
+    }
+
+    #[test]
+    fn test_pixel_css() {
+        assert_eq!(
+            QoiPixel::new(1, 2, 3, 255).to_css_string(),
+            "rgba(1, 2, 3, 100%)"
+        );
+        assert_eq!(
+            QoiPixel::new(255, 0, 128, 0).to_css_string(),
+            "rgba(255, 0, 128, 0%)"
+        );
+        assert_eq!(
+            QoiPixel::new(0, 0, 0, 128).to_css_string(),
+            "rgba(0, 0, 0, 50.2%)"
+        );
+
+        assert_eq!(
+            QoiPixel::from_css_hex("#0a1B2c"),
+            Some(QoiPixel::new(0x0A, 0x1B, 0x2C, 0xFF))
+        );
+        assert_eq!(
+            QoiPixel::from_css_hex("#FFfe0D80"),
+            Some(QoiPixel::new(0xFF, 0xFE, 0x0D, 0x80))
+        );
+        assert_eq!(
+            QoiPixel::from_css_hex("123456"),
+            Some(QoiPixel::new(0x12, 0x34, 0x56, 0xFF))
+        );
+
+        assert_eq!(QoiPixel::from_css_hex(""), None);
+        assert_eq!(QoiPixel::from_css_hex("#"), None);
+        assert_eq!(QoiPixel::from_css_hex("#fff"), None);
+        assert_eq!(QoiPixel::from_css_hex("#1234567"), None);
+        assert_eq!(QoiPixel::from_css_hex("#123456789"), None);
+        assert_eq!(QoiPixel::from_css_hex("#12345g"), None);
+        assert_eq!(QoiPixel::from_css_hex("#+12345"), None);
+        assert_eq!(QoiPixel::from_css_hex("##123456"), None);
+
+        // round-trip via hex.
+        for px in [
+            QoiPixel::new(0, 0, 0, 0),
+            QoiPixel::new(1, 2, 3, 4),
+            QoiPixel::new(0xAB, 0xCD, 0xEF, 0xFF),
+            QoiPixel::new(0xFF, 0xFF, 0xFF, 0xFF),
+        ] {
+            let hex = format!("#{:02x}{:02X}{:02x}{:02X}", px.r(), px.g(), px.b(), px.a());
+            assert_eq!(QoiPixel::from_css_hex(&hex), Some(px));
+        }
+    }
+
+    #[test]
+    fn test_channels_changed() {
+        let px = QoiPixel::new(1, 2, 3, 4);
+
+        assert_eq!(px.channels_changed(px), 0);
+        assert_eq!(px.channels_changed(QoiPixel::new(0, 2, 3, 4)), 0b1000);
+        assert_eq!(px.channels_changed(QoiPixel::new(1, 0, 0, 4)), 0b0110);
+        assert_eq!(px.channels_changed(QoiPixel::new(0, 0, 0, 0)), 0b1111);
+    }
+
+    #[test]
+    fn test_by_luminance() {
+        let mut colors = vec![
+            QoiPixel::new(0, 0, 255, 255),
+            QoiPixel::new(0, 255, 0, 255),
+            QoiPixel::new(255, 0, 0, 255),
+            QoiPixel::new(0, 0, 0, 0),
+            QoiPixel::new(0, 0, 0, 255),
+        ];
+        colors.sort_by_key(|&px| ByLuminance(px));
+        assert_eq!(
+            colors,
+            [
+                QoiPixel::new(0, 0, 0, 0),
+                QoiPixel::new(0, 0, 0, 255),
+                QoiPixel::new(0, 0, 255, 255),
+                QoiPixel::new(255, 0, 0, 255),
+                QoiPixel::new(0, 255, 0, 255),
+            ]
+        );
+        assert_eq!(QoiPixel::new(255, 255, 255, 0).luminance(), 255_000);
+    }
+
+    #[test]
+    fn test_pixel_diff_ranges() {
+        let diff8 = PixelDiff::Diff8(0);
+        let diff16 = PixelDiff::Diff16(0);
+        let diff24 = PixelDiff::Diff24 {
+            diff_r: 0,
+            diff_gba: 0,
+        };
+
+        assert_eq!(diff8.max_abs_delta(), (2, 2, 2, 0));
+        assert_eq!(diff16.max_abs_delta(), (16, 8, 8, 0));
+        assert_eq!(diff24.max_abs_delta(), (16, 16, 16, 16));
+
+        assert!(diff8.would_encode(-2, 1, 0, 0));
+        assert!(!diff8.would_encode(2, 0, 0, 0));
+        assert!(!diff8.would_encode(0, 0, 0, 1));
+        assert!(diff16.would_encode(-16, 7, -8, 0));
+        assert!(!diff16.would_encode(0, 8, 0, 0));
+        assert!(diff24.would_encode(15, -16, 0, 3));
+        assert!(!diff24.would_encode(16, 0, 0, 0));
     }
 
     #[test]
@@ -274,4 +779,271 @@ mod tests {
             DiffOrColor::Color(0b0001)
         );
     }
+
+    /// Previous pixels for enumeration tests, including wrapping channels.
+    const PREVS: [QoiPixel; 3] = [
+        QoiPixel::new(0, 0, 0, 255),
+        QoiPixel::new(255, 255, 255, 255),
+        QoiPixel::new(128, 1, 254, 7),
+    ];
+
+    fn add_deltas(prev: QoiPixel, dr: i8, dg: i8, db: i8) -> QoiPixel {
+        QoiPixel::new(
+            prev.r().wrapping_add(dr as u8),
+            prev.g().wrapping_add(dg as u8),
+            prev.b().wrapping_add(db as u8),
+            prev.a(),
+        )
+    }
+
+    #[test]
+    fn test_all_diff8_eligible() {
+        // one beyond `DIFF_RANGE_2` on each side.
+        for prev in PREVS {
+            for dr in -3..=2 {
+                for dg in -3..=2 {
+                    for db in -3..=2 {
+                        let px = add_deltas(prev, dr, dg, db);
+                        let eligible = [dr, dg, db].iter().all(|d| DIFF_RANGE_2.contains(d));
+                        match px.sub(prev) {
+                            DiffOrColor::Diff(diff @ PixelDiff::Diff8(_)) => {
+                                assert!(eligible, "({}, {}, {})", dr, dg, db);
+                                assert_eq!(diff.deltas(), (dr, dg, db, 0));
+                            }
+                            _ => assert!(!eligible, "({}, {}, {})", dr, dg, db),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_all_diff16_eligible() {
+        // one beyond `DIFF_RANGE_5` and `DIFF_RANGE_4` on each side.
+        for prev in PREVS {
+            for dr in -17..=16 {
+                for dg in -9..=8 {
+                    for db in -9..=8 {
+                        if [dr, dg, db].iter().all(|d| DIFF_RANGE_2.contains(d)) {
+                            continue;
+                        }
+                        let px = add_deltas(prev, dr, dg, db);
+                        let eligible = DIFF_RANGE_5.contains(&dr)
+                            && DIFF_RANGE_4.contains(&dg)
+                            && DIFF_RANGE_4.contains(&db);
+                        match px.sub(prev) {
+                            DiffOrColor::Diff(diff @ PixelDiff::Diff16(_)) => {
+                                assert!(eligible, "({}, {}, {})", dr, dg, db);
+                                assert_eq!(diff.deltas(), (dr, dg, db, 0));
+                            }
+                            _ => assert!(!eligible, "({}, {}, {})", dr, dg, db),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_to() {
+        let write = |diff_or_color: DiffOrColor, px: QoiPixel| {
+            let mut buf = Vec::new();
+            diff_or_color.write_to(px, &mut buf);
+            buf
+        };
+        let px = QoiPixel::new(1, 2, 3, 4);
+
+        // biased: r=0, g=2, b=3.
+        let diff = DiffOrColor::Diff(pixel_diff_8(-2, 0, 1));
+        assert_eq!(write(diff, px), [0b1000_1011]);
+        // biased: r=0, g=15, b=0.
+        let diff = DiffOrColor::Diff(pixel_diff_16(-16, 7, -8));
+        assert_eq!(write(diff, px), [0b1100_0000, 0b1111_0000]);
+        // biased: r=31, g=0, b=16, a=17.
+        let diff = DiffOrColor::Diff(pixel_diff_24(15, -16, 0, 1));
+        assert_eq!(write(diff, px), [0b1110_1111, 0b1000_0010, 0b0001_0001]);
+
+        assert_eq!(write(DiffOrColor::Color(0b1010), px), [0xFA, 1, 3]);
+        assert_eq!(write(DiffOrColor::Color(0b1111), px), [0xFF, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_from_encoded_bytes() {
+        let diffs = [
+            pixel_diff_8(-2, 0, 1),
+            pixel_diff_16(-16, 7, -8),
+            pixel_diff_24(15, -16, 0, 1),
+        ];
+        for diff in diffs {
+            let mut buf = Vec::new();
+            diff.write_to(&mut buf);
+            let decoded = match buf[..] {
+                [b1] => PixelDiff::from_encoded_byte(b1),
+                [b1, b2] => PixelDiff::from_encoded_bytes_16(b1, b2),
+                [b1, b2, b3] => PixelDiff::from_encoded_bytes_24(b1, b2, b3),
+                _ => unreachable!(),
+            };
+            assert_eq!(decoded.unwrap(), diff);
+        }
+
+        assert!(PixelDiff::from_encoded_byte(0b0100_0000).is_err());
+        assert!(PixelDiff::from_encoded_bytes_16(0b1000_0000, 0).is_err());
+        assert!(PixelDiff::from_encoded_bytes_24(0b1111_0000, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_pixel_sub_roundtrip() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+
+        for _ in 0..100_000 {
+            let prev = QoiPixel::from(rng.gen::<[u8; 4]>());
+            // mostly small differences, so that all diff types occur.
+            let px = if rng.gen_bool(0.8) {
+                let d: [i8; 4] = [
+                    rng.gen_range(-20, 20),
+                    rng.gen_range(-20, 20),
+                    rng.gen_range(-20, 20),
+                    rng.gen_range(-2, 2),
+                ];
+                QoiPixel::new(
+                    prev.r().wrapping_add(d[0] as u8),
+                    prev.g().wrapping_add(d[1] as u8),
+                    prev.b().wrapping_add(d[2] as u8),
+                    prev.a().wrapping_add(d[3] as u8),
+                )
+            } else {
+                QoiPixel::from(rng.gen::<[u8; 4]>())
+            };
+
+            let reconstructed = match px.sub(prev) {
+                DiffOrColor::Diff(diff) => diff.apply(prev),
+                DiffOrColor::Color(mask) => {
+                    let pick =
+                        |bit: u8, c: u8, c_prev: u8| if mask & bit != 0 { c } else { c_prev };
+                    QoiPixel::new(
+                        pick(0b1000, px.r(), prev.r()),
+                        pick(0b0100, px.g(), prev.g()),
+                        pick(0b0010, px.b(), prev.b()),
+                        pick(0b0001, px.a(), prev.a()),
+                    )
+                }
+            };
+            assert_eq!(reconstructed, px, "px: {:?}, prev: {:?}", px, prev);
+        }
+    }
+
+    #[test]
+    fn test_batch_hash() {
+        let pixels: Vec<_> = (0..=40_u8)
+            .map(|i| QoiPixel::new(i, 3 * i, i.wrapping_mul(7), 255 - i))
+            .collect();
+
+        for len in [0, 15, 16, 41] {
+            let pixels = &pixels[..len];
+            assert_eq!(
+                batch_hash(pixels),
+                pixels
+                    .iter()
+                    .map(|&p| PixelDict::hash(p))
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_pixel_dict_size() {
+        let px = QoiPixel::new(0xFF, 0, 0, 0);
+
+        let mut dict = PixelDict::with_size(256);
+        assert_eq!(dict.slot(px), 0xFF);
+        dict.write(px, 1);
+        assert_eq!(dict[0xFF], px);
+
+        let mut dict = PixelDict::with_size(16);
+        assert_eq!(dict.slot(px), 0x0F);
+        dict.write(px, 1);
+        dict.clear();
+        assert_eq!(dict.size(), 16);
+        assert_eq!(dict[0x0F], QoiPixel::new(0, 0, 0, 0));
+
+        assert_eq!(PixelDict::new().slot(px), PixelDict::hash(px));
+        assert_ne!(PixelDict::new(), PixelDict::with_size(128));
+    }
+
+    #[test]
+    fn test_pixel_dict_lru_order() {
+        let a = QoiPixel::new(1, 0, 0, 0);
+        let b = QoiPixel::new(2, 0, 0, 0);
+        let c = QoiPixel::new(3, 0, 0, 0);
+
+        let mut dict = PixelDict::new();
+        assert_eq!(dict.lru_order().count(), 0);
+
+        dict.write(a, 1);
+        dict.write(b, 2);
+        dict.write(c, 3);
+        dict.write(a, 4);
+        let order: Vec<_> = dict.lru_order().collect();
+        assert_eq!(order, [(1, a), (3, c), (2, b)]);
+        assert_eq!(dict.last_write_time(2), Some(2));
+        assert_eq!(dict.last_write_time(0), None);
+
+        // write times don't matter for equality.
+        let mut other = PixelDict::new();
+        other.write(c, 10);
+        other.write(b, 20);
+        other.write(a, 30);
+        assert_eq!(dict, other);
+    }
+}
+
+/// Micro benchmarks of the analyzer hot path. Run with
+/// `cargo test --release --features bench -- --nocapture benches`.
+///
+/// Note for CI: results should stay within 2x of the reference baseline measured on the same
+/// machine. Timings of debug builds are meaningless.
+#[cfg(all(test, feature = "bench"))]
+mod benches {
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    use super::*;
+
+    const ITERATIONS: usize = 1_000_000;
+
+    /// Runs `f` `ITERATIONS` times and prints ns/call.
+    fn bench(name: &str, mut f: impl FnMut(usize)) {
+        let start = Instant::now();
+        for i in 0..ITERATIONS {
+            f(i);
+        }
+        let ns = start.elapsed().as_nanos() as f64 / ITERATIONS as f64;
+        println!("{}: {:.2} ns/call", name, ns);
+    }
+
+    #[test]
+    fn bench_pixel_sub() {
+        let prev = QoiPixel::new(100, 100, 100, 255);
+        // Diff8, Diff16, Diff24 and Color.
+        let pixels = [
+            QoiPixel::new(101, 99, 100, 255),
+            QoiPixel::new(110, 104, 100, 255),
+            QoiPixel::new(100, 100, 100, 250),
+            QoiPixel::new(200, 100, 100, 255),
+        ];
+
+        bench("QoiPixel::sub", |i| {
+            black_box(black_box(pixels[i % pixels.len()]).sub(black_box(prev)));
+        });
+    }
+
+    #[test]
+    fn bench_pixel_dict_hash() {
+        bench("PixelDict::hash", |i| {
+            black_box(PixelDict::hash(black_box(QoiPixel(i as u32))));
+        });
+    }
 }
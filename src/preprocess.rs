@@ -0,0 +1,163 @@
+use std::fmt;
+
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+
+use crate::pixel::QoiPixel;
+
+/// Image transform applied before analysis.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Preprocess {
+    Grayscale,
+    /// Keeps only the upper bits of each RGB channel. Clamped to `1..=8`.
+    Quantize(u8),
+    Flip(FlipDir),
+    Rotate(RotateAngle),
+    /// `(x, y, width, height)`, clipped to the image.
+    Crop(u32, u32, u32, u32),
+    /// `(width, height)`.
+    Resize(u32, u32),
+    /// Makes alpha 255 if it is at least the threshold, and 0 otherwise.
+    AlphaThreshold(u8),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FlipDir {
+    Horizontal,
+    Vertical,
+}
+
+/// Clockwise rotation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RotateAngle {
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Preprocess {
+    /// Applies this transform to `img`.
+    ///
+    /// Transforms keeping dimensions are done in place.
+    pub fn apply(self, mut img: RgbaImage) -> RgbaImage {
+        match self {
+            Self::Grayscale => {
+                for px in img.pixels_mut() {
+                    let luma = (QoiPixel::from(px.0).luminance() / 1000) as u8;
+                    px.0[..3].copy_from_slice(&[luma; 3]);
+                }
+                img
+            }
+            Self::Quantize(bits) => {
+                let mask = 0xFF_u8 << (8 - bits.clamp(1, 8));
+                for px in img.pixels_mut() {
+                    for c in &mut px.0[..3] {
+                        *c &= mask;
+                    }
+                }
+                img
+            }
+            Self::Flip(FlipDir::Horizontal) => {
+                imageops::flip_horizontal_in_place(&mut img);
+                img
+            }
+            Self::Flip(FlipDir::Vertical) => {
+                imageops::flip_vertical_in_place(&mut img);
+                img
+            }
+            Self::Rotate(RotateAngle::Deg90) => imageops::rotate90(&img),
+            Self::Rotate(RotateAngle::Deg180) => {
+                imageops::rotate180_in_place(&mut img);
+                img
+            }
+            Self::Rotate(RotateAngle::Deg270) => imageops::rotate270(&img),
+            Self::Crop(..) if img.width() == 0 || img.height() == 0 => img,
+            Self::Crop(x, y, w, h) => {
+                let x = x.min(img.width() - 1);
+                let y = y.min(img.height() - 1);
+                let w = w.clamp(1, img.width() - x);
+                let h = h.clamp(1, img.height() - y);
+                imageops::crop_imm(&img, x, y, w, h).to_image()
+            }
+            Self::Resize(w, h) => imageops::resize(&img, w.max(1), h.max(1), FilterType::Lanczos3),
+            Self::AlphaThreshold(threshold) => {
+                img.pixels_mut()
+                    .for_each(|p| p[3] = if p[3] >= threshold { 255 } else { 0 });
+                img
+            }
+        }
+    }
+
+    /// Returns a short tag to append to image names, like `"_gray"`.
+    pub fn name_suffix(self) -> String {
+        match self {
+            Self::Grayscale => "_gray".to_owned(),
+            Self::Quantize(bits) => format!("_q{}", bits),
+            Self::Flip(FlipDir::Horizontal) => "_fliph".to_owned(),
+            Self::Flip(FlipDir::Vertical) => "_flipv".to_owned(),
+            Self::Rotate(RotateAngle::Deg90) => "_rot90".to_owned(),
+            Self::Rotate(RotateAngle::Deg180) => "_rot180".to_owned(),
+            Self::Rotate(RotateAngle::Deg270) => "_rot270".to_owned(),
+            Self::Crop(x, y, w, h) => format!("_crop{}x{}+{}+{}", w, h, x, y),
+            Self::Resize(w, h) => format!("_{}x{}", w, h),
+            Self::AlphaThreshold(threshold) => format!("_at{}", threshold),
+        }
+    }
+}
+
+impl fmt::Display for Preprocess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Grayscale => write!(f, "grayscale"),
+            Self::Quantize(bits) => write!(f, "quantize ({} bits)", bits),
+            Self::Flip(FlipDir::Horizontal) => write!(f, "flip horizontally"),
+            Self::Flip(FlipDir::Vertical) => write!(f, "flip vertically"),
+            Self::Rotate(RotateAngle::Deg90) => write!(f, "rotate 90\u{b0}"),
+            Self::Rotate(RotateAngle::Deg180) => write!(f, "rotate 180\u{b0}"),
+            Self::Rotate(RotateAngle::Deg270) => write!(f, "rotate 270\u{b0}"),
+            Self::Crop(x, y, w, h) => write!(f, "crop {}x{} at ({}, {})", w, h, x, y),
+            Self::Resize(w, h) => write!(f, "resize to {}x{}", w, h),
+            Self::AlphaThreshold(threshold) => write!(f, "alpha threshold {}", threshold),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn test_preprocess() {
+        let img = RgbaImage::from_fn(3, 2, |x, y| {
+            Rgba([(10 * x) as u8, (10 * y) as u8, 0xFF, 0xFF])
+        });
+
+        let rotated = Preprocess::Rotate(RotateAngle::Deg90).apply(img.clone());
+        assert_eq!(rotated.dimensions(), (2, 3));
+        assert_eq!(rotated.get_pixel(1, 0), img.get_pixel(0, 0));
+
+        let flipped = Preprocess::Flip(FlipDir::Horizontal).apply(img.clone());
+        assert_eq!(flipped.get_pixel(0, 0), img.get_pixel(2, 0));
+
+        let quantized = Preprocess::Quantize(4).apply(img.clone());
+        assert_eq!(quantized.get_pixel(2, 1).0, [0x10, 0x00, 0xF0, 0xFF]);
+
+        let gray = Preprocess::Grayscale.apply(img.clone());
+        assert_eq!(gray.get_pixel(0, 0).0, [29, 29, 29, 0xFF]);
+
+        let translucent =
+            RgbaImage::from_fn(3, 1, |x, _| Rgba([0, 0, 0, [0, 127, 128][x as usize]]));
+        let binarized = Preprocess::AlphaThreshold(128).apply(translucent);
+        assert_eq!(
+            binarized.pixels().map(|p| p[3]).collect::<Vec<_>>(),
+            [0, 0, 255]
+        );
+
+        let cropped = Preprocess::Crop(1, 1, 10, 10).apply(img);
+        assert_eq!(cropped.dimensions(), (2, 1));
+        let cropped = Preprocess::Crop(1, 1, 10, 10).apply(RgbaImage::new(0, 5));
+        assert_eq!(cropped.dimensions(), (0, 5));
+    }
+}
@@ -1,13 +1,85 @@
-use image::{GenericImageView, Rgba};
-use strum::EnumCount;
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use std::rc::Rc;
+
+use anyhow::ensure;
+use image::{GenericImageView, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use strum::{EnumCount, IntoEnumIterator};
 use strum_macros::{EnumCount as EnumCountMacros, EnumIter};
 
 use crate::pixel::{DiffOrColor, PixelDict, PixelDiff, QoiPixel};
 
-const QOI_HEADER_LEN: usize = 14;
-const QOI_PADDING_LEN: usize = 4;
+/// Magic bytes at the start of a QOI file.
+pub const QOI_MAGIC: [u8; 4] = *b"qoif";
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumCountMacros, EnumIter)]
+/// Bytes at the end of a QOI file.
+pub const QOI_END_MARKER: [u8; 4] = [0, 0, 0, 1];
+
+/// Length of the QOI file header, i.e. the offset of the first chunk.
+pub const QOI_HEADER_LEN: usize = 14;
+
+/// Length of the bytes after the last chunk, i.e. `QOI_END_MARKER`.
+pub const QOI_PADDING_LEN: usize = QOI_END_MARKER.len();
+
+/// Size of a QOI file without chunks, e.g. of a 0x0 image.
+///
+/// 4 (magic) + 4 (width) + 4 (height) + 1 (channels) + 1 (colorspace) + 4 (end marker) bytes.
+pub const QOI_MIN_FILESIZE: usize = QOI_HEADER_LEN + QOI_PADDING_LEN;
+
+/// Fields of the QOI file header.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct QoiHeader {
+    pub width: u32,
+    pub height: u32,
+    /// 3 (RGB) or 4 (RGBA).
+    pub channels: u8,
+    /// 0 (sRGB with linear alpha) or 1 (all linear).
+    pub colorspace: u8,
+}
+
+/// Returns the QOI file header with the given fields.
+pub fn qoi_header_bytes(width: u32, height: u32, channels: u8, colorspace: u8) -> [u8; 14] {
+    let mut buf = [0; QOI_HEADER_LEN];
+    buf[..4].copy_from_slice(&QOI_MAGIC);
+    buf[4..8].copy_from_slice(&width.to_be_bytes());
+    buf[8..12].copy_from_slice(&height.to_be_bytes());
+    buf[12] = channels;
+    buf[13] = colorspace;
+    buf
+}
+
+/// Parses the QOI file header at the start of `data`. Invalid channels or colorspace are errors.
+pub fn parse_qoi_header(data: &[u8]) -> anyhow::Result<QoiHeader> {
+    ensure!(data.len() >= QOI_HEADER_LEN, "too short for a QOI file");
+    ensure!(data.starts_with(&QOI_MAGIC), "not a QOI file");
+
+    let header = QoiHeader {
+        width: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        height: u32::from_be_bytes([data[8], data[9], data[10], data[11]]),
+        channels: data[12],
+        colorspace: data[13],
+    };
+    ensure!(
+        matches!(header.channels, 3 | 4),
+        "invalid number of channels: {}",
+        header.channels
+    );
+    ensure!(
+        matches!(header.colorspace, 0 | 1),
+        "invalid colorspace: {}",
+        header.colorspace
+    );
+
+    Ok(header)
+}
+
+/// Serialized as a name like `"DIFF8"`, or as `to_nibble()` with `export::serde_nibble`.
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, PartialEq, EnumCountMacros, EnumIter, Serialize, Deserialize,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum QoiChunk {
     Index = 0,
     Run8,
@@ -36,10 +108,565 @@ impl QoiChunk {
             Self::Color4 => "QOI_COLOR (5-Bytes)",
         }
     }
+
+    /// 2-letter name for cramped places.
+    pub fn abbr(self) -> &'static str {
+        match self {
+            Self::Index => "IX",
+            Self::Run8 => "R8",
+            Self::Run16 => "R6",
+            Self::Diff8 => "D8",
+            Self::Diff16 => "D6",
+            Self::Diff24 => "D4",
+            Self::Color1 => "C1",
+            Self::Color2 => "C2",
+            Self::Color3 => "C3",
+            Self::Color4 => "C4",
+        }
+    }
+
+    /// Returns the chunk type encoding `px` from `px_prev` without using the dict.
+    ///
+    /// Returns `Run8` if both are equal.
+    pub fn of_delta(px: QoiPixel, px_prev: QoiPixel) -> Self {
+        if px == px_prev {
+            return Self::Run8;
+        }
+
+        Self::of_diff_or_color(px.sub(px_prev))
+    }
+
+    pub fn of_diff_or_color(diff_or_color: DiffOrColor) -> Self {
+        match diff_or_color {
+            DiffOrColor::Diff(PixelDiff::Diff8(_)) => Self::Diff8,
+            DiffOrColor::Diff(PixelDiff::Diff16(_)) => Self::Diff16,
+            DiffOrColor::Diff(PixelDiff::Diff24 { .. }) => Self::Diff24,
+            DiffOrColor::Color(mask) => match mask.count_ones() {
+                1 => Self::Color1,
+                2 => Self::Color2,
+                3 => Self::Color3,
+                4 => Self::Color4,
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    /// Returns the 4-bit code of this chunk type, used by the compact sequence export.
+    pub const fn to_nibble(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_nibble(n: u8) -> Option<Self> {
+        Self::iter().nth(usize::from(n))
+    }
+
+    pub const fn family(self) -> ChunkFamily {
+        match self {
+            Self::Index => ChunkFamily::Index,
+            Self::Run8 | Self::Run16 => ChunkFamily::Run,
+            Self::Diff8 | Self::Diff16 | Self::Diff24 => ChunkFamily::Diff,
+            Self::Color1 | Self::Color2 | Self::Color3 | Self::Color4 => ChunkFamily::Color,
+        }
+    }
+
+    /// Returns the number of bits used for the tag in the first byte.
+    pub const fn opcode_bits(self) -> usize {
+        match self {
+            Self::Index => 2,
+            Self::Run8 | Self::Run16 => 3,
+            Self::Diff8 | Self::Diff16 | Self::Diff24 => self.pixel_diff().wasted_bits(),
+            Self::Color1 | Self::Color2 | Self::Color3 | Self::Color4 => 4,
+        }
+    }
+
+    /// Returns `(prefix_bits, prefix_mask)` of the first byte, where `prefix_mask` covers the
+    /// `opcode_bits()` high bits.
+    pub const fn opcode_prefix(self) -> (u8, u8) {
+        let prefix = match self {
+            Self::Index => 0b0000_0000,
+            Self::Run8 => 0b0100_0000,
+            Self::Run16 => 0b0110_0000,
+            Self::Diff8 => 0b1000_0000,
+            Self::Diff16 => 0b1100_0000,
+            Self::Diff24 => 0b1110_0000,
+            Self::Color1 | Self::Color2 | Self::Color3 | Self::Color4 => 0b1111_0000,
+        };
+        (prefix, !(0xFF >> self.opcode_bits()))
+    }
+
+    /// Returns the first byte of this chunk type carrying `data` in the bits after the tag.
+    ///
+    /// Returns `None` if `data` overlaps the tag. For QOI_COLOR, `data` is the channel mask and
+    /// must have as many channels as this chunk type.
+    pub const fn encode_byte(self, data: u8) -> Option<u8> {
+        let (prefix, mask) = self.opcode_prefix();
+        if data & mask != 0 {
+            return None;
+        }
+        if let Self::Color1 | Self::Color2 | Self::Color3 | Self::Color4 = self {
+            if data.count_ones() as usize != self.byte_len() - 1 {
+                return None;
+            }
+        }
+        Some(prefix | data)
+    }
+
+    /// Returns the number of bits following the tag, i.e. all bits except `opcode_bits()`.
+    ///
+    /// For QOI_COLOR, the channel mask is also counted.
+    pub const fn data_bits(self) -> usize {
+        match self {
+            Self::Diff8 | Self::Diff16 | Self::Diff24 => self.pixel_diff().data_bits(),
+            _ => 8 * self.byte_len() - self.opcode_bits(),
+        }
+    }
+
+    /// Returns the number of bits needed to carry the content of this chunk type with an optimal
+    /// fixed-width code, given the chunk type is known.
+    ///
+    /// E.g. QOI_INDEX needs 6 bits for slots 0-63, and QOI_COLOR needs 4 bits of channel mask
+    /// plus 8 bits per channel.
+    pub const fn theoretical_minimum_bits(self) -> usize {
+        match self {
+            Self::Index => 6,
+            Self::Run8 => 5,
+            Self::Run16 => 13,
+            Self::Diff8 => 2 + 2 + 2,
+            Self::Diff16 => 5 + 4 + 4,
+            Self::Diff24 => 5 + 5 + 5 + 5,
+            Self::Color1 => 4 + 8,
+            Self::Color2 => 4 + 2 * 8,
+            Self::Color3 => 4 + 3 * 8,
+            Self::Color4 => 4 + 4 * 8,
+        }
+    }
+
+    /// Returns the bits spent beyond `theoretical_minimum_bits()`.
+    pub const fn coding_overhead_bits(self) -> usize {
+        8 * self.byte_len() - self.theoretical_minimum_bits()
+    }
+
+    /// Returns the ratio of `data_bits()` to all bits of the chunk.
+    pub fn bit_efficiency(self) -> f64 {
+        (self.data_bits() as f64) / ((8 * self.byte_len()) as f64)
+    }
+
+    /// Returns a representative `PixelDiff` of a diff chunk type, whose bit layout is shared.
+    const fn pixel_diff(self) -> PixelDiff {
+        match self {
+            Self::Diff8 => PixelDiff::Diff8(0),
+            Self::Diff16 => PixelDiff::Diff16(0),
+            Self::Diff24 => PixelDiff::Diff24 {
+                diff_r: 0,
+                diff_gba: 0,
+            },
+            _ => panic!("not a diff chunk"),
+        }
+    }
+
+    /// Returns the number of bytes following the first byte which carry channel data.
+    ///
+    /// Deltas packed together with the tag are not counted: `Diff8` and `Diff16` are 0, while
+    /// `Diff24` counts its 2 trailing bytes.
+    pub const fn color_bytes(self) -> usize {
+        match self {
+            Self::Index | Self::Run8 | Self::Run16 | Self::Diff8 | Self::Diff16 => 0,
+            Self::Color1 => 1,
+            Self::Diff24 | Self::Color2 => 2,
+            Self::Color3 => 3,
+            Self::Color4 => 4,
+        }
+    }
+
+    /// Returns the encoded length of a single chunk in bytes.
+    ///
+    /// Note that a run chunk covers multiple pixels.
+    pub const fn byte_len(self) -> usize {
+        match self {
+            Self::Index | Self::Run8 | Self::Diff8 => 1,
+            Self::Run16 | Self::Diff16 | Self::Color1 => 2,
+            Self::Diff24 | Self::Color2 => 3,
+            Self::Color3 => 4,
+            Self::Color4 => 5,
+        }
+    }
+
+    /// Returns the largest QOI file size of an image with `pixel_count` pixels, where every
+    /// pixel is a `QoiChunk::Color4`.
+    pub const fn worst_case_byte_cost(pixel_count: usize) -> usize {
+        QOI_HEADER_LEN + Self::Color4.byte_len() * pixel_count + QOI_PADDING_LEN
+    }
+
+    /// Returns the smallest QOI file size of an image with `pixel_count` pixels, where all
+    /// pixels are the initial pixel and encoded in runs as long as possible.
+    pub const fn best_case_byte_cost(pixel_count: usize) -> usize {
+        let run_max = RUN_MAX as usize;
+        let rest = pixel_count % run_max;
+        let rest_bytes = if rest == 0 {
+            0
+        } else if rest <= RUN8_MAX {
+            Self::Run8.byte_len()
+        } else {
+            Self::Run16.byte_len()
+        };
+
+        QOI_HEADER_LEN
+            + Self::Run16.byte_len() * (pixel_count / run_max)
+            + rest_bytes
+            + QOI_PADDING_LEN
+    }
+}
+
+/// Chunk types grouped ignoring their sizes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumCountMacros, EnumIter)]
+pub enum ChunkFamily {
+    Index,
+    Run,
+    Diff,
+    Color,
+}
+
+impl ChunkFamily {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Index => "Index",
+            Self::Run => "Run",
+            Self::Diff => "Diff",
+            Self::Color => "Color",
+        }
+    }
+}
+
+/// Returns the QOI file size when the dict is cleared just before row `row`.
+///
+/// This is not in the spec, only for experiments. If `row` is out of the image, no clear occurs.
+pub fn filesize_with_dict_clear<I>(img: &I, row: u32) -> usize
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    let mut enc = QoiAnalyzer::new(|_| ());
+    for (x, y, rgba) in img.pixels() {
+        if x == 0 && y == row {
+            enc.clear_dict();
+        }
+        enc.update(QoiPixel::from(rgba));
+    }
+
+    enc.finalize()
+}
+
+/// Result of `qoi_analyze_compare()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompareResult {
+    /// Number of pixels with the same chunk type in both runs.
+    pub same: usize,
+    /// Number of pixels with different chunk types.
+    pub different: usize,
+    /// Count with `initial_b` minus count with `initial_a`, for each chunk type.
+    pub per_chunk_changes: [i32; QoiChunk::COUNT],
+}
+
+/// Analyzes `img` twice with different initial pixels and compares the chunks.
+pub fn qoi_analyze_compare(
+    img: &RgbaImage,
+    initial_a: QoiPixel,
+    initial_b: QoiPixel,
+) -> CompareResult {
+    let analyze = |px_initial| {
+        let mut chunks = Vec::<QoiChunk>::with_capacity(img.len() / 4);
+        let mut enc = QoiAnalyzer::with_initial_pixel(px_initial, |chunk| chunks.push(chunk));
+        for &rgba in img.pixels() {
+            enc.update(QoiPixel::from(rgba));
+        }
+        enc.finalize();
+        chunks
+    };
+    let chunks_a = analyze(initial_a);
+    let chunks_b = analyze(initial_b);
+
+    let diff = qoi_compare_chunks(&chunks_a, &chunks_b);
+
+    let histogram_a = chunk_histogram(&chunks_a);
+    let histogram_b = chunk_histogram(&chunks_b);
+    let mut per_chunk_changes = [0; QoiChunk::COUNT];
+    for (i, e) in per_chunk_changes.iter_mut().enumerate() {
+        *e = histogram_b[i] as i32 - histogram_a[i] as i32;
+    }
+
+    CompareResult {
+        same: diff.same,
+        different: diff.different,
+        per_chunk_changes,
+    }
+}
+
+/// Result of `qoi_compare_chunks()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChunkDiff {
+    /// Number of pixels with the same chunk type in both sequences.
+    pub same: usize,
+    /// Number of pixels with different chunk types.
+    pub different: usize,
+    /// `transitions[i][j]` is the number of pixels with chunk type `i` in `a` and `j` in `b`,
+    /// where `i != j`. The diagonal is always 0.
+    pub transitions: [[usize; QoiChunk::COUNT]; QoiChunk::COUNT],
+}
+
+/// Compares two chunk sequences pixel by pixel.
+///
+/// The sequences should have the same length. Excess chunks of the longer one are ignored.
+pub fn qoi_compare_chunks(a: &[QoiChunk], b: &[QoiChunk]) -> ChunkDiff {
+    let mut transitions = [[0; QoiChunk::COUNT]; QoiChunk::COUNT];
+    let mut same = 0;
+    for (&chunk_a, &chunk_b) in a.iter().zip(b) {
+        if chunk_a == chunk_b {
+            same += 1;
+        } else {
+            transitions[chunk_a as usize][chunk_b as usize] += 1;
+        }
+    }
+
+    ChunkDiff {
+        same,
+        different: a.len().min(b.len()) - same,
+        transitions,
+    }
+}
+
+/// Returns the ratio of pixels with the same chunk type in `a` and `b` (1 if both are empty).
+///
+/// 1 means pixel-by-pixel identical encoding.
+pub fn chunk_sequence_similarity(a: &[QoiChunk], b: &[QoiChunk]) -> f64 {
+    let len = a.len().min(b.len());
+    if len == 0 {
+        return 1.;
+    }
+
+    qoi_compare_chunks(a, b).same as f64 / len as f64
+}
+
+/// Result of `qoi_analyze()`.
+#[derive(Clone, Debug)]
+pub struct AnalysisResult {
+    pub filesize: usize,
+    /// One chunk per pixel.
+    pub chunks: Vec<QoiChunk>,
+    pub histogram: [usize; QoiChunk::COUNT],
+    /// Number of `QoiChunk::Index` hits for each dict slot.
+    pub index_hit_counts: [usize; 64],
+    /// Dict state at the end of the image.
+    pub dict: PixelDict,
+    /// Number of dict writes never read by `QoiChunk::Index` (see `wasted_dict_writes()`).
+    pub wasted_dict_writes: usize,
+    /// Number of non-Index pixels which had been written into the dict before, but evicted.
+    pub missed_index_count: usize,
+    /// Length of the run each pixel belongs to, 0 for non-run pixels.
+    pub run_lengths: Vec<u16>,
+}
+
+impl AnalysisResult {
+    /// Summarizes the output of `QoiAnalyzer` over `img`.
+    pub fn new<I>(
+        img: &I,
+        filesize: usize,
+        chunks: Vec<QoiChunk>,
+        dict: PixelDict,
+        run_lengths: Vec<u16>,
+    ) -> Self
+    where
+        I: GenericImageView<Pixel = Rgba<u8>>,
+    {
+        let histogram = chunk_histogram(&chunks);
+
+        let mut index_hit_counts = [0; 64];
+        for ((_, _, rgba), &chunk) in img.pixels().zip(&chunks) {
+            if chunk == QoiChunk::Index {
+                index_hit_counts[usize::from(PixelDict::hash(QoiPixel::from(rgba)))] += 1;
+            }
+        }
+
+        let wasted_dict_writes = wasted_dict_writes(img, &chunks).len();
+
+        let mut missed_index_count = 0;
+        let mut writtens = HashSet::<QoiPixel>::new();
+        for ((_, _, rgba), &chunk) in img.pixels().zip(&chunks) {
+            if matches!(chunk.family(), ChunkFamily::Diff | ChunkFamily::Color) {
+                let px = QoiPixel::from(rgba);
+                if !writtens.insert(px) {
+                    missed_index_count += 1;
+                }
+            }
+        }
+
+        Self {
+            filesize,
+            chunks,
+            histogram,
+            index_hit_counts,
+            dict,
+            wasted_dict_writes,
+            missed_index_count,
+            run_lengths,
+        }
+    }
+}
+
+/// Returns the QOI file size in a hypothetical QOI with a dict of `1 << slot_bits` slots.
+///
+/// `chunks` must be the actual analysis of `img`. Index chunks are assumed to stay 1 byte.
+/// The hash is `(r ^ g ^ b ^ a)` masked to `slot_bits` bits (`slot_bits` <= 8).
+pub fn filesize_with_dict_bits<I>(img: &I, chunks: &[QoiChunk], slot_bits: u32) -> usize
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    debug_assert!(slot_bits <= 8);
+
+    // runs don't depend on the dict.
+    let bytes = chunk_bytes(chunks);
+    let mut filesize = QOI_HEADER_LEN
+        + bytes[QoiChunk::Run8 as usize]
+        + bytes[QoiChunk::Run16 as usize]
+        + QOI_PADDING_LEN;
+
+    let mask = ((1_u16 << slot_bits) - 1) as u8;
+    let mut dict = vec![QoiPixel::new(0, 0, 0, 0); 1 << slot_bits];
+    let mut px_prev = QoiPixel::default();
+    for ((_, _, rgba), &chunk) in img.pixels().zip(chunks) {
+        if chunk.family() == ChunkFamily::Run {
+            continue;
+        }
+        let px = QoiPixel::from(rgba);
+        let hash = usize::from((px.r() ^ px.g() ^ px.b() ^ px.a()) & mask);
+        if dict[hash] == px {
+            filesize += 1;
+        } else {
+            filesize += QoiChunk::of_delta(px, px_prev).byte_len();
+            dict[hash] = px;
+        }
+        px_prev = px;
+    }
+
+    filesize
+}
+
+/// Returns indices of pixels whose dict writes are wasted, in ascending order.
+///
+/// Every Diff/Color chunk writes its pixel into the dict. A write is wasted if the slot is
+/// overwritten, or the image ends, before any `QoiChunk::Index` reads it.
+pub fn wasted_dict_writes<I>(img: &I, chunks: &[QoiChunk]) -> Vec<usize>
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    // pixel index of the unread write for each slot.
+    let mut unreads = [None::<usize>; 64];
+    let mut wasteds = Vec::<usize>::new();
+
+    for (idx, ((_, _, rgba), &chunk)) in img.pixels().zip(chunks).enumerate() {
+        let slot = usize::from(PixelDict::hash(QoiPixel::from(rgba)));
+        match chunk.family() {
+            ChunkFamily::Run => {}
+            ChunkFamily::Index => unreads[slot] = None,
+            ChunkFamily::Diff | ChunkFamily::Color => {
+                if let Some(idx_prev) = unreads[slot].replace(idx) {
+                    wasteds.push(idx_prev);
+                }
+            }
+        }
+    }
+    wasteds.extend(unreads.iter().flatten());
+    wasteds.sort_unstable();
+
+    wasteds
+}
+
+/// Returns `(x, y, pixel, chunk)` of the pixel with the most expensive chunk, the first one in
+/// scan order among ties.
+///
+/// Returns `None` for empty images.
+pub fn most_expensive_pixel<I>(
+    img: &I,
+    chunks: &[QoiChunk],
+) -> Option<(u32, u32, QoiPixel, QoiChunk)>
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    most_expensive_pixels(img, chunks, 1).pop()
+}
+
+/// Returns up to `n` pixels with distinct colors as `most_expensive_pixel()` does, in descending
+/// order of chunk byte length. Ties are in scan order.
+pub fn most_expensive_pixels<I>(
+    img: &I,
+    chunks: &[QoiChunk],
+    n: usize,
+) -> Vec<(u32, u32, QoiPixel, QoiChunk)>
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    let len_max = QoiChunk::iter().map(QoiChunk::byte_len).max().unwrap_or(0);
+    let mut seen = HashSet::<QoiPixel>::new();
+    let mut res = Vec::with_capacity(n);
+
+    for len in (1..=len_max).rev() {
+        for ((x, y, rgba), &chunk) in img.pixels().zip(chunks) {
+            if res.len() == n {
+                return res;
+            }
+            let px = QoiPixel::from(rgba);
+            if chunk.byte_len() == len && seen.insert(px) {
+                res.push((x, y, px, chunk));
+            }
+        }
+    }
+
+    res
+}
+
+pub fn qoi_analyze<I>(img: &I) -> AnalysisResult
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    qoi_analyze_with_config(img, AnalyzerConfig::default())
+}
+
+/// Analyzes `img` in horizontal bands of `chunk_rows` rows (at least 1), lazily yielding one
+/// result per band from the top.
+///
+/// Each band is analyzed as if it were a separate image, starting from the initial encoder
+/// state. So the sum of `filesize` differs from `qoi_analyze()` of the whole image (headers and
+/// paddings are counted per band, and runs and dict hits across band boundaries are lost).
+pub fn qoi_analyze_chunked(
+    img: &RgbaImage,
+    chunk_rows: u32,
+) -> impl Iterator<Item = AnalysisResult> + '_ {
+    let chunk_rows = chunk_rows.max(1);
+
+    (0..img.height())
+        .step_by(chunk_rows as usize)
+        .map(move |y| {
+            let h = chunk_rows.min(img.height() - y);
+            qoi_analyze(&img.view(0, y, img.width(), h))
+        })
+}
+
+/// Like `qoi_analyze()`, but with encoder parameters deviating from the spec.
+///
+/// Per-slot statistics (`index_hit_counts`, `wasted_dict_writes`) still assume the spec dict.
+pub fn qoi_analyze_with_config<I>(img: &I, config: AnalyzerConfig) -> AnalysisResult
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    analyze(img, config, None)
+}
+
+/// Like `qoi_analyze()`, but starts from `initial_dict` instead of an empty dict, e.g. the final
+/// dict of a related image. This is not in the spec, only for experiments.
+pub fn simulate_with_initial_dict(img: &RgbaImage, initial_dict: &PixelDict) -> AnalysisResult {
+    analyze(img, AnalyzerConfig::default(), Some(initial_dict.clone()))
 }
 
-/// returns (filesize_qoi, chunks, histogram).
-pub fn qoi_analyze<I>(img: &I) -> (usize, Vec<QoiChunk>, [usize; QoiChunk::COUNT])
+/// Analyzes `img` with `config`, starting from `initial_dict` if any.
+fn analyze<I>(img: &I, config: AnalyzerConfig, initial_dict: Option<PixelDict>) -> AnalysisResult
 where
     I: GenericImageView<Pixel = Rgba<u8>>,
 {
@@ -47,118 +674,1546 @@ where
         .checked_mul(img.height() as usize)
         .expect("pixel count should not exceed usize::MAX");
 
-    let pixels = img.pixels().map(|(_, _, Rgba(rgba))| QoiPixel::from(rgba));
+    let pixels = img.pixels().map(|(_, _, rgba)| QoiPixel::from(rgba));
 
     let mut chunks = Vec::<QoiChunk>::with_capacity(pixel_count);
 
-    let mut enc = Analyzer::new(&mut chunks);
+    let mut enc = QoiAnalyzer::new(|chunk| chunks.push(chunk))
+        .with_config(config)
+        .with_run_lengths();
+    if let Some(dict) = initial_dict {
+        enc.dict = dict;
+    }
     for px in pixels {
         enc.update(px);
     }
-    let filesize = enc.finalize();
+    let dict = enc.dict().clone();
+    let (filesize, run_lengths) = enc.finalize_with_run_lengths();
+
+    AnalysisResult::new(img, filesize, chunks, dict, run_lengths)
+}
+
+/// Like `qoi_analyze()`, but returns `(filesize, (chunk, source pixel) pairs, histogram)`.
+///
+/// For run chunks, the source pixel is the repeated pixel. This takes twice the memory of chunks
+/// alone, so use it only when pixels are needed along with chunks.
+pub fn qoi_analyze_with_pixels<I>(
+    img: &I,
+) -> (usize, Vec<(QoiChunk, QoiPixel)>, [usize; QoiChunk::COUNT])
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    let to_pixel = |(_, _, rgba)| QoiPixel::from(rgba);
+
+    let mut pairs = Vec::with_capacity((img.width() as usize) * (img.height() as usize));
+    let mut histogram = [0; QoiChunk::COUNT];
+    // chunks are emitted in scan order, one per pixel, though runs are emitted late.
+    let mut sources = img.pixels().map(to_pixel);
+    let filesize = QoiAnalyzer::for_each(img.pixels().map(to_pixel), |chunk| {
+        let px = sources.next().expect("chunks should not outnumber pixels");
+        histogram[chunk as usize] += 1;
+        pairs.push((chunk, px));
+    });
+
+    (filesize, pairs, histogram)
+}
 
+/// Counts occurrences of each chunk type.
+pub fn chunk_histogram(chunks: &[QoiChunk]) -> [usize; QoiChunk::COUNT] {
     let mut histogram = [0; QoiChunk::COUNT];
-    for &chunk in &chunks {
+    for &chunk in chunks {
         histogram[chunk as usize] += 1;
     }
 
-    (filesize, chunks, histogram)
+    histogram
 }
 
-const RUN_MAX: u16 = 33 + 0x1FFF;
+/// Counts encoded bytes of each chunk type.
+///
+/// `chunks` must be a whole sequence generated by `QoiAnalyzer` (one chunk per pixel).
+pub fn chunk_bytes(chunks: &[QoiChunk]) -> [usize; QoiChunk::COUNT] {
+    let mut bytes = [0; QoiChunk::COUNT];
 
-#[derive(Debug)]
-struct Analyzer<'a> {
-    filesize: usize,
-    chunks: &'a mut Vec<QoiChunk>,
-    px_prev: QoiPixel,
-    dict: PixelDict,
-    run: u16,
+    for (chunk, _) in encoded_chunks(chunks) {
+        bytes[chunk as usize] += chunk.byte_len();
+    }
+
+    bytes
 }
 
-impl<'a> Analyzer<'a> {
-    fn new(chunks: &'a mut Vec<QoiChunk>) -> Self {
-        Analyzer {
-            filesize: QOI_HEADER_LEN + QOI_PADDING_LEN,
-            chunks,
-            px_prev: QoiPixel::new(0, 0, 0, 255),
-            dict: PixelDict::new(),
-            run: 0,
-        }
+/// Returns the fraction of the payload (file size without header and padding) taken by each
+/// chunk type, given encoded bytes of each chunk type (see `chunk_bytes()`).
+///
+/// Unlike `StaticImage::histogram_normalized()`, which is the fraction of pixels, this is the
+/// fraction of bytes: a run chunk covering many pixels counts only once. All zeros if the
+/// payload is empty.
+pub fn byte_cost_fractions(bytes_by_chunk: &[usize; QoiChunk::COUNT]) -> [f64; QoiChunk::COUNT] {
+    let payload: usize = bytes_by_chunk.iter().sum();
+    let payload = payload.max(1) as f64;
+
+    bytes_by_chunk.map(|bytes| (bytes as f64) / payload)
+}
+
+/// Estimates the entropy rate (in bits per chunk) of `chunks` as the joint entropy of
+/// `window` consecutive chunks divided by `window`.
+///
+/// Windows overlap. `window == 1` gives the zeroth-order entropy, and the estimate approaches
+/// the true entropy rate as `window` grows. 0 if `chunks` is shorter than `window`.
+pub fn chunk_block_entropy_rate(chunks: &[QoiChunk], window: usize) -> f64 {
+    if window == 0 || chunks.len() < window {
+        return 0.;
     }
 
-    fn update(&mut self, px: QoiPixel) {
-        if px == self.px_prev {
-            self.run += 1;
-            if self.run == RUN_MAX {
-                self.flush_run();
-            }
-            return;
-        }
+    let mut counts = vec![0; QoiChunk::COUNT.pow(window as u32)];
+    for block in chunks.windows(window) {
+        let idx = block
+            .iter()
+            .fold(0, |acc, &chunk| acc * QoiChunk::COUNT + chunk as usize);
+        counts[idx] += 1;
+    }
 
-        self.flush_run();
+    crate::stats::entropy(&counts) / window as f64
+}
 
-        let hash = PixelDict::hash(px);
+/// Entropy rate (in bits per chunk) of `chunks`, estimated from pairs of consecutive chunks
+/// (see `chunk_block_entropy_rate()`).
+///
+/// Low values mean an entropy coder over the chunk stream could compress it further.
+pub fn chunk_sequence_entropy_rate(chunks: &[QoiChunk]) -> f64 {
+    chunk_block_entropy_rate(chunks, 2)
+}
 
-        if px == self.dict[hash] {
-            self.filesize += 1;
-            self.chunks.push(QoiChunk::Index);
-        } else {
-            let chunk = match px.sub(self.px_prev) {
-                DiffOrColor::Diff(PixelDiff::Diff8(_)) => {
-                    self.filesize += 1;
-                    QoiChunk::Diff8
-                }
-                DiffOrColor::Diff(PixelDiff::Diff16(_)) => {
-                    self.filesize += 2;
-                    QoiChunk::Diff16
-                }
-                DiffOrColor::Diff(PixelDiff::Diff24 { .. }) => {
-                    self.filesize += 3;
-                    QoiChunk::Diff24
-                }
-                DiffOrColor::Color(mask) => {
-                    let n = mask.count_ones();
-                    self.filesize += (n as usize) + 1;
-                    match n {
-                        1 => QoiChunk::Color1,
-                        2 => QoiChunk::Color2,
-                        3 => QoiChunk::Color3,
-                        4 => QoiChunk::Color4,
-                        _ => unreachable!(),
-                    }
-                }
-            };
-            self.chunks.push(chunk);
+/// Iterates over encoded chunks, yielding `(chunk, pixel range covered by the chunk)`.
+///
+/// `chunks` must be a whole sequence generated by `QoiAnalyzer` (one chunk per pixel).
+pub fn encoded_chunks(chunks: &[QoiChunk]) -> impl Iterator<Item = (QoiChunk, Range<usize>)> + '_ {
+    let mut start = 0;
 
-            self.dict[hash] = px;
-        }
+    chunks.chunk_by(|a, b| a == b).flat_map(move |seg| {
+        let chunk = seg[0];
+        let piece_len = match chunk {
+            // a QOI_RUN_8 run never reaches `RUN_MAX` (see `AnalyzerConfig::run8_max`), so it
+            // ends with a different pixel and is never followed by another QOI_RUN_8 run.
+            QoiChunk::Run8 => seg.len(),
+            // QOI_RUN_16 runs are split only when reaching `RUN_MAX`.
+            QoiChunk::Run16 => usize::from(RUN_MAX),
+            _ => 1,
+        };
+        let seg_start = start;
+        start += seg.len();
 
-        self.px_prev = px;
+        (seg_start..seg_start + seg.len())
+            .step_by(piece_len)
+            .map(move |i| (chunk, i..(i + piece_len).min(seg_start + seg.len())))
+    })
+}
+
+/// Returns the index of the encoded chunk covering each pixel, i.e. the position in
+/// `encoded_chunks()`.
+pub fn chunk_indices(chunks: &[QoiChunk]) -> Vec<usize> {
+    let mut indices = Vec::with_capacity(chunks.len());
+    for (i, (_, pixels)) in encoded_chunks(chunks).enumerate() {
+        indices.extend(std::iter::repeat_n(i, pixels.len()));
     }
 
-    fn finalize(mut self) -> usize {
-        self.flush_run();
+    indices
+}
 
-        self.filesize
+/// Returns `(chunk, first pixel)` of the chunk containing byte `offset` of the QOI file.
+///
+/// Returns `None` if `offset` is in the header or the padding.
+pub fn chunk_at_byte_offset(chunks: &[QoiChunk], offset: usize) -> Option<(QoiChunk, usize)> {
+    let mut pos = QOI_HEADER_LEN;
+
+    for (chunk, pixels) in encoded_chunks(chunks) {
+        pos += chunk.byte_len();
+        if offset < pos {
+            return (offset >= QOI_HEADER_LEN).then_some((chunk, pixels.start));
+        }
     }
 
-    fn flush_run(&mut self) {
-        match self.run {
-            0 => {}
-            1..=32 => {
-                self.filesize += 1;
-                self.chunks
-                    .extend(std::iter::repeat(QoiChunk::Run8).take(usize::from(self.run)))
-            }
-            33..=RUN_MAX => {
-                self.filesize += 2;
-                self.chunks
-                    .extend(std::iter::repeat(QoiChunk::Run16).take(usize::from(self.run)))
-            }
-            _ => unreachable!(),
+    None
+}
+
+/// Returns the byte offset in the QOI file of the chunk covering pixel `idx`.
+///
+/// Inverse of `chunk_at_byte_offset()`. Returns `None` if `idx` is out of the image.
+pub fn chunk_byte_offset(chunks: &[QoiChunk], idx: usize) -> Option<usize> {
+    let mut pos = QOI_HEADER_LEN;
+
+    for (chunk, pixels) in encoded_chunks(chunks) {
+        if pixels.contains(&idx) {
+            return Some(pos);
         }
+        pos += chunk.byte_len();
+    }
 
-        self.run = 0;
+    None
+}
+
+/// Maximum run length of a single run chunk.
+pub const RUN_MAX: u16 = 33 + 0x1FFF;
+
+/// Maximum run length of `QoiChunk::Run8`.
+pub const RUN8_MAX: usize = 32;
+
+/// Returns the cheapest chunk encoding `px` after `prev` with `dict`, and its byte length.
+///
+/// Runs are not considered, as if the pending run had just been flushed. So `px == prev` gives
+/// a zero `QOI_DIFF_8` unless `px` is in the dict.
+pub fn optimal_chunk_size(px: QoiPixel, prev: QoiPixel, dict: &PixelDict) -> (QoiChunk, usize) {
+    let chunk = if px == dict[dict.slot(px)] {
+        QoiChunk::Index
+    } else {
+        QoiChunk::of_diff_or_color(px.sub(prev))
+    };
+
+    (chunk, chunk.byte_len())
+}
+
+/// Returns the bit layout of `chunk` and what the fields mean, e.g.
+/// `"10rrggbb: dr, dg, db in -2..=1"`.
+pub fn opcode_description(chunk: QoiChunk) -> &'static str {
+    match chunk {
+        QoiChunk::Index => "00iiiiii: dict slot i",
+        QoiChunk::Run8 => "010rrrrr: run length r + 1",
+        QoiChunk::Run16 => "011rrrrr rrrrrrrr: run length r + 33",
+        QoiChunk::Diff8 => "10rrggbb: dr, dg, db in -2..=1",
+        QoiChunk::Diff16 => "110rrrrr ggggbbbb: dr in -16..=15, dg, db in -8..=7",
+        QoiChunk::Diff24 => "1110rrrr rgggggbb bbbaaaaa: dr, dg, db, da in -16..=15",
+        QoiChunk::Color1 => "1111mmmm cccccccc: channel mask m (RGBA), then new channel values",
+        QoiChunk::Color2 => {
+            "1111mmmm cccccccc cccccccc: channel mask m (RGBA), then new channel values"
+        }
+        QoiChunk::Color3 => {
+            "1111mmmm cccccccc cccccccc cccccccc: channel mask m (RGBA), then new channel values"
+        }
+        QoiChunk::Color4 => {
+            "1111mmmm cccccccc cccccccc cccccccc cccccccc: channel mask m (RGBA), then new channel values"
+        }
+    }
+}
+
+/// Returns a rough lower bound of the QOI file size under optimal pixel ordering.
+///
+/// All run pixels are merged into as few runs as possible, and the other chunks remain as-is
+/// (Index hits are assumed to stay available). This is not achievable in practice.
+pub fn best_case_filesize(histogram: &[usize; QoiChunk::COUNT]) -> usize {
+    let run_pixels = histogram[QoiChunk::Run8 as usize] + histogram[QoiChunk::Run16 as usize];
+    let run_max = usize::from(RUN_MAX);
+    let run_rest = run_pixels % run_max;
+    let run_bytes = 2 * (run_pixels / run_max)
+        + match run_rest {
+            0 => 0,
+            1..=RUN8_MAX => 1,
+            _ => 2,
+        };
+
+    let other_bytes: usize = QoiChunk::iter()
+        .filter(|chunk| chunk.family() != ChunkFamily::Run)
+        .map(|chunk| histogram[chunk as usize] * chunk.byte_len())
+        .sum();
+
+    QOI_HEADER_LEN + run_bytes + other_bytes + QOI_PADDING_LEN
+}
+
+/// Result of `qoi_analyze_png_vs_qoi_runs()`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RunComparison {
+    /// Bytes of QOI run chunks.
+    pub qoi_run_bytes: usize,
+    /// Pixels covered by QOI run chunks.
+    pub qoi_run_pixels: usize,
+    /// Rough estimate of bytes spent by PNG (deflate) on runs of zero filter residuals.
+    pub png_estimated_run_bytes: usize,
+    /// Pixels with zero residuals under the PNG filter chosen for each scanline.
+    pub png_run_pixels: usize,
+}
+
+/// Compares QOI runs (repeats of the previous pixel) with runs of zero residuals under PNG
+/// scanline filters.
+///
+/// For each scanline, the PNG filter "sub" (left pixel) or "up" (above pixel) yielding more
+/// zero residual pixels is chosen. A maximal run of zero residuals is assumed to cost one
+/// 3-byte deflate match per 258 bytes. This ignores the rest of deflate, so it only tells
+/// whether scanline prediction finds more repetition than QOI runs.
+///
+/// `chunks` must be the analysis of `img` (see `qoi_analyze()`).
+pub fn qoi_analyze_png_vs_qoi_runs(img: &RgbaImage, chunks: &[QoiChunk]) -> RunComparison {
+    const MATCH_LEN_MAX: usize = 258;
+    const MATCH_BYTES: usize = 3;
+
+    let bytes = chunk_bytes(chunks);
+    let histogram = chunk_histogram(chunks);
+
+    // (zero residual pixels, estimated bytes) of a scanline.
+    let zero_runs = |residual_zeros: &mut dyn Iterator<Item = bool>| {
+        let mut pixels = 0;
+        let mut run_bytes = 0;
+        let mut flush = |len: usize| {
+            pixels += len;
+            run_bytes += MATCH_BYTES * (4 * len).div_ceil(MATCH_LEN_MAX);
+        };
+        let mut len = 0;
+        for is_zero in residual_zeros {
+            if is_zero {
+                len += 1;
+            } else {
+                flush(len);
+                len = 0;
+            }
+        }
+        flush(len);
+        (pixels, run_bytes)
+    };
+
+    let zero_px = Rgba([0; 4]);
+    let mut png_run_pixels = 0;
+    let mut png_estimated_run_bytes = 0;
+    for y in 0..img.height() {
+        let row = || (0..img.width()).map(move |x| img.get_pixel(x, y));
+        let lefts = std::iter::once(&zero_px).chain(row());
+        let aboves = (0..img.width()).map(|x| match y {
+            0 => &zero_px,
+            _ => img.get_pixel(x, y - 1),
+        });
+        let sub = zero_runs(&mut row().zip(lefts).map(|(px, left)| px == left));
+        let up = zero_runs(&mut row().zip(aboves).map(|(px, above)| px == above));
+
+        let (pixels, run_bytes) = if up.0 > sub.0 { up } else { sub };
+        png_run_pixels += pixels;
+        png_estimated_run_bytes += run_bytes;
+    }
+
+    RunComparison {
+        qoi_run_bytes: bytes[QoiChunk::Run8 as usize] + bytes[QoiChunk::Run16 as usize],
+        qoi_run_pixels: histogram[QoiChunk::Run8 as usize] + histogram[QoiChunk::Run16 as usize],
+        png_estimated_run_bytes,
+        png_run_pixels,
+    }
+}
+
+/// Rough kinds of images, which differ in typical QOI compression.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter)]
+pub enum ImageClass {
+    Photo,
+    Artwork,
+    Screenshot,
+    Gradient,
+    Noise,
+}
+
+impl ImageClass {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Photo => "photo",
+            Self::Artwork => "artwork",
+            Self::Screenshot => "screenshot",
+            Self::Gradient => "gradient",
+            Self::Noise => "noise",
+        }
+    }
+
+    /// Guesses the class of an image by simple heuristics.
+    ///
+    /// `spatial_entropy` is the average pixel delta to the left neighbor (see
+    /// `stats::spatial_entropy()`).
+    pub fn detect(
+        histogram: &[usize; QoiChunk::COUNT],
+        distinct_colors: usize,
+        spatial_entropy: f64,
+    ) -> Self {
+        // about 340 for uniformly random RGB.
+        const NOISE_ENTROPY_MIN: f64 = 150.;
+        const GRADIENT_ENTROPY_MAX: f64 = 4.;
+        const SCREENSHOT_RUN_FRACTION_MIN: f64 = 0.5;
+        const SCREENSHOT_COLORS_MAX: usize = 256;
+        const PHOTO_COLOR_FRACTION_MIN: f64 = 0.2;
+
+        let pixel_count = histogram.iter().sum::<usize>().max(1) as f64;
+        let run_pixels = histogram[QoiChunk::Run8 as usize] + histogram[QoiChunk::Run16 as usize];
+        let run_fraction = run_pixels as f64 / pixel_count;
+
+        if spatial_entropy >= NOISE_ENTROPY_MIN {
+            Self::Noise
+        } else if run_fraction >= SCREENSHOT_RUN_FRACTION_MIN {
+            Self::Screenshot
+        } else if spatial_entropy <= GRADIENT_ENTROPY_MAX {
+            Self::Gradient
+        } else if distinct_colors <= SCREENSHOT_COLORS_MAX {
+            Self::Screenshot
+        } else if distinct_colors as f64 / pixel_count >= PHOTO_COLOR_FRACTION_MIN {
+            Self::Photo
+        } else {
+            Self::Artwork
+        }
+    }
+}
+
+/// Returns the typical `(min, max)` of QOI size / raw RGBA size for `class`.
+///
+/// Only meant as a context for the actual ratio. Provisional estimates, to be replaced by the
+/// output of `stats::corpus::gen_expected_filesize()` run on the QOI benchmark suite
+/// (<https://qoiformat.org/benchmark/>).
+pub fn expected_filesize_for_image_class(class: ImageClass) -> (f64, f64) {
+    match class {
+        ImageClass::Photo => (0.40, 0.70),
+        ImageClass::Artwork => (0.15, 0.40),
+        ImageClass::Screenshot => (0.02, 0.25),
+        ImageClass::Gradient => (0.05, 0.30),
+        ImageClass::Noise => (0.90, 1.25),
+    }
+}
+
+/// Encoder parameters of `QoiAnalyzer`. This is not in the spec, only for experiments.
+///
+/// The default is the spec behavior.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AnalyzerConfig {
+    /// Runs up to this length use `QoiChunk::Run8`, longer ones `QoiChunk::Run16`.
+    /// Clamped to `1..RUN_MAX` so that runs split at `RUN_MAX` are always `QoiChunk::Run16`.
+    pub run8_max: u16,
+    /// Number of dict slots. Must be a power of 2 up to `PixelDict::SIZE_MAX`.
+    /// `QoiChunk::Index` is assumed to stay 1 byte.
+    pub dict_size: usize,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            run8_max: RUN8_MAX as u16,
+            dict_size: 64,
+        }
+    }
+}
+
+/// Incremental QOI analyzer. Feed pixels by `update()` and call `finalize()` at last.
+#[derive(Debug)]
+pub struct QoiAnalyzer<F> {
+    filesize: usize,
+    emit: F,
+    px_prev: QoiPixel,
+    dict: PixelDict,
+    run: u16,
+    /// `run8_max` is clamped.
+    config: AnalyzerConfig,
+    run_lengths: Option<Vec<u16>>,
+    /// Number of dict writes so far, used as the write time of the dict.
+    dict_writes: usize,
+}
+
+/// State of `QoiAnalyzer` at some point, excluding emitted chunks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AnalyzerSnapshot {
+    filesize: usize,
+    px_prev: QoiPixel,
+    dict: PixelDict,
+    run: u16,
+    dict_writes: usize,
+    config: AnalyzerConfig,
+}
+
+impl AnalyzerSnapshot {
+    /// QOI file size so far (including header and padding).
+    pub fn filesize(&self) -> usize {
+        self.filesize
+    }
+
+    /// Previous pixel, i.e. the last pixel fed.
+    pub fn px_prev(&self) -> QoiPixel {
+        self.px_prev
+    }
+
+    /// Number of pixels in the pending run, whose chunks are not emitted yet.
+    pub fn run(&self) -> u16 {
+        self.run
+    }
+
+    pub fn dict(&self) -> &PixelDict {
+        &self.dict
+    }
+
+    /// Returns whether analyzers resumed from `self` and `other` emit the same chunks for the same pixels.
+    pub fn is_equivalent(&self, other: &Self) -> bool {
+        self.px_prev == other.px_prev
+            && self.dict == other.dict
+            && self.run == other.run
+            && self.config == other.config
+    }
+}
+
+impl<F> QoiAnalyzer<F>
+where
+    F: FnMut(QoiChunk),
+{
+    /// Creates an analyzer which calls `emit` with a chunk for each pixel.
+    pub fn new(emit: F) -> Self {
+        Self::with_initial_pixel(QoiPixel::default(), emit)
+    }
+
+    /// Like `new()`, but starts from previous pixel `px_initial` instead of the spec one.
+    pub fn with_initial_pixel(px_initial: QoiPixel, emit: F) -> Self {
+        Self {
+            // header and padding are always present.
+            filesize: QOI_MIN_FILESIZE,
+            emit,
+            px_prev: px_initial,
+            dict: PixelDict::new(),
+            run: 0,
+            config: AnalyzerConfig::default(),
+            run_lengths: None,
+            dict_writes: 0,
+        }
+    }
+
+    /// Makes this analyzer use `config` instead of the spec parameters.
+    pub fn with_config(mut self, config: AnalyzerConfig) -> Self {
+        self.config = AnalyzerConfig {
+            run8_max: config.run8_max.clamp(1, RUN_MAX - 1),
+            ..config
+        };
+        self.dict = PixelDict::with_size(config.dict_size);
+        self
+    }
+
+    /// Makes this analyzer record the run length of each pixel (see `finalize_with_run_lengths()`).
+    pub fn with_run_lengths(mut self) -> Self {
+        self.run_lengths = Some(Vec::new());
+        self
+    }
+
+    /// Analyzes `pixels`, calling `f` with a chunk for each pixel without buffering them.
+    ///
+    /// Returns the QOI file size.
+    pub fn for_each(pixels: impl Iterator<Item = QoiPixel>, f: F) -> usize {
+        let mut enc = Self::new(f);
+        for px in pixels {
+            enc.update(px);
+        }
+        enc.finalize()
+    }
+
+    /// Creates an analyzer resuming from `snapshot`.
+    pub fn from_snapshot(snapshot: &AnalyzerSnapshot, emit: F) -> Self {
+        Self {
+            filesize: snapshot.filesize,
+            emit,
+            px_prev: snapshot.px_prev,
+            dict: snapshot.dict.clone(),
+            run: snapshot.run,
+            config: snapshot.config,
+            run_lengths: None,
+            dict_writes: snapshot.dict_writes,
+        }
+    }
+
+    pub fn dict(&self) -> &PixelDict {
+        &self.dict
+    }
+
+    /// Resets the dict. This is not in the spec, only for experiments.
+    pub fn clear_dict(&mut self) {
+        self.dict.clear();
+    }
+
+    /// Returns the current state of this analyzer.
+    pub fn snapshot(&self) -> AnalyzerSnapshot {
+        AnalyzerSnapshot {
+            filesize: self.filesize,
+            px_prev: self.px_prev,
+            dict: self.dict.clone(),
+            run: self.run,
+            dict_writes: self.dict_writes,
+            config: self.config,
+        }
+    }
+
+    pub fn update(&mut self, px: QoiPixel) {
+        if px == self.px_prev {
+            self.run += 1;
+            if self.run == RUN_MAX {
+                self.flush_run();
+            }
+            return;
+        }
+
+        self.flush_run();
+        if let Some(run_lengths) = &mut self.run_lengths {
+            run_lengths.push(0);
+        }
+
+        let (chunk, size) = optimal_chunk_size(px, self.px_prev, &self.dict);
+        self.filesize += size;
+        (self.emit)(chunk);
+        if chunk != QoiChunk::Index {
+            self.dict_writes += 1;
+            self.dict.write(px, self.dict_writes);
+        }
+
+        self.px_prev = px;
+    }
+
+    /// Flushes the pending run and returns the QOI file size.
+    pub fn finalize(mut self) -> usize {
+        self.flush_run();
+
+        self.filesize
+    }
+
+    /// Like `finalize()`, but also returns the run length of each pixel (0 for non-run pixels).
+    ///
+    /// Run lengths are empty unless `with_run_lengths()` was called.
+    pub fn finalize_with_run_lengths(mut self) -> (usize, Vec<u16>) {
+        self.flush_run();
+
+        (self.filesize, self.run_lengths.take().unwrap_or_default())
+    }
+
+    fn flush_run(&mut self) {
+        match self.run {
+            0 => {}
+            run if run <= self.config.run8_max => {
+                self.filesize += 1;
+                for _ in 0..run {
+                    (self.emit)(QoiChunk::Run8);
+                }
+            }
+            run => {
+                self.filesize += 2;
+                for _ in 0..run {
+                    (self.emit)(QoiChunk::Run16);
+                }
+            }
+        }
+        if let Some(run_lengths) = &mut self.run_lengths {
+            run_lengths.extend(std::iter::repeat_n(self.run, usize::from(self.run)));
+        }
+
+        self.run = 0;
+    }
+}
+
+/// `QoiAnalyzer` emitting into a queue shared with `DiffAnalyzer`.
+type QueuedAnalyzer = QoiAnalyzer<Box<dyn FnMut(QoiChunk)>>;
+
+/// Pair of `QoiAnalyzer`s with different configs fed the same pixels, yielding the chunks of
+/// both for each pixel without storing whole chunk sequences.
+pub struct DiffAnalyzer {
+    primary: QueuedAnalyzer,
+    reference: QueuedAnalyzer,
+    /// Chunks emitted by `primary` and `reference` but not yet paired.
+    queues: [Rc<RefCell<VecDeque<QoiChunk>>>; 2],
+}
+
+impl DiffAnalyzer {
+    pub fn new(primary: AnalyzerConfig, reference: AnalyzerConfig) -> Self {
+        let queues = [
+            Rc::new(RefCell::new(VecDeque::new())),
+            Rc::new(RefCell::new(VecDeque::new())),
+        ];
+        let analyzer = |config, queue: &Rc<RefCell<VecDeque<QoiChunk>>>| {
+            let queue = Rc::clone(queue);
+            let emit: Box<dyn FnMut(QoiChunk)> =
+                Box::new(move |chunk| queue.borrow_mut().push_back(chunk));
+            QoiAnalyzer::new(emit).with_config(config)
+        };
+
+        Self {
+            primary: analyzer(primary, &queues[0]),
+            reference: analyzer(reference, &queues[1]),
+            queues,
+        }
+    }
+
+    /// Feeds `px` to both analyzers and returns the `(primary, reference)` chunk pairs
+    /// determined by it, in scan order.
+    ///
+    /// Chunks of a run are determined when the run ends, so the pairs may be of earlier pixels,
+    /// or there may be none.
+    pub fn push(&mut self, px: QoiPixel) -> impl Iterator<Item = (QoiChunk, QoiChunk)> + '_ {
+        self.primary.update(px);
+        self.reference.update(px);
+
+        std::iter::from_fn(move || pop_pair(&self.queues))
+    }
+
+    /// Flushes pending runs and returns the QOI file sizes of `(primary, reference)` and the
+    /// remaining chunk pairs.
+    pub fn finalize(self) -> (usize, usize, Vec<(QoiChunk, QoiChunk)>) {
+        let filesize_primary = self.primary.finalize();
+        let filesize_reference = self.reference.finalize();
+        let mut pairs = Vec::new();
+        while let Some(pair) = pop_pair(&self.queues) {
+            pairs.push(pair);
+        }
+
+        (filesize_primary, filesize_reference, pairs)
+    }
+}
+
+fn pop_pair(queues: &[Rc<RefCell<VecDeque<QoiChunk>>>; 2]) -> Option<(QoiChunk, QoiChunk)> {
+    let mut primary = queues[0].borrow_mut();
+    let mut reference = queues[1].borrow_mut();
+    if primary.is_empty() || reference.is_empty() {
+        return None;
+    }
+
+    Some((primary.pop_front()?, reference.pop_front()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image_from_pixels(pixels: &[[u8; 4]]) -> RgbaImage {
+        let buf: Vec<u8> = pixels.iter().flatten().copied().collect();
+        RgbaImage::from_vec(pixels.len() as u32, 1, buf).unwrap()
+    }
+
+    #[test]
+    fn test_chunk_block_entropy_rate() {
+        let alternating: Vec<_> = [QoiChunk::Index, QoiChunk::Diff8].repeat(50);
+        assert_eq!(chunk_block_entropy_rate(&alternating, 1), 1.);
+        assert!((chunk_sequence_entropy_rate(&alternating) - 0.5).abs() < 1e-3);
+        assert!((chunk_block_entropy_rate(&alternating, 3) - 1. / 3.).abs() < 1e-12);
+
+        assert_eq!(chunk_sequence_entropy_rate(&[QoiChunk::Run8; 10]), 0.);
+        assert_eq!(chunk_sequence_entropy_rate(&[QoiChunk::Index]), 0.);
+    }
+
+    #[test]
+    fn test_byte_cost_fractions() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let mut bytes = [0; QoiChunk::COUNT];
+            bytes
+                .iter_mut()
+                .for_each(|x| *x = rng.gen_range(0, 1_000_000));
+            bytes[rng.gen_range(0, QoiChunk::COUNT)] += 1;
+
+            let sum: f64 = byte_cost_fractions(&bytes).iter().sum();
+            assert!((sum - 1.).abs() < 1e-9, "{:?}", bytes);
+        }
+
+        assert_eq!(
+            byte_cost_fractions(&[0; QoiChunk::COUNT]),
+            [0.; QoiChunk::COUNT]
+        );
+    }
+
+    #[test]
+    fn test_qoi_magic() {
+        assert_eq!(QOI_MAGIC, [0x71, 0x6F, 0x69, 0x66]);
+    }
+
+    #[test]
+    fn test_qoi_min_filesize() {
+        assert_eq!(QOI_MIN_FILESIZE, 18);
+        assert_eq!(QoiAnalyzer::new(|_| {}).finalize(), QOI_MIN_FILESIZE);
+    }
+
+    #[test]
+    fn test_qoi_header() {
+        let buf = qoi_header_bytes(0x1234, 5, 4, 1);
+        assert_eq!(
+            buf,
+            [b'q', b'o', b'i', b'f', 0, 0, 0x12, 0x34, 0, 0, 0, 5, 4, 1]
+        );
+
+        let header = parse_qoi_header(&buf).unwrap();
+        assert_eq!(
+            header,
+            QoiHeader {
+                width: 0x1234,
+                height: 5,
+                channels: 4,
+                colorspace: 1
+            }
+        );
+
+        assert!(parse_qoi_header(&buf[..13]).is_err());
+        let mut bad = buf;
+        bad[0] = b'Q';
+        assert!(parse_qoi_header(&bad).is_err());
+        assert!(parse_qoi_header(&qoi_header_bytes(1, 1, 2, 0)).is_err());
+        assert!(parse_qoi_header(&qoi_header_bytes(1, 1, 3, 2)).is_err());
+    }
+
+    #[test]
+    fn test_chunk_bytes() {
+        let black = [0, 0, 0, 255];
+        let red = [255, 0, 0, 255];
+
+        let cases = [
+            vec![black; 5],
+            vec![black; 40],
+            vec![black; usize::from(RUN_MAX)],
+            vec![black; usize::from(RUN_MAX) + 10],
+            vec![black; usize::from(RUN_MAX) + 40],
+            vec![black; 2 * usize::from(RUN_MAX) + 1],
+            [vec![red; 3], vec![black; 50], vec![red; 1]].concat(),
+            vec![[1, 2, 3, 4], [200, 100, 50, 25], [1, 2, 3, 4], [0, 0, 0, 0]],
+        ];
+
+        for pixels in &cases {
+            let img = image_from_pixels(pixels);
+            let AnalysisResult {
+                filesize, chunks, ..
+            } = qoi_analyze(&img);
+            let bytes = chunk_bytes(&chunks);
+            assert_eq!(
+                QOI_HEADER_LEN + QOI_PADDING_LEN + bytes.iter().sum::<usize>(),
+                filesize
+            );
+
+            let sum: f64 = byte_cost_fractions(&bytes).iter().sum();
+            assert!((sum - 1.).abs() < 1e-9);
+
+            let covered: usize = encoded_chunks(&chunks)
+                .map(|(_, pixels)| pixels.len())
+                .sum();
+            assert_eq!(covered, pixels.len());
+        }
+    }
+
+    #[test]
+    fn test_chunk_at_byte_offset() {
+        let black = [0, 0, 0, 255];
+        let red = [100, 0, 0, 255];
+
+        // QOI_COLOR (2-Bytes), QOI_RUN_8 (2 pixels), QOI_COLOR (2-Bytes)
+        let img = image_from_pixels(&[red, red, red, black]);
+        let chunks = qoi_analyze(&img).chunks;
+
+        let h = QOI_HEADER_LEN;
+        assert_eq!(chunk_at_byte_offset(&chunks, 0), None);
+        assert_eq!(chunk_at_byte_offset(&chunks, h - 1), None);
+        assert_eq!(
+            chunk_at_byte_offset(&chunks, h),
+            Some((QoiChunk::Color1, 0))
+        );
+        assert_eq!(
+            chunk_at_byte_offset(&chunks, h + 1),
+            Some((QoiChunk::Color1, 0))
+        );
+        assert_eq!(
+            chunk_at_byte_offset(&chunks, h + 2),
+            Some((QoiChunk::Run8, 1))
+        );
+        assert_eq!(
+            chunk_at_byte_offset(&chunks, h + 3),
+            Some((QoiChunk::Color1, 3))
+        );
+        assert_eq!(chunk_at_byte_offset(&chunks, h + 5), None);
+
+        assert_eq!(chunk_byte_offset(&chunks, 0), Some(h));
+        assert_eq!(chunk_byte_offset(&chunks, 1), Some(h + 2));
+        assert_eq!(chunk_byte_offset(&chunks, 2), Some(h + 2));
+        assert_eq!(chunk_byte_offset(&chunks, 3), Some(h + 3));
+        assert_eq!(chunk_byte_offset(&chunks, 4), None);
+    }
+
+    #[test]
+    fn test_index_hit_counts() {
+        const A: [u8; 4] = [100, 0, 0, 255];
+        const B: [u8; 4] = [0, 0, 50, 255];
+
+        let img = image_from_pixels(&[A, B, A, B, A]);
+        let res = qoi_analyze(&img);
+
+        let slot_a = PixelDict::hash(QoiPixel::from(A));
+        let slot_b = PixelDict::hash(QoiPixel::from(B));
+        assert_eq!(res.histogram[QoiChunk::Index as usize], 3);
+        assert_eq!(res.index_hit_counts[usize::from(slot_a)], 2);
+        assert_eq!(res.index_hit_counts[usize::from(slot_b)], 1);
+        assert_eq!(res.index_hit_counts.iter().sum::<usize>(), 3);
+        assert_eq!(res.dict[slot_a], QoiPixel::from(A));
+    }
+
+    #[test]
+    fn test_wasted_dict_writes() {
+        const A: [u8; 4] = [100, 0, 0, 255];
+        const B: [u8; 4] = [0, 0, 50, 255];
+        // same slot as `A`.
+        const C: [u8; 4] = [0, 100, 0, 255];
+
+        // A: written, then read.
+        // B: written, never read.
+        // C: overwrites A, then overwritten by A.
+        // A: written, never read.
+        let img = image_from_pixels(&[A, B, A, C, A]);
+        let res = qoi_analyze(&img);
+
+        assert_eq!(wasted_dict_writes(&img, &res.chunks), vec![1, 3, 4]);
+        assert_eq!(res.wasted_dict_writes, 3);
+    }
+
+    #[test]
+    fn test_most_expensive_pixels() {
+        const A: [u8; 4] = [100, 0, 0, 255];
+        const B: [u8; 4] = [0, 50, 60, 0];
+
+        // Color1, Diff24, Color4, Index, Color4
+        let img = image_from_pixels(&[A, [101, 0, 10, 255], B, A, [1, 2, 3, 4]]);
+        let res = qoi_analyze(&img);
+
+        assert_eq!(
+            most_expensive_pixel(&img, &res.chunks),
+            Some((2, 0, QoiPixel::from(B), QoiChunk::Color4))
+        );
+        let tops: Vec<_> = most_expensive_pixels(&img, &res.chunks, 4)
+            .iter()
+            .map(|&(x, _, _, chunk)| (x, chunk))
+            .collect();
+        assert_eq!(
+            tops,
+            [
+                (2, QoiChunk::Color4),
+                (4, QoiChunk::Color4),
+                (1, QoiChunk::Diff24),
+                (0, QoiChunk::Color1)
+            ]
+        );
+
+        assert_eq!(most_expensive_pixel(&RgbaImage::new(0, 0), &[]), None);
+    }
+
+    #[test]
+    fn test_optimal_chunk_size() {
+        let prev = QoiPixel::new(100, 100, 100, 255);
+        let in_dict = QoiPixel::new(10, 20, 30, 40);
+        let mut dict = PixelDict::new();
+        dict[PixelDict::hash(in_dict)] = in_dict;
+
+        let cases = [
+            (in_dict, (QoiChunk::Index, 1)),
+            (prev, (QoiChunk::Diff8, 1)),
+            (QoiPixel::new(98, 101, 100, 255), (QoiChunk::Diff8, 1)),
+            (QoiPixel::new(84, 107, 92, 255), (QoiChunk::Diff16, 2)),
+            (QoiPixel::new(115, 90, 100, 240), (QoiChunk::Diff24, 3)),
+            (QoiPixel::new(200, 100, 100, 255), (QoiChunk::Color1, 2)),
+            (QoiPixel::new(200, 0, 100, 255), (QoiChunk::Color2, 3)),
+            (QoiPixel::new(200, 0, 0, 255), (QoiChunk::Color3, 4)),
+            (QoiPixel::new(200, 0, 0, 0), (QoiChunk::Color4, 5)),
+            (QoiPixel::new(100, 100, 100, 254), (QoiChunk::Color1, 2)),
+        ];
+        for (px, expected) in cases {
+            assert_eq!(optimal_chunk_size(px, prev, &dict), expected, "{:?}", px);
+        }
+
+        // the dict takes precedence over diffs.
+        dict[PixelDict::hash(prev)] = prev;
+        assert_eq!(optimal_chunk_size(prev, prev, &dict), (QoiChunk::Index, 1));
+    }
+
+    #[test]
+    fn test_theoretical_minimum_bits() {
+        // all the overhead is in the tag.
+        for chunk in QoiChunk::iter() {
+            assert_eq!(
+                chunk.coding_overhead_bits(),
+                chunk.opcode_bits(),
+                "{:?}",
+                chunk
+            );
+        }
+    }
+
+    #[test]
+    fn test_byte_cost_bounds() {
+        let overhead = QOI_HEADER_LEN + QOI_PADDING_LEN;
+        let run_max = usize::from(RUN_MAX);
+
+        assert_eq!(QoiChunk::worst_case_byte_cost(0), overhead);
+        assert_eq!(QoiChunk::best_case_byte_cost(0), overhead);
+
+        assert_eq!(QoiChunk::worst_case_byte_cost(1), overhead + 5);
+        assert_eq!(QoiChunk::best_case_byte_cost(1), overhead + 1);
+
+        assert_eq!(QoiChunk::best_case_byte_cost(33), overhead + 2);
+        assert_eq!(QoiChunk::best_case_byte_cost(run_max), overhead + 2);
+        assert_eq!(QoiChunk::best_case_byte_cost(run_max + 1), overhead + 3);
+        assert_eq!(
+            QoiChunk::worst_case_byte_cost(run_max),
+            overhead + 5 * run_max
+        );
+
+        // a uniform image of the initial pixel achieves the best case.
+        let img = RgbaImage::from_pixel(run_max as u32 + 40, 1, Rgba([0, 0, 0, 255]));
+        assert_eq!(
+            qoi_analyze(&img).filesize,
+            QoiChunk::best_case_byte_cost(run_max + 40)
+        );
+    }
+
+    #[test]
+    fn test_opcode_prefix() {
+        let cases = [
+            (QoiChunk::Index, 0b0000_0000, 0b1100_0000),
+            (QoiChunk::Run8, 0b0100_0000, 0b1110_0000),
+            (QoiChunk::Run16, 0b0110_0000, 0b1110_0000),
+            (QoiChunk::Diff8, 0b1000_0000, 0b1100_0000),
+            (QoiChunk::Diff16, 0b1100_0000, 0b1110_0000),
+            (QoiChunk::Diff24, 0b1110_0000, 0b1111_0000),
+            (QoiChunk::Color1, 0b1111_0000, 0b1111_0000),
+            (QoiChunk::Color4, 0b1111_0000, 0b1111_0000),
+        ];
+        for &(chunk, prefix, mask) in &cases {
+            assert_eq!(chunk.opcode_prefix(), (prefix, mask), "{:?}", chunk);
+        }
+
+        assert_eq!(QoiChunk::Index.encode_byte(63), Some(0x3F));
+        assert_eq!(QoiChunk::Index.encode_byte(64), None);
+        assert_eq!(QoiChunk::Run8.encode_byte(31), Some(0x5F));
+        assert_eq!(QoiChunk::Run8.encode_byte(32), None);
+        assert_eq!(QoiChunk::Run16.encode_byte(1), Some(0x61));
+        assert_eq!(QoiChunk::Diff8.encode_byte(0x2A), Some(0xAA));
+        assert_eq!(QoiChunk::Diff16.encode_byte(0x1F), Some(0xDF));
+        assert_eq!(QoiChunk::Diff24.encode_byte(0x0F), Some(0xEF));
+        assert_eq!(QoiChunk::Diff24.encode_byte(0x10), None);
+        assert_eq!(QoiChunk::Color1.encode_byte(0b0100), Some(0xF4));
+        assert_eq!(QoiChunk::Color1.encode_byte(0b0110), None);
+        assert_eq!(QoiChunk::Color3.encode_byte(0b1101), Some(0xFD));
+        assert_eq!(QoiChunk::Color4.encode_byte(0b1111), Some(0xFF));
+    }
+
+    #[test]
+    fn test_opcode_description() {
+        for chunk in QoiChunk::iter() {
+            let (layout, _) = opcode_description(chunk).split_once(": ").unwrap();
+            let bits: String = layout.chars().filter(|&c| c != ' ').collect();
+            assert_eq!(bits.len(), 8 * chunk.byte_len(), "{:?}", chunk);
+
+            // the tag matches `opcode_prefix()`.
+            let (prefix, _) = chunk.opcode_prefix();
+            let tag = &bits[..chunk.opcode_bits()];
+            let prefix_str = format!("{:08b}", prefix);
+            assert_eq!(tag, &prefix_str[..chunk.opcode_bits()], "{:?}", chunk);
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        assert_eq!(
+            serde_json::to_string(&QoiChunk::Index).unwrap(),
+            r#""INDEX""#
+        );
+        assert_eq!(
+            serde_json::to_string(&QoiChunk::Diff16).unwrap(),
+            r#""DIFF16""#
+        );
+
+        for chunk in QoiChunk::iter() {
+            let json = serde_json::to_string(&chunk).unwrap();
+            assert_eq!(serde_json::from_str::<QoiChunk>(&json).unwrap(), chunk);
+        }
+    }
+
+    #[test]
+    fn test_chunk_bits() {
+        for chunk in QoiChunk::iter() {
+            assert_eq!(
+                chunk.opcode_bits() + chunk.data_bits(),
+                8 * chunk.byte_len()
+            );
+        }
+        assert_eq!(QoiChunk::Diff8.data_bits(), 6);
+        assert_eq!(QoiChunk::Diff16.data_bits(), 13);
+        assert_eq!(QoiChunk::Diff24.data_bits(), 20);
+
+        assert_eq!(QoiChunk::Diff8.color_bytes(), 0);
+        assert_eq!(QoiChunk::Diff16.color_bytes(), 0);
+        assert_eq!(QoiChunk::Diff24.color_bytes(), 2);
+        assert_eq!(QoiChunk::Color1.color_bytes(), 1);
+        assert_eq!(QoiChunk::Color4.data_bits(), 36);
+    }
+
+    #[test]
+    fn test_best_case_filesize() {
+        const OVERHEAD: usize = QOI_HEADER_LEN + QOI_PADDING_LEN;
+
+        let mut histogram = [0; QoiChunk::COUNT];
+        assert_eq!(best_case_filesize(&histogram), OVERHEAD);
+
+        histogram[QoiChunk::Run8 as usize] = 20;
+        histogram[QoiChunk::Run16 as usize] = 12;
+        assert_eq!(best_case_filesize(&histogram), OVERHEAD + 1);
+
+        histogram[QoiChunk::Run16 as usize] = 13;
+        histogram[QoiChunk::Index as usize] = 3;
+        histogram[QoiChunk::Color4 as usize] = 2;
+        assert_eq!(best_case_filesize(&histogram), OVERHEAD + 2 + 3 + 10);
+
+        // best case is never worse than the actual encoding.
+        let img = image_from_pixels(&[[0, 0, 0, 255], [1, 0, 0, 255], [0, 0, 0, 255]]);
+        let res = qoi_analyze(&img);
+        assert!(best_case_filesize(&res.histogram) <= res.filesize);
+    }
+
+    #[test]
+    fn test_analyzer_config_run8_max() {
+        const OVERHEAD: usize = QOI_HEADER_LEN + QOI_PADDING_LEN;
+
+        // initial pixel is (0, 0, 0, 255), so all pixels are a single run.
+        let img = RgbaImage::from_pixel(40, 1, Rgba([0, 0, 0, 255]));
+
+        let res = qoi_analyze(&img);
+        assert_eq!(res.filesize, OVERHEAD + 2);
+        assert!(res.chunks.iter().all(|&chunk| chunk == QoiChunk::Run16));
+
+        let res = qoi_analyze_with_config(
+            &img,
+            AnalyzerConfig {
+                run8_max: 40,
+                ..AnalyzerConfig::default()
+            },
+        );
+        assert_eq!(res.filesize, OVERHEAD + 1);
+        assert!(res.chunks.iter().all(|&chunk| chunk == QoiChunk::Run8));
+
+        // clamped to 1.
+        let img = RgbaImage::from_pixel(2, 1, Rgba([0, 0, 0, 255]));
+        let res = qoi_analyze_with_config(
+            &img,
+            AnalyzerConfig {
+                run8_max: 0,
+                ..AnalyzerConfig::default()
+            },
+        );
+        assert_eq!(res.chunks, [QoiChunk::Run16; 2]);
+    }
+
+    #[test]
+    fn test_diff_analyzer() {
+        let img = RgbaImage::from_fn(50, 3, |x, y| {
+            Rgba([(x / 20 * 7) as u8, (y * 3) as u8, ((x * y) % 5) as u8, 255])
+        });
+        let reference = AnalyzerConfig {
+            run8_max: 4,
+            dict_size: 16,
+        };
+
+        let mut diff = DiffAnalyzer::new(AnalyzerConfig::default(), reference);
+        let mut pairs = Vec::new();
+        for &Rgba(rgba) in img.pixels() {
+            pairs.extend(diff.push(QoiPixel::from(rgba)));
+        }
+        let (filesize_primary, filesize_reference, rest) = diff.finalize();
+        pairs.extend(rest);
+
+        let res_primary = qoi_analyze(&img);
+        let res_reference = qoi_analyze_with_config(&img, reference);
+        assert_eq!(filesize_primary, res_primary.filesize);
+        assert_eq!(filesize_reference, res_reference.filesize);
+        let expected: Vec<_> = res_primary
+            .chunks
+            .into_iter()
+            .zip(res_reference.chunks)
+            .collect();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn test_simulate_with_initial_dict() {
+        let img = image_from_pixels(&[[10, 20, 30, 255], [90, 0, 0, 255], [10, 20, 30, 255]]);
+        let cold = qoi_analyze(&img);
+        let warm = simulate_with_initial_dict(&img, &cold.dict);
+
+        assert_eq!(cold.chunks[0], QoiChunk::Color3);
+        assert_eq!(warm.chunks, [QoiChunk::Index; 3]);
+        assert_eq!(warm.filesize, cold.filesize - 6);
+        assert_eq!(warm.dict, cold.dict);
+    }
+
+    #[test]
+    fn test_qoi_analyze_with_pixels() {
+        let img = RgbaImage::from_fn(30, 4, |x, y| {
+            Rgba([(x / 6 * 40) as u8, (y * 2) as u8, ((x * y) % 7) as u8, 255])
+        });
+
+        let (filesize, pairs, histogram) = qoi_analyze_with_pixels(&img);
+        let res = qoi_analyze(&img);
+        assert_eq!(filesize, res.filesize);
+        assert_eq!(histogram, res.histogram);
+
+        let (chunks, pixels): (Vec<_>, Vec<_>) = pairs.into_iter().unzip();
+        assert_eq!(chunks, res.chunks);
+        let expected: Vec<_> = img
+            .pixels()
+            .map(|&Rgba(rgba)| QoiPixel::from(rgba))
+            .collect();
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn test_qoi_analyze_png_vs_qoi_runs() {
+        // QOI: one run of 8 pixels.
+        // PNG: "sub" finds 3 zero residuals on row 0, "up" finds 4 on row 1.
+        let img = RgbaImage::from_pixel(4, 2, Rgba([0, 0, 0, 255]));
+        assert_eq!(
+            qoi_analyze_png_vs_qoi_runs(&img, &qoi_analyze(&img).chunks),
+            RunComparison {
+                qoi_run_bytes: 1,
+                qoi_run_pixels: 8,
+                png_estimated_run_bytes: 6,
+                png_run_pixels: 7,
+            }
+        );
+
+        // vertical stripes: no QOI runs, but "up" predicts every row after the first.
+        let img = RgbaImage::from_fn(100, 3, |x, _| Rgba([1 + (x % 2) as u8 * 200, 0, 0, 255]));
+        let res = qoi_analyze_png_vs_qoi_runs(&img, &qoi_analyze(&img).chunks);
+        assert_eq!((res.qoi_run_bytes, res.qoi_run_pixels), (0, 0));
+        assert_eq!(res.png_run_pixels, 200);
+        assert_eq!(res.png_estimated_run_bytes, 2 * 3 * 2);
+    }
+
+    #[test]
+    fn test_image_class() {
+        use rand::{Rng, SeedableRng};
+
+        let classify = |img: &RgbaImage| {
+            let res = qoi_analyze(img);
+            ImageClass::detect(
+                &res.histogram,
+                crate::static_image::distinct_color_count(img),
+                crate::stats::spatial_entropy(img),
+            )
+        };
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let noise = RgbaImage::from_fn(32, 32, |_, _| Rgba([rng.gen(), rng.gen(), rng.gen(), 255]));
+        assert_eq!(classify(&noise), ImageClass::Noise);
+
+        let flat = RgbaImage::from_fn(32, 32, |x, _| Rgba([0, 0, 100 * (x / 16) as u8, 255]));
+        assert_eq!(classify(&flat), ImageClass::Screenshot);
+
+        let gradient =
+            RgbaImage::from_fn(300, 4, |x, y| Rgba([x as u8, (x / 2) as u8, y as u8, 255]));
+        assert_eq!(classify(&gradient), ImageClass::Gradient);
+
+        for class in ImageClass::iter() {
+            let (min, max) = expected_filesize_for_image_class(class);
+            assert!(0. < min && min < max, "{:?}", class);
+        }
+    }
+
+    #[test]
+    fn test_run_max_value() {
+        assert_eq!(RUN_MAX, 8224);
+    }
+
+    #[test]
+    fn test_run_max_flush() {
+        let black = [0, 0, 0, 255];
+        let run_max = usize::from(RUN_MAX);
+        let encoded = |len| {
+            let res = qoi_analyze(&image_from_pixels(&vec![black; len]));
+            encoded_chunks(&res.chunks).collect::<Vec<_>>()
+        };
+
+        assert_eq!(encoded(run_max), [(QoiChunk::Run16, 0..run_max)]);
+        assert_eq!(
+            encoded(run_max + 1),
+            [
+                (QoiChunk::Run16, 0..run_max),
+                (QoiChunk::Run8, run_max..run_max + 1)
+            ]
+        );
+
+        // the analyzer flushes as soon as the run reaches `RUN_MAX`.
+        let mut chunks = Vec::new();
+        let mut enc = QoiAnalyzer::new(|chunk| chunks.push(chunk));
+        for _ in 0..run_max {
+            enc.update(QoiPixel::from(black));
+        }
+        assert_eq!(enc.snapshot().run(), 0);
+        assert_eq!(
+            enc.finalize(),
+            QOI_HEADER_LEN + QoiChunk::Run16.byte_len() + QOI_PADDING_LEN
+        );
+        assert_eq!(chunks, [QoiChunk::Run16; RUN_MAX as usize]);
+    }
+
+    #[test]
+    fn test_single_pixel() {
+        let analyze_one = |rgba| qoi_analyze(&RgbaImage::from_pixel(1, 1, Rgba(rgba)));
+
+        let res = analyze_one([0, 0, 0, 255]);
+        assert_eq!(res.chunks, [QoiChunk::Run8]);
+        assert_eq!(res.filesize, QOI_HEADER_LEN + 1 + QOI_PADDING_LEN);
+
+        // channel deltas wrap around, so white is a Diff8 from black.
+        assert_eq!(analyze_one([255, 255, 255, 255]).chunks, [QoiChunk::Diff8]);
+        assert_eq!(analyze_one([128, 128, 128, 255]).chunks, [QoiChunk::Color3]);
+        assert_eq!(analyze_one([0, 0, 0, 254]).chunks, [QoiChunk::Color1]);
+    }
+
+    #[test]
+    fn test_noise_image() {
+        const SIDE: u32 = 64;
+
+        // LCG of Numerical Recipes, seeded at 42.
+        let mut state = 42_u32;
+        let mut next = move || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            state.to_be_bytes()
+        };
+        let img = RgbaImage::from_fn(SIDE, SIDE, |_, _| Rgba(next()));
+
+        let res = qoi_analyze(&img);
+        let pixel_count = (SIDE * SIDE) as usize;
+        assert_eq!(res.chunks.len(), pixel_count);
+        assert!(res
+            .chunks
+            .iter()
+            .all(|&chunk| matches!(chunk, QoiChunk::Color3 | QoiChunk::Color4)));
+        assert_eq!(res.histogram[QoiChunk::Run8 as usize], 0);
+
+        // nearly all pixels are 5-byte QOI_COLOR with all channels changed.
+        let expected = (5 * pixel_count + QOI_HEADER_LEN + QOI_PADDING_LEN) as f64;
+        assert!((res.filesize as f64 - expected).abs() <= 0.05 * expected);
+    }
+
+    #[test]
+    fn test_qoi_compare_chunks() {
+        use QoiChunk::*;
+
+        let a = [Run8, Run8, Diff8, Index, Color1];
+        let b = [Run8, Diff8, Diff8, Color1, Color1];
+
+        let diff = qoi_compare_chunks(&a, &b);
+        assert_eq!((diff.same, diff.different), (3, 2));
+        assert_eq!(diff.transitions[Run8 as usize][Diff8 as usize], 1);
+        assert_eq!(diff.transitions[Index as usize][Color1 as usize], 1);
+        assert_eq!(diff.transitions.iter().flatten().sum::<usize>(), 2);
+
+        assert_eq!(chunk_sequence_similarity(&a, &b), 0.6);
+        assert_eq!(chunk_sequence_similarity(&a, &a), 1.);
+        assert_eq!(chunk_sequence_similarity(&[], &[]), 1.);
+    }
+
+    #[test]
+    fn test_filesize_with_dict_clear() {
+        const A: [u8; 4] = [100, 0, 0, 255];
+        const B: [u8; 4] = [0, 0, 50, 255];
+
+        // 2 rows: [A, B], [A, B]. the second row is all Index unless the dict is cleared.
+        let buf = [A, B, A, B].concat();
+        let img = RgbaImage::from_vec(2, 2, buf).unwrap();
+
+        let filesize = qoi_analyze(&img).filesize;
+        assert_eq!(filesize_with_dict_clear(&img, 0), filesize);
+        assert_eq!(filesize_with_dict_clear(&img, 2), filesize);
+        assert_eq!(filesize_with_dict_clear(&img, 1), filesize + 4);
+    }
+
+    #[test]
+    fn test_missed_index() {
+        const A: [u8; 4] = [100, 0, 0, 255];
+        const B: [u8; 4] = [0, 0, 50, 255];
+        // same slot as `A` with 64 slots, but not with 128 slots.
+        const C: [u8; 4] = [0, 36, 0, 255];
+
+        // the last A is missed since C evicted it.
+        let img = image_from_pixels(&[A, B, C, B, A]);
+        let res = qoi_analyze(&img);
+        assert_eq!(res.missed_index_count, 1);
+
+        assert_eq!(filesize_with_dict_bits(&img, &res.chunks, 6), res.filesize);
+        let a = QoiPixel::from(A);
+        let c = QoiPixel::from(C);
+        assert_ne!(
+            (a.r() ^ a.g() ^ a.b() ^ a.a()) & 0x7F,
+            (c.r() ^ c.g() ^ c.b() ^ c.a()) & 0x7F
+        );
+        assert!(filesize_with_dict_bits(&img, &res.chunks, 7) < res.filesize);
+
+        let config = AnalyzerConfig {
+            dict_size: 128,
+            ..AnalyzerConfig::default()
+        };
+        let res_128 = qoi_analyze_with_config(&img, config);
+        assert_eq!(res_128.missed_index_count, 0);
+        assert_eq!(res_128.chunks[4], QoiChunk::Index);
+        assert_eq!(
+            res_128.filesize,
+            filesize_with_dict_bits(&img, &res.chunks, 7)
+        );
+    }
+
+    #[test]
+    fn test_analyzer_config_run8_max_clamp() {
+        let img = image_from_pixels(&vec![[0, 0, 0, 255]; usize::from(RUN_MAX) + 1]);
+        let config = AnalyzerConfig {
+            run8_max: RUN_MAX,
+            ..AnalyzerConfig::default()
+        };
+        let res = qoi_analyze_with_config(&img, config);
+        let encoded: Vec<_> = encoded_chunks(&res.chunks)
+            .map(|(chunk, _)| chunk)
+            .collect();
+        assert_eq!(encoded, [QoiChunk::Run16, QoiChunk::Run8]);
+        assert_eq!(res.filesize, QOI_MIN_FILESIZE + 3);
+
+        // resuming from a snapshot keeps the config.
+        let config = AnalyzerConfig {
+            run8_max: 1,
+            ..AnalyzerConfig::default()
+        };
+        let enc = QoiAnalyzer::new(|_| {}).with_config(config);
+        let mut chunks = Vec::new();
+        let mut enc = QoiAnalyzer::from_snapshot(&enc.snapshot(), |chunk| chunks.push(chunk));
+        enc.update(QoiPixel::default());
+        enc.update(QoiPixel::default());
+        enc.finalize();
+        assert_eq!(chunks, [QoiChunk::Run16; 2]);
+    }
+
+    #[test]
+    fn test_qoi_analyze_compare() {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+        let img = image_from_pixels(&[BLACK, BLACK, [1, 0, 0, 255]]);
+
+        let res = qoi_analyze_compare(&img, QoiPixel::default(), QoiPixel::default());
+        assert_eq!(res.same, 3);
+        assert_eq!(res.different, 0);
+        assert_eq!(res.per_chunk_changes, [0; QoiChunk::COUNT]);
+
+        // Run8, Run8, Diff8 -> Color3, Run8, Diff8
+        let res = qoi_analyze_compare(&img, QoiPixel::default(), QoiPixel::new(50, 50, 50, 255));
+        assert_eq!(res.same, 2);
+        assert_eq!(res.different, 1);
+        assert_eq!(res.per_chunk_changes[QoiChunk::Run8 as usize], -1);
+        assert_eq!(res.per_chunk_changes[QoiChunk::Color3 as usize], 1);
+    }
+
+    /// Random image mixing runs, small differences, repeated colors and noise.
+    fn random_image(rng: &mut impl rand::Rng) -> RgbaImage {
+        let w = rng.gen_range(1, 40);
+        let h = rng.gen_range(1, 40);
+        let palette: Vec<[u8; 4]> = (0..6).map(|_| rng.gen()).collect();
+
+        let mut prev: [u8; 4] = [0, 0, 0, 255];
+        RgbaImage::from_fn(w, h, |_, _| {
+            let px = match rng.gen_range(0, 4) {
+                0 => prev,
+                1 => prev.map(|c| c.wrapping_add(rng.gen_range(0, 5)).wrapping_sub(2)),
+                2 => palette[rng.gen_range(0, palette.len())],
+                _ => rng.gen(),
+            };
+            prev = px;
+            Rgba(px)
+        })
+    }
+
+    #[test]
+    fn test_pixel_dict_hash_range() {
+        use rand::{Rng, SeedableRng};
+
+        const VALUES: [u8; 6] = [0, 1, 127, 128, 254, 255];
+        for &r in &VALUES {
+            for &g in &VALUES {
+                for &b in &VALUES {
+                    for &a in &VALUES {
+                        assert!(PixelDict::hash(QoiPixel::new(r, g, b, a)) < 64);
+                    }
+                }
+            }
+        }
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        for _ in 0..10000 {
+            let px = QoiPixel::from(rng.gen::<[u8; 4]>());
+            assert!(PixelDict::hash(px) < 64, "{:?}", px);
+        }
+    }
+
+    #[test]
+    fn test_analysis_determinism() {
+        use rand::SeedableRng;
+
+        fn assert_same(a: &AnalysisResult, b: &AnalysisResult) {
+            assert_eq!(a.filesize, b.filesize);
+            assert_eq!(a.chunks, b.chunks);
+            assert_eq!(a.histogram, b.histogram);
+            assert_eq!(a.index_hit_counts, b.index_hit_counts);
+            assert_eq!(a.dict, b.dict);
+            assert_eq!(a.run_lengths, b.run_lengths);
+        }
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+
+        for _ in 0..50 {
+            let img = random_image(&mut rng);
+            let res = qoi_analyze(&img);
+            assert_same(&res, &qoi_analyze(&img));
+
+            // a distinct instance with the same pixels.
+            let copy =
+                RgbaImage::from_raw(img.width(), img.height(), img.as_raw().clone()).unwrap();
+            assert_same(&res, &qoi_analyze(&copy));
+        }
+    }
+
+    #[test]
+    fn test_qoi_analyze_chunked() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+        let img = random_image(&mut rng);
+        let (w, h) = img.dimensions();
+
+        let bands: Vec<_> = qoi_analyze_chunked(&img, 2).collect();
+        assert_eq!(bands.len() as u32, h.div_ceil(2));
+        assert_eq!(
+            bands.iter().map(|res| res.chunks.len()).sum::<usize>(),
+            (w * h) as usize
+        );
+
+        let top = img.view(0, 0, w, h.min(2)).to_image();
+        assert_eq!(bands[0].chunks, qoi_analyze(&top).chunks);
+        assert_eq!(bands[0].filesize, qoi_analyze(&top).filesize);
+
+        assert_eq!(qoi_analyze_chunked(&img, 0).count() as u32, h);
+        assert_eq!(qoi_analyze_chunked(&RgbaImage::new(3, 0), 4).count(), 0);
+    }
+
+    #[test]
+    fn test_qoi_analyze_random() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+
+        for _ in 0..300 {
+            let img = random_image(&mut rng);
+            let AnalysisResult {
+                filesize, chunks, ..
+            } = qoi_analyze(&img);
+
+            assert_eq!(chunks.len(), img.len() / 4);
+            assert_eq!(
+                chunk_bytes(&chunks).iter().sum::<usize>(),
+                filesize - QOI_HEADER_LEN - QOI_PADDING_LEN
+            );
+
+            // replay diffs from the initial pixel.
+            let mut px_prev = QoiPixel::default();
+            for (&Rgba(rgba), &chunk) in img.pixels().zip(&chunks) {
+                let px = QoiPixel::from(rgba);
+                match chunk.family() {
+                    ChunkFamily::Run => assert_eq!(px, px_prev),
+                    ChunkFamily::Diff => match px.sub(px_prev) {
+                        DiffOrColor::Diff(diff) => assert_eq!(diff.apply(px_prev), px),
+                        DiffOrColor::Color(_) => panic!("{:?} for a non-diff pixel", chunk),
+                    },
+                    ChunkFamily::Index | ChunkFamily::Color => assert_ne!(px, px_prev),
+                }
+                px_prev = px;
+            }
+        }
     }
 }
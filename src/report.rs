@@ -0,0 +1,256 @@
+//! Self-contained HTML report.
+
+use std::fmt::Write as _;
+
+use strum::IntoEnumIterator;
+
+use crate::qoi::{byte_cost_fractions, QoiChunk};
+use crate::static_image::{png_data_url, StaticImage};
+use crate::vis::{create_legend_image, visualize, VisConfig};
+
+/// Maximum side length of the chunk grid embedded for tooltips.
+const TOOLTIP_GRID_SIDE_MAX: u32 = 256;
+
+/// Generates an HTML report which requires no external resources.
+///
+/// The report contains the original image, the visualization, statistics, a histogram, and
+/// tooltips showing the chunk type under the cursor. For large images, chunk types for
+/// tooltips are sampled every `step` pixels.
+pub fn export_html_report(img: &StaticImage, config: &VisConfig) -> String {
+    let name = escape_html(img.name());
+    let url_vis =
+        png_data_url(&visualize(img, config)).expect("PNG encoding into memory should not fail");
+
+    let mut html = String::new();
+
+    writeln!(
+        html,
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>QOI report: {name}</title>
+<style>
+body {{ font-family: sans-serif; }}
+img {{ image-rendering: pixelated; }}
+td {{ padding: 0 0.5em; }}
+td.num {{ text-align: right; }}
+#tooltip {{ position: fixed; pointer-events: none; background: #ffffe0; border: 1px solid #808080; padding: 2px 4px; display: none; }}
+</style>
+</head>
+<body>
+<h1>QOI report: {name}</h1>
+<h2>Images</h2>
+<p>Original:</p>
+<img src="{url_orig}" alt="original">
+<p>Visualization (hover to see chunk types):</p>
+<img id="vis" src="{url_vis}" alt="visualization">
+<div id="tooltip"></div>"#,
+        name = name,
+        url_orig = escape_html(img.url()),
+        url_vis = url_vis,
+    )
+    .unwrap();
+
+    write_legend(&mut html, config);
+    write_stats(&mut html, img);
+    write_histogram_svg(&mut html, img, config);
+    write_tooltip_script(&mut html, img);
+
+    html.push_str("</body>\n</html>\n");
+
+    html
+}
+
+fn write_legend(html: &mut String, config: &VisConfig) {
+    let names: Vec<_> = QoiChunk::iter()
+        .filter(|&chunk| config.is_visible(chunk))
+        .map(|chunk| escape_html(chunk.name()))
+        .collect();
+    if names.is_empty() {
+        return;
+    }
+    let url = png_data_url(&create_legend_image(config))
+        .expect("PNG encoding into memory should not fail");
+
+    writeln!(
+        html,
+        r#"<p>Legend (top to bottom): {}</p>
+<img src="{}" alt="legend">"#,
+        names.join(", "),
+        url
+    )
+    .unwrap();
+}
+
+fn write_stats(html: &mut String, img: &StaticImage) {
+    writeln!(
+        html,
+        r#"<h2>Statistics</h2>
+<table>
+<tr><td>Dimensions</td><td class="num">{}x{}</td></tr>
+<tr><td>Original size</td><td class="num">{}</td></tr>
+<tr><td>QOI size</td><td class="num">{}</td></tr>
+</table>
+<table>
+<tr><th>Chunk</th><th>Count</th><th>Ratio</th><th>Bytes</th><th>Byte share</th></tr>"#,
+        img.width(),
+        img.height(),
+        img.filesize_orig(),
+        img.filesize_qoi()
+    )
+    .unwrap();
+
+    let pixel_count = img.pixel_count().max(1);
+    let byte_fractions = byte_cost_fractions(img.bytes_by_chunk());
+    for chunk in QoiChunk::iter() {
+        let count = img.histogram()[chunk as usize];
+        writeln!(
+            html,
+            r#"<tr><td>{}</td><td class="num">{}</td><td class="num">{:.2} %</td><td class="num">{}</td><td class="num">{:.2} %</td></tr>"#,
+            escape_html(chunk.name()),
+            count,
+            100. * (count as f64) / (pixel_count as f64),
+            img.bytes_by_chunk()[chunk as usize],
+            100. * byte_fractions[chunk as usize]
+        )
+        .unwrap();
+    }
+
+    html.push_str("</table>\n");
+}
+
+fn write_histogram_svg(html: &mut String, img: &StaticImage, config: &VisConfig) {
+    const BAR_WIDTH_MAX: f64 = 400.;
+    const BAR_HEIGHT: u32 = 16;
+    const LABEL_WIDTH: u32 = 160;
+
+    let count_max = img.histogram().iter().copied().max().unwrap_or(0).max(1);
+    let w = LABEL_WIDTH + BAR_WIDTH_MAX as u32;
+    let h = BAR_HEIGHT * QoiChunk::iter().count() as u32;
+
+    writeln!(
+        html,
+        r#"<h2>Histogram</h2>
+<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" font-size="12">"#,
+        w = w,
+        h = h
+    )
+    .unwrap();
+
+    for (i, chunk) in QoiChunk::iter().enumerate() {
+        let count = img.histogram()[chunk as usize];
+        let [r, g, b] = config.color(chunk);
+        let y = BAR_HEIGHT * i as u32;
+        let bar_w = BAR_WIDTH_MAX * (count as f64) / (count_max as f64);
+        writeln!(
+            html,
+            r##"<text x="0" y="{}">{}</text><rect x="{}" y="{}" width="{:.1}" height="{}" fill="#{:02X}{:02X}{:02X}" stroke="#000000"/>"##,
+            y + BAR_HEIGHT - 4,
+            escape_html(chunk.name()),
+            LABEL_WIDTH,
+            y + 1,
+            bar_w,
+            BAR_HEIGHT - 2,
+            r,
+            g,
+            b
+        )
+        .unwrap();
+    }
+
+    html.push_str("</svg>\n");
+}
+
+fn write_tooltip_script(html: &mut String, img: &StaticImage) {
+    let (w, h) = (img.width(), img.height());
+    let step = w.max(h).div_ceil(TOOLTIP_GRID_SIDE_MAX).max(1);
+    let grid_w = w.div_ceil(step);
+    let grid_h = h.div_ceil(step);
+
+    let names: Vec<_> = QoiChunk::iter()
+        .map(|chunk| format!("\"{}\"", chunk.name()))
+        .collect();
+
+    let mut grid = String::new();
+    for gy in 0..grid_h {
+        for gx in 0..grid_w {
+            let idx = (gy * step) as usize * (w as usize) + (gx * step) as usize;
+            if !grid.is_empty() {
+                grid.push(',');
+            }
+            write!(grid, "{}", img.chunks()[idx] as usize).unwrap();
+        }
+    }
+
+    writeln!(
+        html,
+        r#"<script>
+const DATA = {{"width":{w},"height":{h},"step":{step},"gridWidth":{grid_w},"names":[{names}],"chunks":[{grid}]}};
+const vis = document.getElementById("vis");
+const tooltip = document.getElementById("tooltip");
+vis.addEventListener("mousemove", (ev) => {{
+  const rect = vis.getBoundingClientRect();
+  const x = Math.floor((ev.clientX - rect.left) * DATA.width / rect.width);
+  const y = Math.floor((ev.clientY - rect.top) * DATA.height / rect.height);
+  if (x < 0 || x >= DATA.width || y < 0 || y >= DATA.height) {{
+    tooltip.style.display = "none";
+    return;
+  }}
+  const chunk = DATA.chunks[Math.floor(y / DATA.step) * DATA.gridWidth + Math.floor(x / DATA.step)];
+  tooltip.textContent = `(${{x}}, ${{y}}): ${{DATA.names[chunk]}}`;
+  tooltip.style.left = `${{ev.clientX + 12}}px`;
+  tooltip.style.top = `${{ev.clientY + 12}}px`;
+  tooltip.style.display = "block";
+}});
+vis.addEventListener("mouseleave", () => {{
+  tooltip.style.display = "none";
+}});
+</script>"#,
+        w = w,
+        h = h,
+        step = step,
+        grid_w = grid_w,
+        names = names.join(","),
+        grid = grid,
+    )
+    .unwrap();
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_html_report() {
+        let img = StaticImage::default();
+        let html = export_html_report(&img, &VisConfig::default());
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("data:image/png;base64,"));
+        assert!(html.contains("<svg"));
+        assert!(html.contains(r#"alt="legend""#));
+        assert!(html.contains(QoiChunk::Run16.name()));
+
+        assert_eq!(
+            escape_html(r#"<a href="x">&'"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;"
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use image::RgbaImage;
+
+use crate::pixel::QoiPixel;
+use crate::qoi::{AnalyzerSnapshot, QoiAnalyzer};
+use crate::static_image::StaticImage;
+
+/// Interval (in pixels) between `AnalyzerSnapshot`s.
+pub const SNAPSHOT_INTERVAL: usize = 100;
+
+/// Returns analyzer states. `snapshots[i]` is the state just before pixel `i * SNAPSHOT_INTERVAL`.
+pub fn take_snapshots(img: &RgbaImage) -> Vec<AnalyzerSnapshot> {
+    let mut snapshots = Vec::with_capacity(img.as_raw().len() / 4 / SNAPSHOT_INTERVAL + 1);
+
+    let mut enc = QoiAnalyzer::new(|_| {});
+    for (i, rgba) in img.as_raw().chunks_exact(4).enumerate() {
+        if i % SNAPSHOT_INTERVAL == 0 {
+            snapshots.push(enc.snapshot());
+        }
+        enc.update(QoiPixel::new(rgba[0], rgba[1], rgba[2], rgba[3]));
+    }
+
+    snapshots
+}
+
+/// Returns the analyzer state just before pixel `pos` (`pos` may be the pixel count).
+///
+/// Replay starts from the nearest snapshot.
+pub fn state_at(img: &StaticImage, pos: usize) -> AnalyzerSnapshot {
+    let snapshots = img.snapshots();
+    if snapshots.is_empty() {
+        return QoiAnalyzer::new(|_| {}).snapshot();
+    }
+    let snap_idx = (pos / SNAPSHOT_INTERVAL).min(snapshots.len() - 1);
+    let pos_start = snap_idx * SNAPSHOT_INTERVAL;
+
+    let mut enc = QoiAnalyzer::from_snapshot(&snapshots[snap_idx], |_| {});
+    let pixels = img.image().as_raw().chunks_exact(4);
+    for rgba in pixels.take(pos).skip(pos_start) {
+        enc.update(QoiPixel::new(rgba[0], rgba[1], rgba[2], rgba[3]));
+    }
+
+    enc.snapshot()
+}
+
+/// Effect of changing a single pixel.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Sensitivity {
+    /// Indices of pixels whose chunk type changed.
+    pub changed: Vec<usize>,
+    /// Difference of QOI file size in bytes.
+    pub byte_delta: isize,
+}
+
+/// Replays the analysis of `img` with pixel `idx` replaced by `px_new`.
+///
+/// Replay starts from the nearest snapshot, and stops when the analyzer state becomes
+/// equivalent to the original one again.
+pub fn analyze_sensitivity(img: &StaticImage, idx: usize, px_new: QoiPixel) -> Sensitivity {
+    let snapshots = img.snapshots();
+    let pixels = img.image().as_raw().chunks_exact(4);
+
+    let snap_idx = idx / SNAPSHOT_INTERVAL;
+    let snap = &snapshots[snap_idx];
+    let pos_start = snap_idx * SNAPSHOT_INTERVAL;
+    // first pixel whose chunk is not emitted yet.
+    let chunk_start = pos_start - usize::from(snap.run());
+
+    let mut chunks_new = Vec::new();
+    let mut enc = QoiAnalyzer::from_snapshot(snap, |chunk| chunks_new.push(chunk));
+
+    let mut converged = None;
+    for (i, rgba) in pixels.enumerate().skip(pos_start) {
+        if i > idx && i % SNAPSHOT_INTERVAL == 0 {
+            let snap_new = enc.snapshot();
+            let snap_orig = &snapshots[i / SNAPSHOT_INTERVAL];
+            if snap_new.is_equivalent(snap_orig) {
+                converged = Some(snap_new.filesize() as isize - snap_orig.filesize() as isize);
+                break;
+            }
+        }
+
+        let px = if i == idx {
+            px_new
+        } else {
+            QoiPixel::new(rgba[0], rgba[1], rgba[2], rgba[3])
+        };
+        enc.update(px);
+    }
+    let byte_delta = match converged {
+        Some(delta) => delta,
+        None => enc.finalize() as isize - img.filesize_qoi() as isize,
+    };
+
+    let changed = chunks_new
+        .iter()
+        .zip(&img.chunks()[chunk_start..])
+        .enumerate()
+        .filter(|(_, (new, orig))| new != orig)
+        .map(|(i, _)| chunk_start + i)
+        .collect();
+
+    Sensitivity {
+        changed,
+        byte_delta,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+    use crate::qoi::{qoi_analyze, AnalysisResult};
+
+    #[test]
+    fn test_state_at() {
+        let img = StaticImage::default();
+        let pixel_count = img.pixel_count();
+
+        for pos in [0, 1, 150, pixel_count - 1, pixel_count] {
+            let mut enc = QoiAnalyzer::new(|_| {});
+            for rgba in img.image().as_raw().chunks_exact(4).take(pos) {
+                enc.update(QoiPixel::new(rgba[0], rgba[1], rgba[2], rgba[3]));
+            }
+            assert_eq!(state_at(&img, pos), enc.snapshot());
+        }
+    }
+
+    #[test]
+    fn test_analyze_sensitivity() {
+        let img = StaticImage::default();
+        let pixel_count = img.pixel_count();
+
+        for idx in [0, 1, 99, 100, 12345 % pixel_count, pixel_count - 1] {
+            for px_new in [QoiPixel::new(0, 0, 0, 255), QoiPixel::new(12, 34, 56, 78)] {
+                let width = img.width() as usize;
+                let (x, y) = ((idx % width) as u32, (idx / width) as u32);
+                let mut modified = img.image().clone();
+                modified.put_pixel(x, y, Rgba([px_new.r(), px_new.g(), px_new.b(), px_new.a()]));
+                let AnalysisResult {
+                    filesize, chunks, ..
+                } = qoi_analyze(&modified);
+
+                let expected: Vec<_> = chunks
+                    .iter()
+                    .zip(img.chunks())
+                    .enumerate()
+                    .filter(|(_, (new, orig))| new != orig)
+                    .map(|(i, _)| i)
+                    .collect();
+
+                let sensitivity = analyze_sensitivity(&img, idx, px_new);
+                assert_eq!(sensitivity.changed, expected);
+                assert_eq!(
+                    sensitivity.byte_delta,
+                    filesize as isize - img.filesize_qoi() as isize
+                );
+            }
+        }
+    }
+}
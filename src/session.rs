@@ -0,0 +1,67 @@
+//! Analysis sessions saved as JSON, without pixel data.
+
+use serde::{Deserialize, Serialize};
+use strum::EnumCount;
+
+use crate::qoi::QoiChunk;
+use crate::static_image::StaticImage;
+use crate::vis::VisConfig;
+
+/// File name of downloaded sessions.
+pub const SESSION_FILENAME: &str = "qoi_session.json";
+
+/// Display config and analysis statistics of an image.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub config: VisConfig,
+    pub image_name: String,
+    pub width: u32,
+    pub height: u32,
+    pub filesize_orig: usize,
+    pub filesize_qoi: usize,
+    pub histogram: [usize; QoiChunk::COUNT],
+    pub zoom: u32,
+    /// Window scroll position in CSS pixels.
+    pub scroll: (f64, f64),
+}
+
+impl Session {
+    pub fn new(img: &StaticImage, config: VisConfig, zoom: u32, scroll: (f64, f64)) -> Self {
+        Self {
+            config,
+            image_name: img.name().to_owned(),
+            width: img.width(),
+            height: img.height(),
+            filesize_orig: img.filesize_orig(),
+            filesize_qoi: img.filesize_qoi(),
+            histogram: *img.histogram(),
+            zoom,
+            scroll,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("session should be serializable")
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_json() {
+        let img = StaticImage::default();
+        let session = Session::new(&img, VisConfig::default(), 4, (10., 20.5));
+
+        let session_rt = Session::from_json(&session.to_json()).unwrap();
+        assert_eq!(session_rt, session);
+        assert_eq!(session_rt.filesize_qoi, img.filesize_qoi());
+
+        assert!(Session::from_json("{}").is_err());
+    }
+}
@@ -1,23 +1,91 @@
+use std::cell::OnceCell;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::rc::Rc;
+
+use anyhow::{anyhow, ensure};
 use gloo_file::Blob;
-use image::{ImageFormat, RgbaImage};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, ColorType, ImageFormat, Rgba, RgbaImage};
+use seed::futures::channel::mpsc::Sender;
+use seed::futures::SinkExt;
+use seed::prelude::cmds;
 use strum::EnumCount;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlCanvasElement, HtmlImageElement};
+
+use crate::codec::{self, QoiColorspace};
+use crate::format_compare::{estimate_sizes, FormatSizes};
+use crate::pixel::{ByLuminance, PixelDict, QoiPixel};
+use crate::preprocess::Preprocess;
+use crate::qoi::{
+    chunk_bytes, chunk_sequence_entropy_rate, filesize_with_dict_bits, parse_qoi_header,
+    qoi_analyze, qoi_analyze_png_vs_qoi_runs, AnalysisResult, AnalyzerSnapshot, ImageClass,
+    QoiAnalyzer, QoiChunk, RunComparison, QOI_MAGIC,
+};
+use crate::sensitivity::take_snapshots;
+use crate::stats::{spatial_entropy, transition_matrix, TransitionMatrix};
+use crate::util;
+
+/// Maximum number of frames loaded from an animated GIF.
+const GIF_FRAME_MAX: usize = 50;
+
+/// Number of most frequent colors kept (see `StaticImage::dominant_colors()`).
+pub const DOMINANT_COLOR_MAX: usize = 64;
+
+/// Upper limit of `max_side` for `StaticImage::thumbnail()`.
+const THUMBNAIL_SIDE_MAX: u32 = 128;
 
-use crate::qoi::{qoi_analyze, QoiChunk};
+/// Number of rows analyzed between progress reports.
+const ANALYZE_ROWS_PER_YIELD: usize = 64;
 
 /// `img` and `url` contain almost the same content, but don't care.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct StaticImage {
     name: String,
     img: RgbaImage,
     url: String,
     filesize_orig: usize,
-    filesize_qoi: usize,
-    chunks: Vec<QoiChunk>,
-    histogram: [usize; QoiChunk::COUNT],
+    analysis: AnalysisResult,
+    bytes_by_chunk: [usize; QoiChunk::COUNT],
+    transitions: TransitionMatrix,
+    variable_alpha: bool,
+    /// Shared by clones, which have the same pixels.
+    lazy: Rc<LazyStats>,
+    analysis_time_ms: f64,
+    /// `util::now_ms()` when the analysis finished.
+    analysis_timestamp: f64,
+    preprocess_chain: Vec<Preprocess>,
+    /// Name of the full resolution image, if this is a thumbnail.
+    thumbnail_of: Option<String>,
+    /// Colorspace of the original file (see `detect_colorspace()`).
+    colorspace: QoiColorspace,
+    /// Format of the original file, e.g. `"PNG"` (see `name_of_format()`).
+    format_name: String,
+}
+
+/// Statistics of `StaticImage` computed on first use.
+///
+/// Most images (thumbnails, GIF frames, intermediate preprocess results, history entries) are
+/// never shown in full, so they don't pay for extra encoder passes and snapshots.
+#[derive(Debug, Default)]
+struct LazyStats {
+    format_sizes: OnceCell<FormatSizes>,
+    run_comparison: OnceCell<RunComparison>,
+    snapshots: OnceCell<Vec<AnalyzerSnapshot>>,
+    distinct_colors: OnceCell<usize>,
+    /// At most `DOMINANT_COLOR_MAX` colors.
+    dominant_colors: OnceCell<Vec<(QoiPixel, usize)>>,
+    spatial_entropy: OnceCell<f64>,
+    sequence_entropy_rate: OnceCell<f64>,
+    filesize_dict128: OnceCell<usize>,
 }
 
 impl StaticImage {
-    fn new<S1, S2>(name: S1, img: RgbaImage, url: S2, filesize_orig: usize) -> Self
+    pub fn new<S1, S2>(name: S1, img: RgbaImage, url: S2, filesize_orig: usize) -> Self
     where
         S1: Into<String>,
         S2: Into<String>,
@@ -25,31 +93,423 @@ impl StaticImage {
         let name = name.into();
         let url = url.into();
 
-        let (filesize_qoi, chunks, histogram) = qoi_analyze(&img);
+        let start = util::now_ms();
+        let analysis = qoi_analyze(&img);
+        let analysis_time_ms = util::now_ms() - start;
+
+        Self::from_analysis(name, img, url, filesize_orig, analysis, analysis_time_ms)
+    }
+
+    fn from_analysis(
+        name: String,
+        img: RgbaImage,
+        url: String,
+        filesize_orig: usize,
+        analysis: AnalysisResult,
+        analysis_time_ms: f64,
+    ) -> Self {
+        let analysis_timestamp = util::now_ms();
+        let bytes_by_chunk = chunk_bytes(&analysis.chunks);
+        let transitions = transition_matrix(&analysis.chunks);
+        let variable_alpha = img.pixels().any(|px| px.0[3] != 0xFF);
 
         Self {
             name,
             img,
             url,
             filesize_orig,
-            filesize_qoi,
-            chunks,
-            histogram,
+            analysis,
+            bytes_by_chunk,
+            transitions,
+            variable_alpha,
+            lazy: Rc::default(),
+            analysis_time_ms,
+            analysis_timestamp,
+            preprocess_chain: Vec::new(),
+            thumbnail_of: None,
+            colorspace: QoiColorspace::Srgb,
+            format_name: RAW_FORMAT_NAME.to_owned(),
         }
     }
 
-    pub async fn from_blob(name: impl Into<String>, blob: &Blob) -> anyhow::Result<Self> {
-        let name = name.into();
+    /// Loads an image from `blob`, reporting progress (0.0 to 1.0) to `progress`.
+    ///
+    /// If `max_side` is given, images larger than `max_side` x `max_side` are downscaled
+    /// before analysis (see `resize_for_analysis()`).
+    ///
+    /// Progress stages: reading (0.0 to 0.3), decoding (0.3 to 0.6), analyzing (0.6 to 1.0).
+    pub async fn from_blob(
+        name: impl Into<String>,
+        blob: &Blob,
+        max_side: Option<u32>,
+        mut progress: Sender<f32>,
+    ) -> anyhow::Result<Self> {
+        let mut name = name.into();
+
+        // progress receiver may be dropped. it's not an error.
+        let _ = progress.send(0.).await;
 
         // first, check the size limitation of Data URL. (fail fast)
-        let url = gloo_file::futures::read_as_data_url(blob).await?;
+        let mut url = gloo_file::futures::read_as_data_url(blob).await?;
+        let _ = progress.send(0.15).await;
 
         let buf = gloo_file::futures::read_as_bytes(blob).await?;
         let filesize_orig = buf.len();
-        let img = image::load_from_memory(&buf)?;
-        let img = img.to_rgba8();
+        let _ = progress.send(0.3).await;
+        yield_now().await;
+
+        let (mut img, format) = decode_file(&buf)?;
+        // browsers can't display QOI.
+        if format.is_none() {
+            url = png_data_url(&img)?;
+        }
+        if let Some(img_small) = max_side.and_then(|side| fit_within(&img, side)) {
+            img = img_small;
+            name = scaled_name(&name, &img);
+            url = png_data_url(&img)?;
+        }
+        let _ = progress.send(0.6).await;
+        yield_now().await;
+
+        // excludes yielding to the browser, which also re-renders the progress.
+        let mut analysis_time_ms = 0.;
+        let mut start = util::now_ms();
+        let (filesize_qoi, chunks, dict, run_lengths) = {
+            let mut chunks = Vec::<QoiChunk>::with_capacity(img.len() / 4);
+            let mut enc = QoiAnalyzer::new(|chunk| chunks.push(chunk)).with_run_lengths();
+            let height = img.height() as usize;
+            for (y, row) in img.rows().enumerate() {
+                for &Rgba(rgba) in row {
+                    enc.update(QoiPixel::from(rgba));
+                }
+                if (y + 1) % ANALYZE_ROWS_PER_YIELD == 0 {
+                    analysis_time_ms += util::now_ms() - start;
+                    let ratio = (y + 1) as f32 / height as f32;
+                    let _ = progress.send(0.6 + 0.4 * ratio).await;
+                    yield_now().await;
+                    start = util::now_ms();
+                }
+            }
+            let dict = enc.dict().clone();
+            let (filesize_qoi, run_lengths) = enc.finalize_with_run_lengths();
+            (filesize_qoi, chunks, dict, run_lengths)
+        };
+        let analysis = AnalysisResult::new(&img, filesize_qoi, chunks, dict, run_lengths);
+        analysis_time_ms += util::now_ms() - start;
+        let _ = progress.send(1.).await;
+
+        Ok(Self {
+            colorspace: detect_colorspace(&buf),
+            format_name: name_of_format(format),
+            ..Self::from_analysis(name, img, url, filesize_orig, analysis, analysis_time_ms)
+        })
+    }
+
+    /// Loads frames of an animated GIF (at most `GIF_FRAME_MAX` frames).
+    ///
+    /// `filesize_orig` of each frame is the size of the whole GIF file.
+    /// `max_side` and progress are handled like `from_blob()`, where decoding and analyzing
+    /// are done per frame.
+    pub async fn load_gif_frames(
+        name: impl Into<String>,
+        blob: &Blob,
+        max_side: Option<u32>,
+        mut progress: Sender<f32>,
+    ) -> anyhow::Result<Vec<Self>> {
+        let name = name.into();
+
+        let _ = progress.send(0.).await;
+
+        let buf = gloo_file::futures::read_as_bytes(blob).await?;
+        let filesize_orig = buf.len();
+        let _ = progress.send(0.3).await;
+        yield_now().await;
+
+        let decoder = GifDecoder::new(Cursor::new(&buf))?;
+        let mut frames = Vec::<Self>::new();
+        for frame in decoder.into_frames().take(GIF_FRAME_MAX) {
+            let img = frame?.into_buffer();
+            let (name, img) = match max_side.and_then(|side| fit_within(&img, side)) {
+                Some(img_small) => (scaled_name(&name, &img_small), img_small),
+                None => (name.clone(), img),
+            };
+            let url = png_data_url(&img)?;
+            frames.push(Self {
+                format_name: name_of_format(Some(ImageFormat::Gif)),
+                ..Self::new(name, img, url, filesize_orig)
+            });
+
+            let ratio = frames.len() as f32 / GIF_FRAME_MAX as f32;
+            let _ = progress.send(0.3 + 0.7 * ratio).await;
+            yield_now().await;
+        }
+        let _ = progress.send(1.).await;
+
+        Ok(frames)
+    }
+
+    /// Decodes an image file (including QOI) synchronously.
+    ///
+    /// If browsers cannot display the format, the original image is displayed as PNG
+    /// re-encoded from the decoded image.
+    pub fn from_bytes(name: impl Into<String>, buf: &[u8]) -> anyhow::Result<Self> {
+        let (img, format) = decode_file(buf)?;
+
+        // make Data URL by myself to avoid async.
+        let url = match format.and_then(mime_of_format) {
+            Some(mime) => data_url(mime, buf),
+            None => png_data_url(&img)?,
+        };
+
+        Ok(Self {
+            colorspace: detect_colorspace(buf),
+            format_name: name_of_format(format),
+            ..Self::new(name, img, url, buf.len())
+        })
+    }
+
+    /// Decodes an image from a base64 Data URL synchronously.
+    ///
+    /// Only PNG, JPEG, GIF and WebP are accepted.
+    pub fn from_data_url(data_url: &str) -> anyhow::Result<Self> {
+        const DATA_URL_NAME: &str = "data-url";
+        const MIMES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
 
-        Ok(Self::new(name, img, url, filesize_orig))
+        let data_url = data_url.trim();
+        let (mime, body) = data_url
+            .strip_prefix("data:")
+            .and_then(|rest| rest.split_once(";base64,"))
+            .ok_or_else(|| anyhow!("not a base64 Data URL"))?;
+        ensure!(
+            MIMES.contains(&mime),
+            "unsupported MIME type '{}' (expected one of: {})",
+            mime,
+            MIMES.join(", ")
+        );
+
+        let buf = base64::decode(body)?;
+        let (img, format) = decode_file(&buf)?;
+
+        Ok(Self {
+            colorspace: detect_colorspace(&buf),
+            format_name: name_of_format(format),
+            ..Self::new(DATA_URL_NAME, img, data_url, buf.len())
+        })
+    }
+
+    /// Makes an image from raw RGBA bytes, row-major without padding.
+    ///
+    /// The original image is displayed as PNG encoded from `buf`.
+    pub fn from_rgba_bytes(
+        name: impl Into<String>,
+        width: u32,
+        height: u32,
+        buf: Vec<u8>,
+    ) -> anyhow::Result<Self> {
+        let len = buf.len();
+        let img = RgbaImage::from_vec(width, height, buf).ok_or_else(|| {
+            anyhow!(
+                "buffer length {} does not match {}x{} RGBA image",
+                len,
+                width,
+                height
+            )
+        })?;
+        let url = png_data_url(&img)?;
+
+        Ok(Self::new(name, img, url, len))
+    }
+
+    /// Reads the pixels of a loaded `<img>` element through a hidden canvas.
+    ///
+    /// The name is the basename of `src`. Cross-origin images without CORS approval can't be
+    /// read, and result in an error.
+    #[allow(dead_code)]
+    pub fn from_image_element(img_el: &HtmlImageElement) -> anyhow::Result<Self> {
+        let (w, h) = (img_el.natural_width(), img_el.natural_height());
+        ensure!(w > 0 && h > 0, "image is not loaded");
+
+        let canvas: HtmlCanvasElement = seed::document()
+            .create_element("canvas")
+            .map_err(|e| anyhow!("{:?}", e))?
+            .dyn_into()
+            .map_err(|e| anyhow!("{:?}", e))?;
+        canvas.set_width(w);
+        canvas.set_height(h);
+        seed::canvas_context_2d(&canvas)
+            .draw_image_with_html_image_element(img_el, 0., 0.)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let img = util::image_from_canvas(&canvas)?;
+
+        Self::from_rgba_bytes(url_basename(&img_el.src()), w, h, img.into_raw())
+    }
+
+    /// Returns a copy downscaled to fit within `max_side` x `max_side` and re-analyzed.
+    ///
+    /// The aspect ratio is kept, and the scaled dimensions are appended to the name.
+    /// If the image already fits, a plain copy is returned.
+    pub fn resize_for_analysis(&self, max_side: u32) -> Self {
+        let img = match fit_within(&self.img, max_side) {
+            Some(img) => img,
+            None => return self.clone(),
+        };
+        let url = png_data_url(&img).expect("PNG encoding into memory should not fail");
+
+        Self {
+            colorspace: self.colorspace,
+            format_name: self.format_name.clone(),
+            ..Self::new(scaled_name(&self.name, &img), img, url, self.filesize_orig)
+        }
+    }
+
+    /// Returns a copy downscaled to fit within `max_side` x `max_side` (at most
+    /// `THUMBNAIL_SIDE_MAX`) and re-analyzed, marked as a thumbnail of this image.
+    ///
+    /// The aspect ratio is kept. Small images are just copied.
+    pub fn thumbnail(&self, max_side: u32) -> Self {
+        let max_side = max_side.clamp(1, THUMBNAIL_SIDE_MAX);
+        let img = fit_within(&self.img, max_side).unwrap_or_else(|| self.img.clone());
+        let url = png_data_url(&img).expect("PNG encoding into memory should not fail");
+
+        Self {
+            thumbnail_of: Some(self.name.clone()),
+            colorspace: self.colorspace,
+            format_name: self.format_name.clone(),
+            ..Self::new(self.name.clone(), img, url, self.filesize_orig)
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_thumbnail(&self) -> bool {
+        self.thumbnail_of.is_some()
+    }
+
+    /// Returns `"{width}x{height}"`, followed by `" (thumbnail of {name})"` for thumbnails.
+    pub fn dimensions_label(&self) -> String {
+        let dims = format!("{}x{}", self.width(), self.height());
+        match &self.thumbnail_of {
+            Some(name) => format!("{} (thumbnail of {})", dims, name),
+            None => dims,
+        }
+    }
+
+    /// Returns a new image with `p` applied and analysis re-run.
+    pub fn apply_preprocess(&self, p: Preprocess) -> Self {
+        self.preprocessed(self.img.clone(), p)
+    }
+
+    /// Same as `apply_preprocess()`, but reuses the image buffer of `self`.
+    ///
+    /// Use this for chaining: `img.with_preprocess(Grayscale).with_preprocess(Quantize(5))`.
+    pub fn with_preprocess(mut self, p: Preprocess) -> Self {
+        let img = std::mem::take(&mut self.img);
+        self.preprocessed(img, p)
+    }
+
+    fn preprocessed(&self, img: RgbaImage, p: Preprocess) -> Self {
+        let img = p.apply(img);
+        let url = png_data_url(&img).expect("PNG encoding into memory should not fail");
+
+        let mut preprocess_chain = self.preprocess_chain.clone();
+        preprocess_chain.push(p);
+
+        Self {
+            preprocess_chain,
+            colorspace: self.colorspace,
+            format_name: self.format_name.clone(),
+            ..Self::new(self.name.clone(), img, url, self.filesize_orig)
+        }
+    }
+
+    /// Returns a new image with alpha binarized at `threshold` (`Preprocess::AlphaThreshold`).
+    pub fn apply_alpha_threshold(&self, threshold: u8) -> Self {
+        self.apply_preprocess(Preprocess::AlphaThreshold(threshold))
+    }
+
+    /// Returns the left and right halves, analyzed independently and named `"{name}_left"` and
+    /// `"{name}_right"`. The left half gets the extra column of odd widths.
+    ///
+    /// Returns `None` if the image is narrower than 2 pixels.
+    pub fn split_horizontally(&self) -> Option<(Self, Self)> {
+        let (w, h) = (self.width(), self.height());
+        if w < 2 {
+            return None;
+        }
+        let w_left = w.div_ceil(2);
+
+        let left = self.apply_preprocess(Preprocess::Crop(0, 0, w_left, h));
+        let right = self.apply_preprocess(Preprocess::Crop(w_left, 0, w - w_left, h));
+        Some((
+            left.with_name(format!("{}_left", self.name)),
+            right.with_name(format!("{}_right", self.name)),
+        ))
+    }
+
+    /// Like `split_horizontally()`, but top and bottom (`"{name}_top"` and `"{name}_bottom"`).
+    pub fn split_vertically(&self) -> Option<(Self, Self)> {
+        let (w, h) = (self.width(), self.height());
+        if h < 2 {
+            return None;
+        }
+        let h_top = h.div_ceil(2);
+
+        let top = self.apply_preprocess(Preprocess::Crop(0, 0, w, h_top));
+        let bottom = self.apply_preprocess(Preprocess::Crop(0, h_top, w, h - h_top));
+        Some((
+            top.with_name(format!("{}_top", self.name)),
+            bottom.with_name(format!("{}_bottom", self.name)),
+        ))
+    }
+
+    /// Renames this image. The analysis is kept.
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..self
+        }
+    }
+
+    /// Appends `suffix` to the name of this image, before the extension if any (`"a.png"` becomes
+    /// `"a_gray.png"`). The analysis is kept.
+    pub fn with_suffix(mut self, suffix: &str) -> Self {
+        let stem_len = match std::path::Path::new(&self.name).extension() {
+            Some(ext) => self.name.len() - ext.len() - 1,
+            None => self.name.len(),
+        };
+        self.name.insert_str(stem_len, suffix);
+        self
+    }
+
+    /// Transforms applied to the loaded image, in order.
+    pub fn preprocess_chain(&self) -> &[Preprocess] {
+        &self.preprocess_chain
+    }
+
+    /// Re-encodes the image to PNG.
+    pub fn to_png_bytes(&self) -> Vec<u8> {
+        encode_png(&self.img).expect("PNG encoding into memory should not fail")
+    }
+
+    /// Encodes the image to QOI, with the colorspace detected from the original file.
+    #[allow(dead_code)]
+    pub fn to_qoi_bytes(&self) -> Vec<u8> {
+        let qoi = codec::encode(&self.img, self.colorspace);
+        debug_assert_eq!(qoi.len(), self.filesize_qoi());
+
+        qoi
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.img
+    }
+
+    pub fn colorspace(&self) -> QoiColorspace {
+        self.colorspace
+    }
+
+    /// Format of the original file, e.g. `"PNG"`, or `RAW_FORMAT_NAME` for raw pixels.
+    pub fn format_name(&self) -> &str {
+        &self.format_name
     }
 
     pub fn name(&self) -> &str {
@@ -65,15 +525,211 @@ impl StaticImage {
     }
 
     pub fn filesize_qoi(&self) -> usize {
-        self.filesize_qoi
+        self.analysis.filesize
     }
 
     pub fn chunks(&self) -> &[QoiChunk] {
-        &self.chunks
+        &self.analysis.chunks
+    }
+
+    /// Returns the chunks of pixels in the `w` x `h` region at `(x, y)`, one slice per row.
+    ///
+    /// Fails if the region is not inside the image.
+    pub fn chunks_in_region(
+        &self,
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+    ) -> anyhow::Result<Vec<&[QoiChunk]>> {
+        let inside =
+            |pos: u32, len: u32, side: u32| pos.checked_add(len).is_some_and(|end| end <= side);
+        ensure!(
+            inside(x, w, self.width()) && inside(y, h, self.height()),
+            "region {}x{} at ({}, {}) is out of {}x{} image",
+            w,
+            h,
+            x,
+            y,
+            self.width(),
+            self.height()
+        );
+
+        let width = self.width() as usize;
+        let (x, w) = (x as usize, w as usize);
+        Ok((y..y + h)
+            .map(|row| {
+                let start = row as usize * width + x;
+                &self.chunks()[start..start + w]
+            })
+            .collect())
+    }
+
+    /// Length of the run each pixel belongs to, 0 for non-run pixels.
+    pub fn run_lengths(&self) -> &[u16] {
+        &self.analysis.run_lengths
     }
 
     pub fn histogram(&self) -> &[usize; QoiChunk::COUNT] {
-        &self.histogram
+        &self.analysis.histogram
+    }
+
+    /// Returns the fraction of pixels encoded as each chunk type.
+    pub fn histogram_normalized(&self) -> [f64; QoiChunk::COUNT] {
+        let pixel_count = self.pixel_count().max(1) as f64;
+
+        self.analysis
+            .histogram
+            .map(|count| (count as f64) / pixel_count)
+    }
+
+    /// Returns the fraction of the QOI file size taken by each chunk type.
+    ///
+    /// The rest is header and padding.
+    pub fn histogram_bytes_fraction(&self) -> [f64; QoiChunk::COUNT] {
+        let filesize = self.filesize_qoi().max(1) as f64;
+
+        self.bytes_by_chunk.map(|bytes| (bytes as f64) / filesize)
+    }
+
+    /// Returns whether `chunk` occurs at least once.
+    pub fn chunk_present(&self, chunk: QoiChunk) -> bool {
+        self.analysis.histogram[chunk as usize] > 0
+    }
+
+    /// Number of `QoiChunk::Index` hits for each dict slot.
+    pub fn index_hit_counts(&self) -> &[usize; 64] {
+        &self.analysis.index_hit_counts
+    }
+
+    /// Dict state at the end of the image.
+    pub fn dict(&self) -> &PixelDict {
+        &self.analysis.dict
+    }
+
+    /// Number of dict writes never read by `QoiChunk::Index`.
+    pub fn wasted_dict_writes(&self) -> usize {
+        self.analysis.wasted_dict_writes
+    }
+
+    /// Number of non-Index pixels which had been in the dict before, but evicted.
+    pub fn missed_index_count(&self) -> usize {
+        self.analysis.missed_index_count
+    }
+
+    /// QOI file size in a hypothetical QOI with 128 dict slots.
+    pub fn filesize_dict128(&self) -> usize {
+        *self
+            .lazy
+            .filesize_dict128
+            .get_or_init(|| filesize_with_dict_bits(&self.img, self.chunks(), 7))
+    }
+
+    /// Encoded bytes of each chunk type. The sum is `filesize_qoi()` minus header and padding.
+    pub fn bytes_by_chunk(&self) -> &[usize; QoiChunk::COUNT] {
+        &self.bytes_by_chunk
+    }
+
+    pub fn transitions(&self) -> &TransitionMatrix {
+        &self.transitions
+    }
+
+    pub fn format_sizes(&self) -> &FormatSizes {
+        self.lazy
+            .format_sizes
+            .get_or_init(|| estimate_sizes(&self.img, self.filesize_qoi()))
+    }
+
+    pub fn run_comparison(&self) -> &RunComparison {
+        self.lazy
+            .run_comparison
+            .get_or_init(|| qoi_analyze_png_vs_qoi_runs(&self.img, self.chunks()))
+    }
+
+    /// Analyzer states taken every `SNAPSHOT_INTERVAL` pixels.
+    pub fn snapshots(&self) -> &[AnalyzerSnapshot] {
+        self.lazy
+            .snapshots
+            .get_or_init(|| take_snapshots(&self.img))
+    }
+
+    /// Returns whether `snapshots()` were taken, i.e. occupy memory.
+    #[allow(dead_code)]
+    pub fn has_snapshots(&self) -> bool {
+        self.lazy.snapshots.get().is_some()
+    }
+
+    /// Number of distinct RGBA values.
+    pub fn distinct_colors(&self) -> usize {
+        *self
+            .lazy
+            .distinct_colors
+            .get_or_init(|| distinct_color_count(&self.img))
+    }
+
+    /// Returns whether any pixel is not fully opaque.
+    pub fn has_variable_alpha(&self) -> bool {
+        self.variable_alpha
+    }
+
+    /// Returns the `n` most frequent colors with their pixel counts, sorted by luminance
+    /// (darkest first).
+    ///
+    /// Only the `DOMINANT_COLOR_MAX` most frequent colors are kept, so at most that many are
+    /// returned even if `n` is larger.
+    pub fn dominant_colors(&self, n: usize) -> Vec<(QoiPixel, usize)> {
+        let dominant_colors = self
+            .lazy
+            .dominant_colors
+            .get_or_init(|| most_frequent(color_counts(&self.img), DOMINANT_COLOR_MAX));
+        let mut colors: Vec<_> = dominant_colors.iter().take(n).copied().collect();
+        colors.sort_by_key(|&(color, _)| ByLuminance(color));
+        colors
+    }
+
+    /// Average pixel delta to the left neighbor (see `stats::spatial_entropy()`).
+    pub fn spatial_entropy(&self) -> f64 {
+        *self
+            .lazy
+            .spatial_entropy
+            .get_or_init(|| spatial_entropy(&self.img))
+    }
+
+    /// Entropy rate of the chunk sequence (see `qoi::chunk_sequence_entropy_rate()`).
+    pub fn sequence_entropy_rate(&self) -> f64 {
+        *self
+            .lazy
+            .sequence_entropy_rate
+            .get_or_init(|| chunk_sequence_entropy_rate(self.chunks()))
+    }
+
+    /// Guesses the kind of this image (see `ImageClass::detect()`).
+    pub fn image_class(&self) -> ImageClass {
+        ImageClass::detect(
+            self.histogram(),
+            self.distinct_colors(),
+            self.spatial_entropy(),
+        )
+    }
+
+    /// QOI size / raw RGBA size.
+    pub fn compression_ratio(&self) -> f64 {
+        (self.filesize_qoi() as f64) / ((4 * self.pixel_count()).max(1) as f64)
+    }
+
+    /// Time taken by the QOI analysis during construction, in milliseconds.
+    pub fn analysis_time_ms(&self) -> f64 {
+        self.analysis_time_ms
+    }
+
+    /// `util::now_ms()` when the analysis finished.
+    pub fn analysis_timestamp(&self) -> f64 {
+        self.analysis_timestamp
+    }
+
+    /// Seconds elapsed since `analysis_timestamp()`.
+    pub fn analysis_age_secs(&self) -> f64 {
+        (util::now_ms() - self.analysis_timestamp) / 1000.
     }
 
     pub fn width(&self) -> u32 {
@@ -87,6 +743,21 @@ impl StaticImage {
     pub fn pixel_count(&self) -> usize {
         (self.width() as usize) * (self.height() as usize)
     }
+
+    /// Estimates the bytes of the largest buffers: the RGBA pixels, the chunk sequence, the run
+    /// lengths and the snapshots (if taken).
+    pub fn estimated_memory_bytes(&self) -> usize {
+        let snapshots: usize = self.lazy.snapshots.get().map_or(0, |snapshots| {
+            snapshots
+                .iter()
+                .map(|snap| std::mem::size_of::<AnalyzerSnapshot>() + snap.dict().heap_bytes())
+                .sum()
+        });
+
+        (4 + std::mem::size_of::<QoiChunk>()) * self.pixel_count()
+            + std::mem::size_of::<u16>() * self.analysis.run_lengths.len()
+            + snapshots
+    }
 }
 
 // default image to avoid managing `Option<StaticImage>`.
@@ -95,18 +766,518 @@ impl Default for StaticImage {
         const DEFAULT_PNG_NAME: &str = "default.png";
         const DEFAULT_PNG: &[u8] =
             include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/asset/default.png"));
-        const URL_PREFIX: &str = "data:image/png;base64,";
 
-        // make Data URL by myself to avoid async.
-        let url_cap = URL_PREFIX.len() + (4 * DEFAULT_PNG.len() + 2) / 3;
-        let mut url = String::with_capacity(url_cap);
-        url.push_str(URL_PREFIX);
-        base64::encode_config_buf(DEFAULT_PNG, base64::STANDARD, &mut url);
+        Self::from_bytes(DEFAULT_PNG_NAME, DEFAULT_PNG).expect("default png image should be valid")
+    }
+}
+
+/// PNG file signature.
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// `gAMA` value of gamma 1.0 (scaled by 100000), i.e. linear.
+const PNG_GAMMA_LINEAR: u32 = 100_000;
+
+/// Guesses the colorspace of image file `buf`, best-effort.
+///
+/// QOI files use the colorspace of the header. Only PNGs with linear `gAMA` (and no `sRGB`
+/// chunk) are detected as `QoiColorspace::Linear`. Others, including JPEGs (JFIF is always
+/// gamma-encoded), are `QoiColorspace::Srgb`.
+pub fn detect_colorspace(buf: &[u8]) -> QoiColorspace {
+    if let Ok(header) = parse_qoi_header(buf) {
+        return match header.colorspace {
+            1 => QoiColorspace::Linear,
+            _ => QoiColorspace::Srgb,
+        };
+    }
+    match png_gamma(buf) {
+        Some(PNG_GAMMA_LINEAR) => QoiColorspace::Linear,
+        _ => QoiColorspace::Srgb,
+    }
+}
+
+/// Returns the value of the `gAMA` chunk of PNG file `buf`.
+///
+/// Returns `None` for non-PNGs, broken PNGs, PNGs without `gAMA`, and PNGs with `sRGB`, which
+/// overrides `gAMA`.
+fn png_gamma(buf: &[u8]) -> Option<u32> {
+    let mut rest = buf.strip_prefix(&PNG_SIGNATURE)?;
+    let mut gamma = None;
+
+    // both must precede IDAT.
+    while rest.len() >= 8 {
+        let len = usize::try_from(u32::from_be_bytes(rest[..4].try_into().ok()?)).ok()?;
+        let data_end = 8usize.checked_add(len)?;
+        let data = rest.get(8..data_end)?;
+        match &rest[4..8] {
+            b"gAMA" => gamma = Some(u32::from_be_bytes(data.try_into().ok()?)),
+            b"sRGB" => return None,
+            b"IDAT" => break,
+            _ => {}
+        }
+        // skip the CRC.
+        rest = rest.get(data_end.checked_add(4)?..)?;
+    }
+
+    gamma
+}
+
+/// Decodes image file `buf`, returning the image and its format (`None` for QOI).
+///
+/// QOI files are decoded by `codec::decode()`, as the `image` crate does not support them.
+fn decode_file(buf: &[u8]) -> anyhow::Result<(RgbaImage, Option<ImageFormat>)> {
+    if buf.starts_with(&QOI_MAGIC) {
+        return Ok((codec::decode(buf)?, None));
+    }
+
+    let format = image::guess_format(buf)?;
+    let img = image::load_from_memory_with_format(buf, format)?;
+    Ok((img.to_rgba8(), Some(format)))
+}
+
+/// Returns the number of distinct RGBA values in `img`.
+pub fn distinct_color_count(img: &RgbaImage) -> usize {
+    img.pixels()
+        .map(|&Rgba(rgba)| QoiPixel::from(rgba))
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+/// Counts pixels of each distinct RGBA value in `img`.
+fn color_counts(img: &RgbaImage) -> HashMap<QoiPixel, usize> {
+    let mut counts = HashMap::<QoiPixel, usize>::new();
+    for &Rgba(rgba) in img.pixels() {
+        *counts.entry(QoiPixel::from(rgba)).or_default() += 1;
+    }
+    counts
+}
+
+/// Returns the `n` most frequent entries of `counts`. Ties are broken by color.
+fn most_frequent(counts: HashMap<QoiPixel, usize>, n: usize) -> Vec<(QoiPixel, usize)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_unstable_by_key(|&(px, count)| (Reverse(count), px));
+    counts.truncate(n);
+    counts
+}
+
+/// Yields control to the browser so that it can render progress.
+async fn yield_now() {
+    cmds::timeout(0, || ()).await
+}
+
+/// Format name of images made from raw pixels rather than an image file.
+const RAW_FORMAT_NAME: &str = "raw RGBA";
+
+/// Returns the display name of `format` (`None` for QOI), e.g. `"PNG"`, `"JPEG"` or `"QOI"`.
+fn name_of_format(format: Option<ImageFormat>) -> String {
+    let format = match format {
+        Some(format) => format,
+        None => return "QOI".to_owned(),
+    };
+    let name = match format {
+        ImageFormat::Png => "PNG",
+        ImageFormat::Jpeg => "JPEG",
+        ImageFormat::Gif => "GIF",
+        ImageFormat::WebP => "WebP",
+        ImageFormat::Bmp => "BMP",
+        ImageFormat::Ico => "ICO",
+        ImageFormat::Tiff => "TIFF",
+        _ => return format!("{:?}", format).to_uppercase(),
+    };
+    name.to_owned()
+}
+
+/// Returns MIME type of `format` if browsers can display it.
+fn mime_of_format(format: ImageFormat) -> Option<&'static str> {
+    match format {
+        ImageFormat::Png => Some("image/png"),
+        ImageFormat::Jpeg => Some("image/jpeg"),
+        ImageFormat::Gif => Some("image/gif"),
+        ImageFormat::WebP => Some("image/webp"),
+        ImageFormat::Bmp => Some("image/bmp"),
+        ImageFormat::Ico => Some("image/x-icon"),
+        ImageFormat::Tiff => Some("image/tiff"),
+        _ => None,
+    }
+}
+
+/// Resizes `img` with Lanczos3 to fit within `max_side` x `max_side`, keeping the aspect ratio.
+///
+/// Returns `None` if `img` already fits.
+fn fit_within(img: &RgbaImage, max_side: u32) -> Option<RgbaImage> {
+    let (w, h) = img.dimensions();
+    if w <= max_side && h <= max_side {
+        return None;
+    }
+
+    let scale = f64::from(max_side) / f64::from(w.max(h));
+    let w_new = ((f64::from(w) * scale).round() as u32).clamp(1, max_side);
+    let h_new = ((f64::from(h) * scale).round() as u32).clamp(1, max_side);
+
+    Some(image::imageops::resize(
+        img,
+        w_new,
+        h_new,
+        FilterType::Lanczos3,
+    ))
+}
+
+/// Appends the dimensions of resized `img` to `name`.
+fn scaled_name(name: &str, img: &RgbaImage) -> String {
+    format!("{} ({}x{})", name, img.width(), img.height())
+}
+
+/// Returns the last path segment of `url` without query and fragment, or `"image"` for empty
+/// paths and Data URLs.
+fn url_basename(url: &str) -> String {
+    if url.starts_with("data:") {
+        return "image".to_owned();
+    }
+
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+
+    if name.is_empty() {
+        "image".to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+fn data_url(mime: &str, data: &[u8]) -> String {
+    let prefix = format!("data:{};base64,", mime);
+
+    let url_cap = prefix.len() + (4 * data.len()).div_ceil(3);
+    let mut url = String::with_capacity(url_cap);
+    url.push_str(&prefix);
+    base64::encode_config_buf(data, base64::STANDARD, &mut url);
+
+    url
+}
+
+pub fn encode_png(img: &RgbaImage) -> anyhow::Result<Vec<u8>> {
+    let mut png = Vec::<u8>::new();
+    PngEncoder::new(&mut png).encode(img, img.width(), img.height(), ColorType::Rgba8)?;
+
+    Ok(png)
+}
+
+pub fn png_data_url(img: &RgbaImage) -> anyhow::Result<String> {
+    let png = encode_png(img)?;
+
+    Ok(data_url("image/png", &png))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_roundtrip() {
+        let img = StaticImage::default();
+
+        let png = img.to_png_bytes();
+        let img_rt = StaticImage::from_bytes(img.name(), &png).unwrap();
+
+        assert_eq!(img_rt.image(), img.image());
+        assert_eq!(img_rt.chunks(), img.chunks());
+        assert_eq!(img_rt.filesize_qoi(), img.filesize_qoi());
+    }
+
+    #[test]
+    fn test_url_basename() {
+        assert_eq!(url_basename("https://example.com/img/cat.png"), "cat.png");
+        assert_eq!(url_basename("https://example.com/a.png?w=100#top"), "a.png");
+        assert_eq!(url_basename("https://example.com/"), "example.com");
+        assert_eq!(url_basename("data:image/png;base64,AAAA"), "image");
+        assert_eq!(url_basename(""), "image");
+    }
+
+    #[test]
+    fn test_from_rgba_bytes() {
+        let img = StaticImage::from_rgba_bytes("blank", 3, 2, [0, 0, 0, 255].repeat(6)).unwrap();
+        assert_eq!((img.width(), img.height()), (3, 2));
+        assert_eq!(img.chunks()[0], QoiChunk::Run8);
+
+        assert!(StaticImage::from_rgba_bytes("blank", 3, 2, vec![0; 4]).is_err());
+    }
+
+    #[test]
+    fn test_chunks_in_region() {
+        let img = RgbaImage::from_fn(3, 3, |x, y| Rgba([(10 * (3 * y + x)) as u8, 0, 0, 255]));
+        let img = StaticImage::new("test", img, "", 0);
+
+        let rows = img.chunks_in_region(1, 1, 2, 2).unwrap();
+        assert_eq!(rows, [&img.chunks()[4..6], &img.chunks()[7..9]]);
+        assert_eq!(
+            img.chunks_in_region(0, 0, 3, 3).unwrap().concat(),
+            img.chunks()
+        );
+        assert!(img
+            .chunks_in_region(3, 0, 0, 3)
+            .unwrap()
+            .iter()
+            .all(|row| row.is_empty()));
+
+        assert!(img.chunks_in_region(2, 0, 2, 1).is_err());
+        assert!(img.chunks_in_region(0, 1, 1, 3).is_err());
+        assert!(img.chunks_in_region(u32::MAX, 0, 2, 1).is_err());
+    }
+
+    #[test]
+    fn test_qoi_roundtrip() {
+        let img = RgbaImage::from_fn(40, 7, |x, y| {
+            Rgba([
+                (x * 7) as u8,
+                (y * 30) as u8,
+                (x / 8) as u8,
+                255 - (y % 3) as u8,
+            ])
+        });
+        let img = StaticImage::new("test", img, "", 0);
+
+        let qoi = img.to_qoi_bytes();
+        assert_eq!(qoi.len(), img.filesize_qoi());
+        assert_eq!(&codec::decode(&qoi).unwrap(), img.image());
+    }
+
+    #[test]
+    fn test_detect_colorspace() {
+        fn chunk(ty: &[u8; 4], data: &[u8]) -> Vec<u8> {
+            // the CRC is not checked.
+            let mut buf = (data.len() as u32).to_be_bytes().to_vec();
+            buf.extend_from_slice(ty);
+            buf.extend_from_slice(data);
+            buf.extend_from_slice(&[0; 4]);
+            buf
+        }
+        let png = |chunks: &[Vec<u8>]| -> Vec<u8> {
+            let mut buf = PNG_SIGNATURE.to_vec();
+            buf.extend(chunks.iter().flatten());
+            buf
+        };
+        let ihdr = chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        let idat = chunk(b"IDAT", &[]);
+        let gama = |gamma: u32| chunk(b"gAMA", &gamma.to_be_bytes());
+
+        let linear = png(&[ihdr.clone(), gama(100_000), idat.clone()]);
+        assert_eq!(detect_colorspace(&linear), QoiColorspace::Linear);
+
+        let srgb = png(&[ihdr.clone(), gama(45_455), idat.clone()]);
+        assert_eq!(detect_colorspace(&srgb), QoiColorspace::Srgb);
+        let overridden = png(&[ihdr.clone(), gama(100_000), chunk(b"sRGB", &[0]), idat]);
+        assert_eq!(detect_colorspace(&overridden), QoiColorspace::Srgb);
+
+        // corrupt length which would overflow `usize` on 32-bit targets.
+        let mut corrupt = png(&[ihdr, gama(100_000)]);
+        corrupt[PNG_SIGNATURE.len()..][..4].copy_from_slice(&0xFFFF_FFF8_u32.to_be_bytes());
+        assert_eq!(png_gamma(&corrupt), None);
+        assert_eq!(detect_colorspace(&corrupt), QoiColorspace::Srgb);
+
+        let png_plain = StaticImage::default().to_png_bytes();
+        assert_eq!(detect_colorspace(&png_plain), QoiColorspace::Srgb);
+        assert_eq!(detect_colorspace(b"not an image"), QoiColorspace::Srgb);
+    }
+
+    #[test]
+    fn test_histogram_fractions() {
+        let img = StaticImage::default();
+
+        let sum: f64 = img.histogram_normalized().iter().sum();
+        assert!((sum - 1.).abs() < 1e-9);
+
+        let sum: f64 = img.histogram_bytes_fraction().iter().sum();
+        let expected = 1. - 18. / (img.filesize_qoi() as f64);
+        assert!((sum - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_dominant_colors() {
+        let img = RgbaImage::from_fn(4, 1, |x, _| Rgba([u8::from(x == 3), 0, 0, 255]));
+        let img = StaticImage::new("test", img, "", 0);
+
+        assert_eq!(
+            img.dominant_colors(5),
+            [
+                (QoiPixel::new(0, 0, 0, 255), 3),
+                (QoiPixel::new(1, 0, 0, 255), 1)
+            ]
+        );
+        assert_eq!(img.dominant_colors(1).len(), 1);
+
+        // the most frequent color is not necessarily the first one.
+        let img = RgbaImage::from_fn(4, 1, |x, _| Rgba([if x == 3 { 0 } else { 255 }, 0, 0, 255]));
+        let img = StaticImage::new("test", img, "", 0);
+        assert_eq!(
+            img.dominant_colors(5),
+            [
+                (QoiPixel::new(0, 0, 0, 255), 1),
+                (QoiPixel::new(255, 0, 0, 255), 3)
+            ]
+        );
+        assert_eq!(img.dominant_colors(1), [(QoiPixel::new(255, 0, 0, 255), 3)]);
+
+        let img = RgbaImage::from_fn(100, 1, |x, _| Rgba([x as u8, 0, 0, 255]));
+        let img = StaticImage::new("test", img, "", 0);
+        assert_eq!(img.dominant_colors(100).len(), DOMINANT_COLOR_MAX);
+    }
+
+    #[test]
+    fn test_distinct_color_count() {
+        let mut img = RgbaImage::new(4, 4);
+        assert_eq!(distinct_color_count(&img), 1);
+
+        img.put_pixel(0, 0, Rgba([1, 2, 3, 4]));
+        img.put_pixel(1, 0, Rgba([1, 2, 3, 5]));
+        img.put_pixel(2, 0, Rgba([1, 2, 3, 4]));
+        assert_eq!(distinct_color_count(&img), 3);
+
+        assert!(StaticImage::new("test", img, "", 0).has_variable_alpha());
+        let img = RgbaImage::from_pixel(4, 4, Rgba([1, 2, 3, 255]));
+        assert!(!StaticImage::new("test", img, "", 0).has_variable_alpha());
+    }
+
+    #[test]
+    fn test_lazy_stats() {
+        let img = StaticImage::default();
+        assert!(!img.has_snapshots());
+        assert_eq!(img.format_sizes().qoi, img.filesize_qoi());
+
+        // clones share the statistics.
+        let img_clone = img.clone();
+        assert!(!img_clone.snapshots().is_empty());
+        assert!(img.has_snapshots());
+        assert!(!img.apply_preprocess(Preprocess::Grayscale).has_snapshots());
+    }
+
+    #[test]
+    fn test_from_bytes_qoi() {
+        let img = StaticImage::default();
+        let qoi = codec::encode(img.image(), QoiColorspace::Linear);
+
+        let img_rt = StaticImage::from_bytes("test.qoi", &qoi).unwrap();
+        assert_eq!(img_rt.image(), img.image());
+        assert_eq!(img_rt.format_name(), "QOI");
+        assert_eq!(img_rt.colorspace(), QoiColorspace::Linear);
+        assert!(img_rt.url().starts_with("data:image/png;base64,"));
+
+        let buf = [&QOI_MAGIC[..], &[0; 10]].concat();
+        assert!(StaticImage::from_bytes("test.qoi", &buf).is_err());
+
+        // header claims more pixels than the data can hold.
+        let mut oversized = qoi.clone();
+        oversized[4..12].fill(0xFF);
+        assert!(StaticImage::from_bytes("test.qoi", &oversized).is_err());
+
+        // header claims fewer pixels than the data holds.
+        let mut inconsistent = qoi;
+        inconsistent[4..12].copy_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1]);
+        assert!(StaticImage::from_bytes("test.qoi", &inconsistent).is_err());
+    }
+
+    #[test]
+    fn test_from_data_url() {
+        let img = StaticImage::default();
+        let png = img.to_png_bytes();
+
+        let img_rt = StaticImage::from_data_url(&data_url("image/png", &png)).unwrap();
+        assert_eq!(img_rt.image(), img.image());
+        assert_eq!(img_rt.filesize_orig(), png.len());
+        assert_eq!(img_rt.format_name(), "PNG");
+        assert_eq!(
+            img_rt.apply_preprocess(Preprocess::Grayscale).format_name(),
+            "PNG"
+        );
+
+        assert!(StaticImage::from_data_url(&data_url("text/plain", &png)).is_err());
+        assert!(StaticImage::from_data_url("data:image/png,abc").is_err());
+        assert!(StaticImage::from_data_url("https://example.com/a.png").is_err());
+    }
+
+    #[test]
+    fn test_resize_for_analysis() {
+        let img = StaticImage::new("test", RgbaImage::new(300, 100), "", 0);
+
+        let img_small = img.resize_for_analysis(30);
+        assert_eq!((img_small.width(), img_small.height()), (30, 10));
+        assert_eq!(img_small.chunks().len(), 300);
+        assert_eq!(img_small.name(), "test (30x10)");
+
+        let img_same = img.resize_for_analysis(300);
+        assert_eq!((img_same.width(), img_same.height()), (300, 100));
+        assert_eq!(img_same.name(), "test");
+    }
+
+    #[test]
+    fn test_split() {
+        let img = RgbaImage::from_fn(5, 2, |x, _| Rgba([x as u8, 0, 0, 255]));
+        let img = StaticImage::new("test", img, "", 0);
+
+        let (left, right) = img.split_horizontally().unwrap();
+        assert_eq!((left.width(), left.height()), (3, 2));
+        assert_eq!((right.width(), right.height()), (2, 2));
+        assert_eq!((left.name(), right.name()), ("test_left", "test_right"));
+        assert_eq!(right.image().get_pixel(0, 0), &Rgba([3, 0, 0, 255]));
+        assert_eq!(right.chunks().len(), 4);
+
+        let (top, bottom) = img.split_vertically().unwrap();
+        assert_eq!((top.width(), top.height()), (5, 1));
+        assert_eq!((bottom.width(), bottom.height()), (5, 1));
+        assert_eq!(bottom.name(), "test_bottom");
+
+        let img = StaticImage::new("test", RgbaImage::new(1, 2), "", 0);
+        assert!(img.split_horizontally().is_none());
+        assert!(img.split_vertically().is_some());
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let img = StaticImage::new("test", RgbaImage::new(400, 100), "", 0);
+        assert!(!img.is_thumbnail());
+        assert_eq!(img.dimensions_label(), "400x100");
+        // RGBA, chunk and run length of each pixel.
+        assert_eq!(img.estimated_memory_bytes(), 7 * 400 * 100);
+        img.snapshots();
+        assert!(img.estimated_memory_bytes() > 7 * 400 * 100 + 400 * 64 * 4);
+
+        let thumb = img.thumbnail(1000);
+        assert!(thumb.is_thumbnail());
+        assert_eq!((thumb.width(), thumb.height()), (128, 32));
+        assert_eq!(thumb.chunks().len(), 128 * 32);
+        assert_eq!(thumb.dimensions_label(), "128x32 (thumbnail of test)");
+
+        let small = StaticImage::new("small", RgbaImage::new(3, 2), "", 0).thumbnail(64);
+        assert_eq!((small.width(), small.height()), (3, 2));
+    }
+
+    #[test]
+    fn test_preprocess_chain() {
+        let img = StaticImage::new("test", RgbaImage::new(30, 10), "", 0);
 
-        let img = image::load_from_memory_with_format(DEFAULT_PNG, ImageFormat::Png)
-            .expect("default png image should be valid");
-        let img = img.to_rgba8();
+        let img = img
+            .with_preprocess(Preprocess::Grayscale)
+            .with_preprocess(Preprocess::Resize(15, 5));
+        assert_eq!((img.width(), img.height()), (15, 5));
+        assert_eq!(img.chunks().len(), 75);
+        assert_eq!(
+            img.preprocess_chain(),
+            [Preprocess::Grayscale, Preprocess::Resize(15, 5)]
+        );
 
-        Self::new(DEFAULT_PNG_NAME, img, url, DEFAULT_PNG.len())
+        let img = img.with_suffix("_small");
+        assert_eq!(img.name(), "test_small");
+        let img = img.with_name("photo.png").with_suffix("_gray");
+        assert_eq!(img.name(), "photo_gray.png");
+        // exports are named after the file stem.
+        assert_eq!(
+            std::path::Path::new(img.name()).file_stem(),
+            Some("photo_gray".as_ref())
+        );
+        let img = img.with_name("renamed");
+        assert_eq!(img.name(), "renamed");
+        assert_eq!(img.chunks().len(), 75);
     }
 }
@@ -0,0 +1,343 @@
+use std::cmp::Reverse;
+
+use image::{Rgba, RgbaImage};
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::pixel::QoiPixel;
+use crate::qoi::QoiChunk;
+
+/// Chunk transition counts. `matrix[i][j]` is the number of times chunk `j` follows chunk `i`.
+pub type TransitionMatrix = [[usize; QoiChunk::COUNT]; QoiChunk::COUNT];
+
+pub fn transition_matrix(chunks: &[QoiChunk]) -> TransitionMatrix {
+    let mut matrix = [[0; QoiChunk::COUNT]; QoiChunk::COUNT];
+
+    for pair in chunks.windows(2) {
+        matrix[pair[0] as usize][pair[1] as usize] += 1;
+    }
+
+    matrix
+}
+
+/// Returns the chunk type which most often follows `chunk` in `matrix` (the earliest type on
+/// ties), or `None` if `chunk` is never followed by any chunk.
+pub fn most_likely_next(matrix: &TransitionMatrix, chunk: QoiChunk) -> Option<QoiChunk> {
+    let row = &matrix[chunk as usize];
+    QoiChunk::iter()
+        .filter(|&next| row[next as usize] > 0)
+        .max_by_key(|&next| (row[next as usize], Reverse(next as usize)))
+}
+
+/// Shannon entropy (in bits) of the distribution given by `counts`.
+pub fn entropy(counts: &[usize]) -> f64 {
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return 0.;
+    }
+    let total = total as f64;
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = (count as f64) / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Average Manhattan distance between each pixel and its left neighbor (0 for the leftmost).
+///
+/// Smooth images have small values.
+pub fn spatial_entropy(img: &RgbaImage) -> f64 {
+    let pixel_count = (img.width() as usize) * (img.height() as usize);
+    if pixel_count == 0 {
+        return 0.;
+    }
+
+    let total: u64 = img
+        .rows()
+        .flat_map(|row| {
+            row.scan(None, |prev: &mut Option<QoiPixel>, &Rgba(rgba)| {
+                let px = QoiPixel::from(rgba);
+                let dist = prev.map_or(0, |prev| px.manhattan_distance(prev));
+                *prev = Some(px);
+                Some(u64::from(dist))
+            })
+        })
+        .sum();
+
+    (total as f64) / (pixel_count as f64)
+}
+
+/// Zeroth-order and first-order entropies of a chunk sequence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChunkEntropy {
+    /// `H(next)`.
+    pub zeroth_order: f64,
+    /// `H(next | prev=i)` for each `i`. `None` if `i` is never followed by any chunk.
+    pub conditional: [Option<f64>; QoiChunk::COUNT],
+    /// `H(next | prev)`, i.e. weighted average of `conditional`.
+    pub first_order: f64,
+}
+
+impl ChunkEntropy {
+    pub fn new(histogram: &[usize; QoiChunk::COUNT], matrix: &TransitionMatrix) -> Self {
+        let zeroth_order = entropy(histogram);
+
+        let mut conditional = [None; QoiChunk::COUNT];
+        let mut first_order = 0.;
+        let mut total = 0;
+        for (i, row) in matrix.iter().enumerate() {
+            let n: usize = row.iter().sum();
+            if n == 0 {
+                continue;
+            }
+            let h = entropy(row);
+            conditional[i] = Some(h);
+            first_order += (n as f64) * h;
+            total += n;
+        }
+        if total > 0 {
+            first_order /= total as f64;
+        }
+
+        Self {
+            zeroth_order,
+            conditional,
+            first_order,
+        }
+    }
+
+    /// Information (in bits per chunk) provided by the previous chunk.
+    pub fn context_gain(&self) -> f64 {
+        self.zeroth_order - self.first_order
+    }
+}
+
+/// Normalized chunk histogram.
+pub type ChunkDistribution = [f64; QoiChunk::COUNT];
+
+/// Minimum number of sample images to compute `ChunkBaseline` from.
+const BASELINE_SAMPLE_MIN: usize = 5;
+
+/// Chunk types deviating more than this many standard deviations are outliers.
+const OUTLIER_SIGMA: f64 = 2.;
+
+/// Mean and standard deviation of chunk type frequencies over images.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChunkBaseline {
+    pub mean: ChunkDistribution,
+    pub std_dev: ChunkDistribution,
+}
+
+impl ChunkBaseline {
+    /// Frequencies in typical images (photos, screenshots and illustrations).
+    ///
+    /// Provisional estimates, to be replaced by the output of `corpus::gen_chunk_baseline()` run
+    /// on the QOI benchmark suite (<https://qoiformat.org/benchmark/>).
+    ///
+    /// Order: Index, Run8, Run16, Diff8, Diff16, Diff24, Color1-4.
+    pub const TYPICAL: Self = Self {
+        mean: [0.10, 0.25, 0.05, 0.20, 0.15, 0.05, 0.08, 0.05, 0.05, 0.02],
+        std_dev: [0.08, 0.15, 0.08, 0.12, 0.10, 0.05, 0.05, 0.04, 0.06, 0.03],
+    };
+
+    /// Computes the baseline from `samples`, or returns `TYPICAL` if there are too few samples.
+    pub fn new(samples: &[ChunkDistribution]) -> Self {
+        if samples.len() < BASELINE_SAMPLE_MIN {
+            return Self::TYPICAL;
+        }
+
+        let n = samples.len() as f64;
+        let mut mean = [0.; QoiChunk::COUNT];
+        let mut std_dev = [0.; QoiChunk::COUNT];
+        for i in 0..QoiChunk::COUNT {
+            mean[i] = samples.iter().map(|dist| dist[i]).sum::<f64>() / n;
+            let var = samples
+                .iter()
+                .map(|dist| (dist[i] - mean[i]).powi(2))
+                .sum::<f64>()
+                / n;
+            std_dev[i] = var.sqrt();
+        }
+
+        Self { mean, std_dev }
+    }
+
+    /// Returns whether each chunk type in `dist` deviates more than 2 standard deviations.
+    pub fn outliers(&self, dist: &ChunkDistribution) -> [bool; QoiChunk::COUNT] {
+        let mut res = [false; QoiChunk::COUNT];
+        for (i, e) in res.iter_mut().enumerate() {
+            *e = (dist[i] - self.mean[i]).abs() > OUTLIER_SIGMA * self.std_dev[i];
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&[]), 0.);
+        assert_eq!(entropy(&[0, 5, 0]), 0.);
+        assert_eq!(entropy(&[1, 1]), 1.);
+        assert_eq!(entropy(&[2, 2, 2, 2]), 2.);
+    }
+
+    #[test]
+    fn test_most_likely_next() {
+        use QoiChunk::*;
+
+        let chunks = [
+            Index, Diff8, Index, Diff8, Index, Run8, Diff8, Color1, Diff8, Color1,
+        ];
+        let matrix = transition_matrix(&chunks);
+        assert_eq!(most_likely_next(&matrix, Index), Some(Diff8));
+        // `Index` and `Color1` follow `Diff8` twice each.
+        assert_eq!(most_likely_next(&matrix, Diff8), Some(Index));
+        assert_eq!(most_likely_next(&matrix, Run8), Some(Diff8));
+        assert_eq!(most_likely_next(&matrix, Color1), Some(Diff8));
+        assert_eq!(most_likely_next(&matrix, Color4), None);
+    }
+
+    #[test]
+    fn test_chunk_entropy() {
+        use QoiChunk::*;
+
+        // alternating sequence: fully predictable from the previous chunk.
+        let chunks = [Index, Diff8, Index, Diff8, Index, Diff8];
+        let matrix = transition_matrix(&chunks);
+        assert_eq!(matrix[Index as usize][Diff8 as usize], 3);
+        assert_eq!(matrix[Diff8 as usize][Index as usize], 2);
+
+        let mut histogram = [0; QoiChunk::COUNT];
+        histogram[Index as usize] = 3;
+        histogram[Diff8 as usize] = 3;
+
+        let ent = ChunkEntropy::new(&histogram, &matrix);
+        assert_eq!(ent.zeroth_order, 1.);
+        assert_eq!(ent.conditional[Index as usize], Some(0.));
+        assert_eq!(ent.conditional[Diff8 as usize], Some(0.));
+        assert_eq!(ent.conditional[Run8 as usize], None);
+        assert_eq!(ent.first_order, 0.);
+        assert_eq!(ent.context_gain(), 1.);
+    }
+
+    #[test]
+    fn test_spatial_entropy() {
+        // rows: [0, 10], [3, 3] (gray levels, alpha 255).
+        let buf = [0, 10, 3, 3].iter().flat_map(|&v| [v, v, v, 255]).collect();
+        let img = RgbaImage::from_vec(2, 2, buf).unwrap();
+
+        // leftmost pixels count as 0. (30 + 0) / 4
+        assert_eq!(spatial_entropy(&img), 7.5);
+        assert_eq!(spatial_entropy(&RgbaImage::new(0, 0)), 0.);
+    }
+
+    #[test]
+    fn test_chunk_baseline() {
+        let mut dist = [0.; QoiChunk::COUNT];
+        dist[QoiChunk::Run8 as usize] = 1.;
+        assert_eq!(ChunkBaseline::new(&[dist]), ChunkBaseline::TYPICAL);
+        assert!(ChunkBaseline::TYPICAL.outliers(&dist)[QoiChunk::Run8 as usize]);
+
+        // Run8 frequencies: 0.1, 0.2, 0.3, 0.4, 0.5 (mean 0.3, std dev ~0.141)
+        let samples: Vec<_> = (1..=5)
+            .map(|i| {
+                let mut dist = [0.; QoiChunk::COUNT];
+                dist[QoiChunk::Run8 as usize] = 0.1 * f64::from(i);
+                dist
+            })
+            .collect();
+        let baseline = ChunkBaseline::new(&samples);
+        assert!((baseline.mean[QoiChunk::Run8 as usize] - 0.3).abs() < 1e-9);
+
+        let outliers = baseline.outliers(&dist);
+        assert!(outliers[QoiChunk::Run8 as usize]);
+        assert_eq!(outliers.iter().filter(|&&e| e).count(), 1);
+        assert!(!baseline.outliers(&samples[4])[QoiChunk::Run8 as usize]);
+    }
+}
+
+/// Generation of the constants derived from an image corpus. Run with
+/// `QOI_CORPUS_DIR=<dir> cargo test --release --features corpus -- --nocapture corpus`.
+///
+/// All images under `QOI_CORPUS_DIR` which can be loaded are used, recursively.
+#[cfg(all(test, feature = "corpus"))]
+mod corpus {
+    use std::path::{Path, PathBuf};
+
+    use crate::qoi::ImageClass;
+    use crate::static_image::StaticImage;
+
+    use super::*;
+
+    fn load_corpus() -> Vec<StaticImage> {
+        fn walk(dir: &Path, paths: &mut Vec<PathBuf>) {
+            for entry in std::fs::read_dir(dir).expect("corpus dir should be readable") {
+                let path = entry.expect("corpus dir should be readable").path();
+                if path.is_dir() {
+                    walk(&path, paths);
+                } else {
+                    paths.push(path);
+                }
+            }
+        }
+
+        let dir = std::env::var("QOI_CORPUS_DIR").expect("QOI_CORPUS_DIR should be set");
+        let mut paths = Vec::new();
+        walk(Path::new(&dir), &mut paths);
+        paths.sort();
+
+        paths
+            .iter()
+            .filter_map(|path| {
+                let buf = std::fs::read(path).ok()?;
+                StaticImage::from_bytes(path.display().to_string(), &buf).ok()
+            })
+            .collect()
+    }
+
+    /// Prints `ChunkBaseline::TYPICAL`.
+    #[test]
+    fn gen_chunk_baseline() {
+        let imgs = load_corpus();
+        let dists: Vec<_> = imgs.iter().map(|img| img.histogram_normalized()).collect();
+        assert!(dists.len() >= BASELINE_SAMPLE_MIN, "too few images");
+
+        let baseline = ChunkBaseline::new(&dists);
+        println!("// {} images", dists.len());
+        println!("mean: {:.3?},", baseline.mean);
+        println!("std_dev: {:.3?},", baseline.std_dev);
+    }
+
+    /// Prints the ratios of `qoi::expected_filesize_for_image_class()`, as the 10th and 90th
+    /// percentiles of each class.
+    #[test]
+    fn gen_expected_filesize() {
+        let imgs = load_corpus();
+        for class in ImageClass::iter() {
+            let mut ratios: Vec<_> = imgs
+                .iter()
+                .filter(|img| img.image_class() == class)
+                .map(|img| img.compression_ratio())
+                .collect();
+            if ratios.is_empty() {
+                println!("// {}: no images", class.name());
+                continue;
+            }
+            ratios.sort_by(f64::total_cmp);
+            let percentile = |p: usize| ratios[(ratios.len() - 1) * p / 100];
+            println!(
+                "ImageClass::{:?} => ({:.2}, {:.2}), // {} images",
+                class,
+                percentile(10),
+                percentile(90),
+                ratios.len()
+            );
+        }
+    }
+}
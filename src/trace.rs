@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+
+use crate::pixel::{DiffOrColor, PixelDiff, QoiPixel};
+use crate::qoi::{AnalyzerSnapshot, QoiAnalyzer, QoiChunk};
+use crate::sensitivity::state_at;
+use crate::static_image::StaticImage;
+
+/// Encoding decision for a single pixel.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceEntry {
+    /// Pixel index in scan order.
+    pub idx: usize,
+    pub x: u32,
+    pub y: u32,
+    pub px: QoiPixel,
+    pub chunk: QoiChunk,
+    /// Human readable reason of the decision, e.g. `"diff8: dr=-1 dg=0 db=1"`.
+    pub reason: String,
+}
+
+/// Returns the decisions for the last `n` pixels before pixel `pos` (`pos` may be the pixel
+/// count), oldest first.
+pub fn trace_before(img: &StaticImage, pos: usize, n: usize) -> VecDeque<TraceEntry> {
+    let pos = pos.min(img.pixel_count());
+    let start = pos.saturating_sub(n);
+    let width = img.width() as usize;
+
+    let mut trace = VecDeque::with_capacity(pos - start);
+    let mut enc = QoiAnalyzer::from_snapshot(&state_at(img, start), |_| {});
+    let pixels = img.image().as_raw().chunks_exact(4);
+    for (idx, rgba) in pixels.enumerate().take(pos).skip(start) {
+        let px = QoiPixel::new(rgba[0], rgba[1], rgba[2], rgba[3]);
+        trace.push_back(TraceEntry {
+            idx,
+            x: (idx % width) as u32,
+            y: (idx / width) as u32,
+            px,
+            chunk: img.chunks()[idx],
+            reason: reason(&enc.snapshot(), px),
+        });
+        enc.update(px);
+    }
+
+    trace
+}
+
+/// Explains why the analyzer in `state` encodes `px` as it does.
+fn reason(state: &AnalyzerSnapshot, px: QoiPixel) -> String {
+    if px == state.px_prev() {
+        return format!("run continues: length {}", state.run() + 1);
+    }
+
+    let slot = state.dict().slot(px);
+    if state.dict()[slot] == px {
+        return format!("dict hit: slot {} = {}", slot, rgba_str(px));
+    }
+
+    match px.sub(state.px_prev()) {
+        DiffOrColor::Diff(diff) => {
+            let (dr, dg, db, da) = diff.deltas();
+            match diff {
+                PixelDiff::Diff8(_) => format!("diff8: dr={} dg={} db={}", dr, dg, db),
+                PixelDiff::Diff16(_) => format!("diff16: dr={} dg={} db={}", dr, dg, db),
+                PixelDiff::Diff24 { .. } => {
+                    format!("diff24: dr={} dg={} db={} da={}", dr, dg, db, da)
+                }
+            }
+        }
+        DiffOrColor::Color(mask) => {
+            let channels: Vec<_> = ["R", "G", "B", "A"]
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| mask & (0b1000 >> i) != 0)
+                .map(|(_, &name)| name)
+                .collect();
+            format!("color: {} changed", channels.join(", "))
+        }
+    }
+}
+
+fn rgba_str(px: QoiPixel) -> String {
+    format!("({},{},{},{})", px.r(), px.g(), px.b(), px.a())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use super::*;
+
+    #[test]
+    fn test_trace_before() {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+        const RED: [u8; 4] = [100, 0, 0, 255];
+
+        let buf: Vec<u8> = [
+            BLACK,
+            [1, 0, 10, 255],
+            BLACK,
+            BLACK,
+            RED,
+            [101, 0, 0, 255],
+            RED,
+        ]
+        .iter()
+        .flatten()
+        .copied()
+        .collect();
+        let img = RgbaImage::from_vec(7, 1, buf).unwrap();
+        let img = StaticImage::new("test", img, "", 0);
+
+        let trace = trace_before(&img, 7, 100);
+        let reasons: Vec<_> = trace.iter().map(|entry| entry.reason.as_str()).collect();
+        assert_eq!(
+            reasons,
+            [
+                "run continues: length 1",
+                "diff24: dr=1 dg=0 db=10 da=0",
+                "diff24: dr=-1 dg=0 db=-10 da=0",
+                "run continues: length 1",
+                "color: R changed",
+                "diff8: dr=1 dg=0 db=0",
+                "dict hit: slot 27 = (100,0,0,255)",
+            ]
+        );
+        assert_eq!(trace[6].chunk, QoiChunk::Index);
+
+        let trace = trace_before(&img, 3, 2);
+        assert_eq!(trace.len(), 2);
+        assert_eq!((trace[0].idx, trace[0].x), (1, 1));
+        assert_eq!(trace[1].px, QoiPixel::from(BLACK));
+        assert_eq!(trace[1].chunk, QoiChunk::Diff24);
+
+        assert_eq!(trace_before(&img, 0, 10).len(), 0);
+    }
+}
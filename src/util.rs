@@ -1,7 +1,12 @@
 use anyhow::anyhow;
 use image::RgbaImage;
-use wasm_bindgen::Clamped;
-use web_sys::ImageData;
+use wasm_bindgen::{Clamped, JsCast};
+use web_sys::{
+    CanvasRenderingContext2d, DomException, HtmlAnchorElement, HtmlCanvasElement, ImageData, Url,
+};
+
+use crate::qoi::qoi_analyze;
+use crate::static_image::StaticImage;
 
 pub fn create_image_data(img: &RgbaImage) -> anyhow::Result<ImageData> {
     let clamped = Clamped(&**img);
@@ -9,3 +14,164 @@ pub fn create_image_data(img: &RgbaImage) -> anyhow::Result<ImageData> {
     ImageData::new_with_u8_clamped_array_and_sh(clamped, img.width(), img.height())
         .map_err(|e| anyhow!("{:?}", e))
 }
+
+/// Reads the pixels of `canvas`. This is the inverse of `create_image_data()`.
+pub fn image_from_canvas(canvas: &HtmlCanvasElement) -> anyhow::Result<RgbaImage> {
+    let (w, h) = (canvas.width(), canvas.height());
+    let ctx = seed::canvas_context_2d(canvas);
+
+    let image_data = ctx
+        .get_image_data(0., 0., f64::from(w), f64::from(h))
+        .map_err(|e| {
+            // cross-origin images make the canvas unreadable.
+            let tainted = e
+                .dyn_ref::<DomException>()
+                .is_some_and(|e| e.name() == "SecurityError");
+            if tainted {
+                anyhow!("canvas is tainted")
+            } else {
+                anyhow!("{:?}", e)
+            }
+        })?;
+
+    RgbaImage::from_raw(w, h, image_data.data().0).ok_or_else(|| anyhow!("invalid ImageData size"))
+}
+
+/// Enlarges `img` by integer factor `scale` (must be positive), repeating each pixel.
+pub fn nearest_neighbor_scale(img: &RgbaImage, scale: u32) -> RgbaImage {
+    debug_assert!(scale > 0);
+
+    RgbaImage::from_fn(img.width() * scale, img.height() * scale, |x, y| {
+        *img.get_pixel(x / scale, y / scale)
+    })
+}
+
+/// Draws `text` centered on pixel `(x, y)` of a canvas showing an image enlarged by `zoom`.
+///
+/// Font, alignment and fill style are taken from `ctx` as is.
+pub fn overlay_text_on_canvas(
+    ctx: &CanvasRenderingContext2d,
+    text: &str,
+    x: u32,
+    y: u32,
+    zoom: u32,
+) {
+    let zoom = f64::from(zoom);
+    let _ = ctx.fill_text(
+        text,
+        zoom * (f64::from(x) + 0.5),
+        zoom * (f64::from(y) + 0.5),
+    );
+}
+
+/// Returns the used JS heap size in bytes from `performance.memory.usedJSHeapSize`.
+///
+/// The API is non-standard (Chromium only), so `None` is returned where unavailable.
+pub fn measure_memory_bytes() -> Option<usize> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        use js_sys::Reflect;
+
+        let performance = seed::window().performance()?;
+        let memory = Reflect::get(&performance, &"memory".into()).ok()?;
+        if memory.is_undefined() {
+            return None;
+        }
+        let used = Reflect::get(&memory, &"usedJSHeapSize".into())
+            .ok()?
+            .as_f64()?;
+        Some(used as usize)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        None
+    }
+}
+
+/// Writes `text` to the clipboard in the background.
+///
+/// `navigator.clipboard` is called dynamically, since it is an unstable API in `web-sys`.
+pub fn copy_to_clipboard(text: &str) -> anyhow::Result<()> {
+    use js_sys::{Function, Reflect};
+
+    let clipboard = Reflect::get(&seed::window().navigator(), &"clipboard".into())
+        .map_err(|e| anyhow!("{:?}", e))?;
+    let write_text = Reflect::get(&clipboard, &"writeText".into())
+        .map_err(|e| anyhow!("{:?}", e))?
+        .dyn_into::<Function>()
+        .map_err(|_| anyhow!("clipboard is not available"))?;
+    write_text
+        .call1(&clipboard, &text.into())
+        .map_err(|e| anyhow!("{:?}", e))?;
+
+    Ok(())
+}
+
+/// Lets the browser download `blob` as `filename`.
+pub fn download_blob(filename: &str, blob: &gloo_file::Blob) -> anyhow::Result<()> {
+    let url = Url::create_object_url_with_blob(blob.as_ref()).map_err(|e| anyhow!("{:?}", e))?;
+
+    let anchor = seed::document()
+        .create_element("a")
+        .map_err(|e| anyhow!("{:?}", e))?
+        .dyn_into::<HtmlAnchorElement>()
+        .map_err(|e| anyhow!("{:?}", e))?;
+    anchor.set_href(&url);
+    anchor.set_download(filename);
+    anchor.click();
+
+    Url::revoke_object_url(&url).map_err(|e| anyhow!("{:?}", e))
+}
+
+/// Returns a high resolution timestamp in milliseconds.
+///
+/// In browsers, `performance.now()` is used.
+pub fn now_ms() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        seed::window()
+            .performance()
+            .expect("performance should be available")
+            .now()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0., |d| 1000. * d.as_secs_f64())
+    }
+}
+
+/// Analyzes `img` again and returns the elapsed time in milliseconds.
+pub fn measure_analysis_time(img: &StaticImage) -> f64 {
+    let start = now_ms();
+    let res = qoi_analyze(img.image());
+    let elapsed = now_ms() - start;
+
+    debug_assert_eq!(res.filesize, img.filesize_qoi());
+
+    elapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn test_nearest_neighbor_scale() {
+        let img = RgbaImage::from_fn(2, 2, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+
+        let scaled = nearest_neighbor_scale(&img, 2);
+        assert_eq!(scaled.dimensions(), (4, 4));
+        for (x, y, px) in scaled.enumerate_pixels() {
+            assert_eq!(px, img.get_pixel(x / 2, y / 2), "({}, {})", x, y);
+        }
+
+        assert_eq!(nearest_neighbor_scale(&img, 1), img);
+    }
+}
@@ -1,28 +1,277 @@
-use image::RgbaImage;
-use strum::EnumCount;
+use std::collections::HashMap;
+use std::fmt::Write as _;
 
-use crate::qoi::QoiChunk;
+use anyhow::ensure;
+use image::imageops::FilterType;
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use strum::{EnumCount, IntoEnumIterator};
+use strum_macros::EnumIter;
+
+use crate::palette::{chunk_colors, PaletteSet};
+use crate::pixel::{PixelDict, QoiPixel, QOI_INITIAL_PIXEL};
+use crate::qoi::{chunk_indices, ChunkFamily, QoiChunk};
 use crate::static_image::StaticImage;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+/// What the visualization shows.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum VisMode {
+    /// Colors each pixel by its chunk type.
+    Chunks,
+    /// Colors each pixel by the value of a channel, ignoring chunk types.
+    ChannelHeatMap { channel: Channel },
+    /// Colors each pixel by the chunk type its difference from the previous pixel fits in,
+    /// ignoring the dict. Pixels equal to the previous one are colored as `Run8`.
+    DiffEligibility,
+    /// Colors each pixel by the bytes spent on it and its neighbors in scan order,
+    /// from green (cheap) to red (expensive).
+    LocalBytePressure,
+    /// Colors each `QoiChunk::Index` pixel by the dict slot it matched, on a rainbow.
+    /// Other pixels are black.
+    IndexSlots,
+    /// Colors each `QoiChunk::Index` pixel by how long ago its dict entry was written, from
+    /// blue (recent) to red (long ago). Other pixels are black.
+    IndexDistance,
+    /// Colors each `QoiChunk::Index` pixel by the hit ratio of its dict slot, from red (rarely
+    /// hit) to green (hit by half of the accesses or more). Other pixels are black.
+    IndexEfficiency,
+    /// Colors each pixel by the channels differing from the previous pixel, mixing R: red,
+    /// G: green, B: blue and A: white additively. Unchanged pixels are black.
+    ChangedChannels,
+    /// Colors each pixel by its chunk family, darker for more bytes (see
+    /// `color_of_chunk_family()`).
+    Family,
+    /// Colors each pixel by the index of the encoded chunk covering it, cycling through
+    /// `CHUNK_BLOCK_COLORS`. Pixels of a run share a color, so chunk boundaries stand out.
+    ChunkBlocks,
+}
+
+/// Text drawn on each pixel at high zoom.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum ChunkLabelMode {
+    None,
+    /// `QoiChunk::abbr()`.
+    Abbreviation,
+    /// `QoiChunk::name()` without the `"QOI_"` prefix, truncated to fit.
+    FullName,
+    /// `QoiChunk::byte_len()`.
+    ByteCost,
+}
+
+impl ChunkLabelMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Abbreviation => "abbreviation",
+            Self::FullName => "full name",
+            Self::ByteCost => "byte cost",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::iter().find(|mode| mode.name() == name)
+    }
+
+    /// Returns the label of `chunk` in at most `max_chars` characters, or `None` for
+    /// `ChunkLabelMode::None`.
+    pub fn text(self, chunk: QoiChunk, max_chars: usize) -> Option<String> {
+        let text: String = match self {
+            Self::None => return None,
+            Self::Abbreviation => chunk.abbr().to_owned(),
+            Self::FullName => chunk.name().trim_start_matches("QOI_").to_owned(),
+            Self::ByteCost => chunk.byte_len().to_string(),
+        };
+        Some(text.chars().take(max_chars).collect())
+    }
+}
+
+/// Which adjacent pixels are separated by `overlay_chunk_borders()`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum ChunkBorderMode {
+    #[default]
+    None,
+    /// Pixels of different chunk types.
+    SameType,
+    /// Pixels of different chunk families. The most legible one.
+    SameFamily,
+    /// Pixels of different byte costs.
+    SameCost,
+}
+
+impl ChunkBorderMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::SameType => "chunk type",
+            Self::SameFamily => "family",
+            Self::SameCost => "byte cost",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::iter().find(|mode| mode.name() == name)
+    }
+
+    /// Returns the group of `chunk`. Adjacent pixels in different groups are separated.
+    fn group(self, chunk: QoiChunk) -> usize {
+        match self {
+            Self::None => 0,
+            Self::SameType => chunk as usize,
+            Self::SameFamily => chunk.family() as usize,
+            Self::SameCost => chunk.byte_len(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter, Serialize, Deserialize)]
+pub enum Channel {
+    R,
+    G,
+    B,
+    A,
+}
+
+impl Channel {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::R => "R",
+            Self::G => "G",
+            Self::B => "B",
+            Self::A => "A",
+        }
+    }
+
+    /// Returns the heat map color for channel value `value`.
+    const fn heat_color(self, value: u8) -> [u8; 3] {
+        match self {
+            Self::R => [value, 0, 0],
+            Self::G => [0, value, 0],
+            Self::B => [0, 0, value],
+            Self::A => [value, value, value],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct VisConfig {
+    mode: VisMode,
     visibles: [bool; QoiChunk::COUNT],
+    palette: PaletteSet,
+    colors: [[u8; 3]; QoiChunk::COUNT],
+    /// Display color of invisible chunks.
+    invisible_color: [u8; 3],
+    /// Spacing of the coordinate grid drawn by `overlay_grid()`, if shown.
+    grid_spacing: Option<u32>,
+    chunk_label_mode: ChunkLabelMode,
+    /// Missing in sessions saved by older versions.
+    #[serde(default)]
+    show_chunk_borders: ChunkBorderMode,
 }
 
 impl VisConfig {
     fn new() -> Self {
         Self {
+            mode: VisMode::Chunks,
             visibles: [true; QoiChunk::COUNT],
+            palette: PaletteSet::Default,
+            colors: chunk_colors(PaletteSet::Default),
+            invisible_color: [0, 0, 0],
+            grid_spacing: None,
+            chunk_label_mode: ChunkLabelMode::Abbreviation,
+            show_chunk_borders: ChunkBorderMode::None,
+        }
+    }
+
+    pub fn mode(&self) -> VisMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: VisMode) {
+        self.mode = mode;
+    }
+
+    /// Returns the display color of `chunk`.
+    pub fn color(&self, chunk: QoiChunk) -> [u8; 3] {
+        self.colors[chunk as usize]
+    }
+
+    /// Returns the display colors of all chunks, indexed by `QoiChunk as usize`.
+    pub fn colors(&self) -> &[[u8; 3]; QoiChunk::COUNT] {
+        &self.colors
+    }
+
+    pub fn set_color(&mut self, chunk: QoiChunk, rgb: [u8; 3]) {
+        self.colors[chunk as usize] = rgb;
+    }
+
+    pub fn palette(&self) -> PaletteSet {
+        self.palette
+    }
+
+    /// Resets all chunk colors to `palette`. Customized colors are discarded.
+    pub fn set_palette(&mut self, palette: PaletteSet) {
+        self.palette = palette;
+        self.colors = chunk_colors(palette);
+    }
+
+    /// Switches default chunk colors between the light and dark palettes.
+    ///
+    /// Customized colors are kept.
+    pub fn set_dark_palette(&mut self, dark: bool) {
+        for chunk in QoiChunk::iter() {
+            let (from, to) = if dark {
+                (color_of_chunk(chunk), color_of_chunk_dark(chunk))
+            } else {
+                (color_of_chunk_dark(chunk), color_of_chunk(chunk))
+            };
+            if self.color(chunk) == from {
+                self.set_color(chunk, to);
+            }
         }
     }
 
+    pub fn invisible_color(&self) -> [u8; 3] {
+        self.invisible_color
+    }
+
+    pub fn set_invisible_color(&mut self, rgb: [u8; 3]) {
+        self.invisible_color = rgb;
+    }
+
+    pub fn grid_spacing(&self) -> Option<u32> {
+        self.grid_spacing
+    }
+
+    pub fn set_grid_spacing(&mut self, spacing: Option<u32>) {
+        self.grid_spacing = spacing;
+    }
+
+    pub fn chunk_label_mode(&self) -> ChunkLabelMode {
+        self.chunk_label_mode
+    }
+
+    pub fn set_chunk_label_mode(&mut self, mode: ChunkLabelMode) {
+        self.chunk_label_mode = mode;
+    }
+
+    pub fn show_chunk_borders(&self) -> ChunkBorderMode {
+        self.show_chunk_borders
+    }
+
+    pub fn set_show_chunk_borders(&mut self, mode: ChunkBorderMode) {
+        self.show_chunk_borders = mode;
+    }
+
     pub fn is_visible(&self, chunk: QoiChunk) -> bool {
         self.visibles[chunk as usize]
     }
 
+    pub fn set_visibility(&mut self, chunk: QoiChunk, visible: bool) {
+        self.visibles[chunk as usize] = visible;
+    }
+
     pub fn toggle_visibility(&mut self, chunk: QoiChunk) {
-        let e = &mut self.visibles[chunk as usize];
-        *e = !*e;
+        self.set_visibility(chunk, !self.is_visible(chunk));
     }
 
     pub fn make_all_visible(&mut self) {
@@ -32,8 +281,69 @@ impl VisConfig {
     pub fn make_all_invisible(&mut self) {
         self.visibles.fill(false);
     }
+
+    pub fn make_only_visible(&mut self, chunk: QoiChunk) {
+        self.make_all_invisible();
+        self.visibles[chunk as usize] = true;
+    }
+
+    /// Returns the chunk visibility changes from `self` to `other`.
+    ///
+    /// Element `i` is `Some(visible)` if the visibility of chunk `i` differs, where `visible` is
+    /// the one in `other`, and `None` otherwise.
+    pub fn diff(self, other: Self) -> [Option<bool>; QoiChunk::COUNT] {
+        let mut res = [None; QoiChunk::COUNT];
+        for (i, e) in res.iter_mut().enumerate() {
+            *e = (self.visibles[i] != other.visibles[i]).then_some(other.visibles[i]);
+        }
+        res
+    }
+
+    /// Returns the visibility of chunks as bits, where bit `i` is chunk `i`.
+    pub fn to_bitmask(self) -> u16 {
+        self.visibles
+            .iter()
+            .enumerate()
+            .filter(|&(_, &visible)| visible)
+            .fold(0, |mask, (i, _)| mask | (1 << i))
+    }
+
+    /// Creates the default config with the visibility given by `to_bitmask()`.
+    ///
+    /// Bits for nonexistent chunks are ignored.
+    pub fn from_bitmask(mask: u16) -> Self {
+        let mut config = Self::new();
+        for (i, visible) in config.visibles.iter_mut().enumerate() {
+            *visible = mask & (1 << i) != 0;
+        }
+        config
+    }
+
+    /// Encodes the visibility into 3 base-36 digits, for the URL hash fragment.
+    pub fn to_hash_fragment(self) -> String {
+        let mask = u32::from(self.to_bitmask());
+        [36 * 36, 36, 1]
+            .iter()
+            .map(|&unit| char::from_digit(mask / unit % 36, 36).expect("digit should be < 36"))
+            .collect()
+    }
+
+    /// Decodes `to_hash_fragment()`. Returns `None` for invalid fragments.
+    pub fn from_hash_fragment(fragment: &str) -> Option<Self> {
+        // `from_str_radix()` accepts a sign.
+        if fragment.len() != HASH_FRAGMENT_LEN
+            || !fragment.bytes().all(|b| b.is_ascii_alphanumeric())
+        {
+            return None;
+        }
+        let mask = u16::from_str_radix(fragment, 36).ok()?;
+        (mask >> QoiChunk::COUNT == 0).then(|| Self::from_bitmask(mask))
+    }
 }
 
+/// Length of `VisConfig::to_hash_fragment()`. 3 base-36 digits cover `QoiChunk::COUNT` bits.
+const HASH_FRAGMENT_LEN: usize = 3;
+
 impl Default for VisConfig {
     fn default() -> Self {
         Self::new()
@@ -41,14 +351,223 @@ impl Default for VisConfig {
 }
 
 pub fn visualize(img: &StaticImage, config: &VisConfig) -> RgbaImage {
+    let buf_rgba: Vec<_> = match config.mode() {
+        VisMode::Chunks => img
+            .chunks()
+            .iter()
+            .flat_map(|&chunk| {
+                let [r, g, b] = if config.is_visible(chunk) {
+                    config.color(chunk)
+                } else {
+                    config.invisible_color()
+                };
+                [r, g, b, 0xFF]
+            })
+            .collect(),
+        VisMode::ChannelHeatMap {
+            channel: Channel::A,
+        } => visualize_alpha_channel(img).into_raw(),
+        VisMode::ChannelHeatMap { channel } => img
+            .image()
+            .pixels()
+            .flat_map(|px| {
+                let [r, g, b] = channel.heat_color(px.0[channel as usize]);
+                [r, g, b, 0xFF]
+            })
+            .collect(),
+        VisMode::DiffEligibility => {
+            let colors = chunk_colors(config.palette());
+            img.image()
+                .pixels()
+                .scan(QOI_INITIAL_PIXEL, |px_prev, &Rgba(rgba)| {
+                    let px = QoiPixel::from(rgba);
+                    let chunk = QoiChunk::of_delta(px, *px_prev);
+                    *px_prev = px;
+                    Some(chunk)
+                })
+                .flat_map(|chunk| {
+                    let [r, g, b] = colors[chunk as usize];
+                    [r, g, b, 0xFF]
+                })
+                .collect()
+        }
+        VisMode::ChangedChannels => img
+            .image()
+            .pixels()
+            .scan(QOI_INITIAL_PIXEL, |px_prev, &Rgba(rgba)| {
+                let px = QoiPixel::from(rgba);
+                let mask = px.channels_changed(*px_prev);
+                *px_prev = px;
+                Some(mask)
+            })
+            .flat_map(|mask| {
+                let [r, g, b] = color_of_channel_mask(mask);
+                [r, g, b, 0xFF]
+            })
+            .collect(),
+        VisMode::IndexSlots => visualize_index_slots(img).into_raw(),
+        VisMode::IndexDistance => visualize_index_distance(img).into_raw(),
+        VisMode::IndexEfficiency => visualize_index_efficiency(img).into_raw(),
+        VisMode::Family => visualize_chunk_family(img, config).into_raw(),
+        VisMode::ChunkBlocks => chunk_indices(img.chunks())
+            .into_iter()
+            .flat_map(|i| {
+                let [r, g, b] = CHUNK_BLOCK_COLORS[i % CHUNK_BLOCK_COLORS.len()];
+                [r, g, b, 0xFF]
+            })
+            .collect(),
+        VisMode::LocalBytePressure => local_byte_pressure(img)
+            .into_iter()
+            .flat_map(|pressure| {
+                let t = pressure / BYTE_PRESSURE_MAX;
+                let r = (255. * t).round() as u8;
+                [r, 0xFF - r, 0, 0xFF]
+            })
+            .collect(),
+    };
+
+    RgbaImage::from_vec(img.width(), img.height(), buf_rgba)
+        .expect("buffer size should be equal to `4 * width * height`")
+}
+
+/// Draws 1 pixel wide lines on every column and row whose coordinate is a multiple of
+/// `grid_spacing`, alpha-blending `color` over `img`.
+///
+/// Does nothing if `grid_spacing` is 0.
+pub fn overlay_grid(img: &mut RgbaImage, grid_spacing: u32, color: [u8; 4]) {
+    if grid_spacing == 0 {
+        return;
+    }
+
+    for (x, y, px) in img.enumerate_pixels_mut() {
+        if x % grid_spacing != 0 && y % grid_spacing != 0 {
+            continue;
+        }
+        blend_over(px, color);
+    }
+}
+
+/// Default color of `overlay_chunk_borders()`, translucent black.
+pub const CHUNK_BORDER_COLOR: [u8; 4] = [0, 0, 0, 128];
+
+/// Alpha-blends `color` over pixels of `img` separated from the left or upper neighbor by
+/// `mode`, which draws 1 pixel wide borders between groups of chunks.
+///
+/// `chunks` must have one chunk per pixel of `img`.
+pub fn overlay_chunk_borders(
+    img: &mut RgbaImage,
+    chunks: &[QoiChunk],
+    mode: ChunkBorderMode,
+    color: [u8; 4],
+) {
+    if mode == ChunkBorderMode::None {
+        return;
+    }
+    debug_assert_eq!(chunks.len(), (img.width() * img.height()) as usize);
+
+    let groups = QoiChunk::iter()
+        .map(|chunk| mode.group(chunk))
+        .collect::<Vec<_>>();
+    let groups: Vec<_> = chunks.iter().map(|&chunk| groups[chunk as usize]).collect();
+
+    // blending results of each channel value, since most pixels may be on borders.
+    let luts: Vec<Vec<u8>> = (0..3)
+        .map(|ch| {
+            (0..=255)
+                .map(|c| {
+                    let mut px = Rgba([c; 4]);
+                    blend_over(&mut px, color);
+                    px.0[ch]
+                })
+                .collect()
+        })
+        .collect();
+
+    let w = img.width() as usize;
+    for (i, px) in img.chunks_exact_mut(4).enumerate() {
+        let left = i % w > 0 && groups[i] != groups[i - 1];
+        let up = i >= w && groups[i] != groups[i - w];
+        if left || up {
+            px[0] = luts[0][usize::from(px[0])];
+            px[1] = luts[1][usize::from(px[1])];
+            px[2] = luts[2][usize::from(px[2])];
+        }
+    }
+}
+
+/// Alpha-blends RGBA `color` over `px`, keeping the alpha of `px`.
+fn blend_over(px: &mut Rgba<u8>, color: [u8; 4]) {
+    let [r, g, b, a] = color.map(u32::from);
+    for (c, src) in px.0[..3].iter_mut().zip([r, g, b]) {
+        *c = ((src * a + u32::from(*c) * (255 - a)) / 255) as u8;
+    }
+}
+
+/// Linearly interpolates every channel from `a` (`t == 0`) to `b` (`t == 1`).
+///
+/// Panics if the dimensions differ.
+pub fn blend_images(a: &RgbaImage, b: &RgbaImage, t: f32) -> RgbaImage {
+    assert_eq!(a.dimensions(), b.dimensions());
+    let t = t.clamp(0., 1.);
+    let buf = a
+        .iter()
+        .zip(b.iter())
+        .map(|(&x, &y)| (f32::from(x) + t * (f32::from(y) - f32::from(x))).round() as u8)
+        .collect();
+    RgbaImage::from_raw(a.width(), a.height(), buf).unwrap()
+}
+
+/// Side length of a color square in `create_legend_image()`.
+pub const LEGEND_CELL_SIDE: u32 = 24;
+
+/// Renders the colors of visible chunks in `config` as squares with black borders, stacked
+/// vertically in `QoiChunk` order.
+///
+/// The image is empty if no chunk is visible.
+pub fn create_legend_image(config: &VisConfig) -> RgbaImage {
+    let chunks: Vec<_> = QoiChunk::iter()
+        .filter(|&chunk| config.is_visible(chunk))
+        .collect();
+    let h = LEGEND_CELL_SIDE * chunks.len() as u32;
+
+    RgbaImage::from_fn(LEGEND_CELL_SIDE, h, |x, y| {
+        let (cy, chunk) = (
+            y % LEGEND_CELL_SIDE,
+            chunks[(y / LEGEND_CELL_SIDE) as usize],
+        );
+        let border = x == 0 || x == LEGEND_CELL_SIDE - 1 || cy == 0 || cy == LEGEND_CELL_SIDE - 1;
+        let [r, g, b] = if border {
+            [0, 0, 0]
+        } else {
+            config.color(chunk)
+        };
+        Rgba([r, g, b, 0xFF])
+    })
+}
+
+/// Colors pixels by alpha only, from black (0) to white (255).
+pub fn visualize_alpha_channel(img: &StaticImage) -> RgbaImage {
+    let buf_rgba: Vec<_> = img
+        .image()
+        .pixels()
+        .flat_map(|&Rgba([_, _, _, a])| [a, a, a, 0xFF])
+        .collect();
+
+    RgbaImage::from_vec(img.width(), img.height(), buf_rgba)
+        .expect("buffer size should be equal to `4 * width * height`")
+}
+
+/// Colors pixels by `color_of_chunk_family()`. Invisible chunks in `config` are
+/// `VisConfig::invisible_color()`.
+pub fn visualize_chunk_family(img: &StaticImage, config: &VisConfig) -> RgbaImage {
     let buf_rgba: Vec<_> = img
         .chunks()
         .iter()
         .flat_map(|&chunk| {
             let [r, g, b] = if config.is_visible(chunk) {
-                color_of_chunk(chunk)
+                color_of_chunk_family(chunk)
             } else {
-                [0, 0, 0]
+                config.invisible_color()
             };
             [r, g, b, 0xFF]
         })
@@ -58,19 +577,1051 @@ pub fn visualize(img: &StaticImage, config: &VisConfig) -> RgbaImage {
         .expect("buffer size should be equal to `4 * width * height`")
 }
 
+/// Renders a stacked bar of chunk type fractions for each column of `img`, in `QoiChunk` order
+/// from the top, colored by `palette` (indexed by `QoiChunk as usize`).
+///
+/// The chart is `img.width()` x `chart_height`.
+pub fn visualize_column_chart(
+    img: &StaticImage,
+    palette: &[[u8; 3]; QoiChunk::COUNT],
+    chart_height: u32,
+) -> RgbaImage {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+
+    let mut counts = vec![[0_usize; QoiChunk::COUNT]; w];
+    for (i, &chunk) in img.chunks().iter().enumerate() {
+        counts[i % w][chunk as usize] += 1;
+    }
+
+    let mut chart = RgbaImage::new(img.width(), chart_height);
+    for (x, counts) in counts.iter().enumerate() {
+        let mut cum = 0;
+        let mut top = 0;
+        for (chunk, &count) in counts.iter().enumerate() {
+            cum += count;
+            // rounded cumulative boundaries make bars always fill the column.
+            let bottom = ((chart_height as usize) * cum + h / 2) / h.max(1);
+            let [r, g, b] = palette[chunk];
+            for y in top..bottom {
+                chart.put_pixel(x as u32, y as u32, Rgba([r, g, b, 0xFF]));
+            }
+            top = bottom;
+        }
+    }
+
+    chart
+}
+
+/// Colors `QoiChunk::Index` pixels by their dict slots on a rainbow, and others black.
+pub fn visualize_index_slots(img: &StaticImage) -> RgbaImage {
+    let buf_rgba: Vec<_> = img
+        .image()
+        .pixels()
+        .zip(img.chunks())
+        .flat_map(|(&Rgba(rgba), &chunk)| {
+            if chunk != QoiChunk::Index {
+                return [0, 0, 0, 0xFF];
+            }
+            // the slot is the hash of the pixel itself.
+            let slot = PixelDict::hash(QoiPixel::from(rgba));
+            let [r, g, b] = hue_color(f64::from(slot) / 64.);
+            [r, g, b, 0xFF]
+        })
+        .collect();
+
+    RgbaImage::from_vec(img.width(), img.height(), buf_rgba)
+        .expect("buffer size should be equal to `4 * width * height`")
+}
+
+/// Colors `QoiChunk::Index` pixels by the distance in scan order to the pixel which wrote the
+/// matched dict entry, from blue (nearest) to red (farthest in `img`). Others are black.
+///
+/// Hits on a never-written (zero-initialized) slot are measured from just before the first pixel.
+pub fn visualize_index_distance(img: &StaticImage) -> RgbaImage {
+    // every chunk except runs and indices writes the pixel into its slot.
+    let mut last_written: [Option<usize>; 64] = [None; 64];
+    let distances: Vec<Option<usize>> = img
+        .image()
+        .pixels()
+        .zip(img.chunks())
+        .enumerate()
+        .map(|(i, (&Rgba(rgba), &chunk))| {
+            let slot = usize::from(PixelDict::hash(QoiPixel::from(rgba)));
+            match chunk {
+                QoiChunk::Index => Some(last_written[slot].map_or(i + 1, |w| i - w)),
+                QoiChunk::Run8 | QoiChunk::Run16 => None,
+                _ => {
+                    last_written[slot] = Some(i);
+                    None
+                }
+            }
+        })
+        .collect();
+    let dist_max = distances
+        .iter()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(2);
+
+    let buf_rgba: Vec<_> = distances
+        .iter()
+        .flat_map(|&dist| match dist {
+            Some(dist) => {
+                let t = (dist - 1) as f64 / (dist_max - 1) as f64;
+                let r = (255. * t).round() as u8;
+                [r, 0, 0xFF - r, 0xFF]
+            }
+            None => [0, 0, 0, 0xFF],
+        })
+        .collect();
+
+    RgbaImage::from_vec(img.width(), img.height(), buf_rgba)
+        .expect("buffer size should be equal to `4 * width * height`")
+}
+
+/// Returns `QoiChunk::Index` hits divided by accesses (every non-run chunk) of each dict slot.
+///
+/// Slots never accessed are 0.
+pub fn index_efficiency(img: &StaticImage) -> [f64; 64] {
+    let mut hits = [0_usize; 64];
+    let mut accesses = [0_usize; 64];
+    for (&Rgba(rgba), &chunk) in img.image().pixels().zip(img.chunks()) {
+        let slot = usize::from(PixelDict::hash(QoiPixel::from(rgba)));
+        match chunk {
+            QoiChunk::Run8 | QoiChunk::Run16 => continue,
+            QoiChunk::Index => hits[slot] += 1,
+            _ => {}
+        }
+        accesses[slot] += 1;
+    }
+
+    std::array::from_fn(|i| hits[i] as f64 / accesses[i].max(1) as f64)
+}
+
+/// Colors `QoiChunk::Index` pixels by `index_efficiency()` of the matched slot, from red (0) to
+/// green (0.5 or more). Others are black.
+pub fn visualize_index_efficiency(img: &StaticImage) -> RgbaImage {
+    let efficiency = index_efficiency(img);
+
+    let buf_rgba: Vec<_> = img
+        .image()
+        .pixels()
+        .zip(img.chunks())
+        .flat_map(|(&Rgba(rgba), &chunk)| {
+            if chunk != QoiChunk::Index {
+                return [0, 0, 0, 0xFF];
+            }
+            let slot = usize::from(PixelDict::hash(QoiPixel::from(rgba)));
+            let t = (2. * efficiency[slot]).min(1.);
+            let g = (255. * t).round() as u8;
+            [0xFF - g, g, 0, 0xFF]
+        })
+        .collect();
+
+    RgbaImage::from_vec(img.width(), img.height(), buf_rgba)
+        .expect("buffer size should be equal to `4 * width * height`")
+}
+
+/// Returns the fully saturated color of hue `hue` (0.0 to 1.0, red to red).
+fn hue_color(hue: f64) -> [u8; 3] {
+    let h = 6. * hue.rem_euclid(1.);
+    let x = 1. - (h % 2. - 1.).abs();
+    let (r, g, b) = match h as u32 {
+        0 => (1., x, 0.),
+        1 => (x, 1., 0.),
+        2 => (0., 1., x),
+        3 => (0., x, 1.),
+        4 => (x, 0., 1.),
+        _ => (1., 0., x),
+    };
+    [r, g, b].map(|c: f64| (255. * c).round() as u8)
+}
+
+/// Maximum of `local_byte_pressure()`: three `QOI_COLOR` chunks of 5 bytes.
+const BYTE_PRESSURE_MAX: f64 = 15.;
+
+/// Returns the sum of bytes spent on each pixel and its left and right neighbors in scan order.
+///
+/// Bytes of a run chunk are shared among the pixels in the run.
+fn local_byte_pressure(img: &StaticImage) -> Vec<f64> {
+    let costs: Vec<f64> = img
+        .chunks()
+        .iter()
+        .zip(img.run_lengths())
+        .map(|(&chunk, &run_len)| {
+            let len = chunk.byte_len() as f64;
+            if run_len == 0 {
+                len
+            } else {
+                len / f64::from(run_len)
+            }
+        })
+        .collect();
+
+    (0..costs.len())
+        .map(|i| {
+            costs[i.saturating_sub(1)..(i + 2).min(costs.len())]
+                .iter()
+                .sum()
+        })
+        .collect()
+}
+
+/// Options of chunk group borders (see `visualize_with_borders()`).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct BorderConfig {
+    families: [bool; ChunkFamily::COUNT],
+    /// `None` means the chunk color darkened by 40%.
+    color: Option<[u8; 3]>,
+    min_group_size: usize,
+}
+
+impl BorderConfig {
+    pub fn is_bordered(&self, family: ChunkFamily) -> bool {
+        self.families[family as usize]
+    }
+
+    pub fn toggle_bordered(&mut self, family: ChunkFamily) {
+        let e = &mut self.families[family as usize];
+        *e = !*e;
+    }
+
+    pub fn color(&self) -> Option<[u8; 3]> {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Option<[u8; 3]>) {
+        self.color = color;
+    }
+
+    /// Groups smaller than this are not bordered.
+    pub fn min_group_size(&self) -> usize {
+        self.min_group_size
+    }
+
+    pub fn set_min_group_size(&mut self, size: usize) {
+        self.min_group_size = size;
+    }
+}
+
+impl Default for BorderConfig {
+    /// Borders only runs of at least 2 pixels.
+    fn default() -> Self {
+        let mut families = [false; ChunkFamily::COUNT];
+        families[ChunkFamily::Run as usize] = true;
+
+        Self {
+            families,
+            color: None,
+            min_group_size: 2,
+        }
+    }
+}
+
+/// Like `visualize()`, but draws 1-pixel borders along the edges of groups of 4-connected
+/// pixels with the same chunk type.
+///
+/// Border pixels are drawn inside groups, replacing the outermost pixels.
+pub fn visualize_with_borders(
+    img: &StaticImage,
+    config: &VisConfig,
+    border_config: &BorderConfig,
+) -> RgbaImage {
+    let mut img_vis = visualize(img, config);
+
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let chunks = img.chunks();
+
+    // union-find over pixels.
+    let mut parents: Vec<usize> = (0..chunks.len()).collect();
+    fn find(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+    for i in 0..chunks.len() {
+        let (x, y) = (i % w, i / w);
+        if x + 1 < w && chunks[i] == chunks[i + 1] {
+            let (a, b) = (find(&mut parents, i), find(&mut parents, i + 1));
+            parents[a] = b;
+        }
+        if y + 1 < h && chunks[i] == chunks[i + w] {
+            let (a, b) = (find(&mut parents, i), find(&mut parents, i + w));
+            parents[a] = b;
+        }
+    }
+    let roots: Vec<_> = (0..chunks.len()).map(|i| find(&mut parents, i)).collect();
+    let mut sizes = vec![0_usize; chunks.len()];
+    for &root in &roots {
+        sizes[root] += 1;
+    }
+
+    for (i, &chunk) in chunks.iter().enumerate() {
+        if !border_config.is_bordered(chunk.family())
+            || sizes[roots[i]] < border_config.min_group_size()
+        {
+            continue;
+        }
+
+        let (x, y) = (i % w, i / w);
+        let is_edge = x == 0
+            || x + 1 == w
+            || y == 0
+            || y + 1 == h
+            || [i - 1, i + 1, i - w, i + w]
+                .iter()
+                .any(|&j| roots[j] != roots[i]);
+        if !is_edge {
+            continue;
+        }
+
+        let [r, g, b] = border_config
+            .color()
+            .unwrap_or_else(|| darken(config.color(chunk)));
+        img_vis.put_pixel(x as u32, y as u32, Rgba([r, g, b, 0xFF]));
+    }
+
+    img_vis
+}
+
+/// Darkens `rgb` by 40%.
+fn darken(rgb: [u8; 3]) -> [u8; 3] {
+    rgb.map(|c| (u16::from(c) * 3 / 5) as u8)
+}
+
+/// Colors run pixels by their run length on a green (short) to blue (long) gradient.
+///
+/// The gradient is logarithmic, up to the longest run in `img`. Non-run pixels are black.
+pub fn visualize_run_length_gradient(img: &StaticImage) -> RgbaImage {
+    let run_lengths = img.run_lengths();
+    let len_max = run_lengths.iter().copied().max().unwrap_or(0).max(2);
+    let log_max = f64::from(len_max).ln();
+
+    let buf_rgba: Vec<_> = run_lengths
+        .iter()
+        .flat_map(|&len| {
+            if len == 0 {
+                return [0, 0, 0, 0xFF];
+            }
+            let t = f64::from(len).ln() / log_max;
+            let b = (255. * t).round() as u8;
+            [0, 0xFF - b, b, 0xFF]
+        })
+        .collect();
+
+    RgbaImage::from_vec(img.width(), img.height(), buf_rgba)
+        .expect("buffer size should be equal to `4 * width * height`")
+}
+
+/// Visualizes differences of chunk assignments between two images of the same dimensions.
+///
+/// Green: same chunk, orange: same family but different size, red: different families.
+/// Pixels where either chunk is invisible are `VisConfig::invisible_color()`.
+pub fn visualize_diff(
+    img_a: &StaticImage,
+    img_b: &StaticImage,
+    config: &VisConfig,
+) -> anyhow::Result<RgbaImage> {
+    const COLOR_SAME: [u8; 3] = [0x00, 0xFF, 0x00];
+    const COLOR_SAME_FAMILY: [u8; 3] = [0xFF, 0x80, 0x00];
+    const COLOR_DIFFERENT: [u8; 3] = [0xFF, 0x00, 0x00];
+
+    ensure!(
+        img_a.width() == img_b.width() && img_a.height() == img_b.height(),
+        "dimensions differ: {}x{} vs {}x{}",
+        img_a.width(),
+        img_a.height(),
+        img_b.width(),
+        img_b.height()
+    );
+
+    let buf_rgba: Vec<_> = img_a
+        .chunks()
+        .iter()
+        .zip(img_b.chunks())
+        .flat_map(|(&a, &b)| {
+            let [r, g, b] = if !config.is_visible(a) || !config.is_visible(b) {
+                config.invisible_color()
+            } else if a == b {
+                COLOR_SAME
+            } else if a.family() == b.family() {
+                COLOR_SAME_FAMILY
+            } else {
+                COLOR_DIFFERENT
+            };
+            [r, g, b, 0xFF]
+        })
+        .collect();
+
+    Ok(RgbaImage::from_vec(img_a.width(), img_a.height(), buf_rgba)
+        .expect("buffer size should be equal to `4 * width * height`"))
+}
+
+/// Generates SVG of the visualization.
+///
+/// Images larger than `SVG_SIDE_MAX` are scaled down with nearest-neighbor sampling.
+/// Same-color pixels are merged into rects to reduce the output size.
+pub fn visualize_svg(img: &StaticImage, config: &VisConfig) -> String {
+    const SVG_SIDE_MAX: u32 = 512;
+
+    let mut img_vis = visualize(img, config);
+    let (w, h) = img_vis.dimensions();
+    if w > SVG_SIDE_MAX || h > SVG_SIDE_MAX {
+        let scale = f64::from(SVG_SIDE_MAX) / f64::from(w.max(h));
+        let w_new = ((f64::from(w) * scale) as u32).max(1);
+        let h_new = ((f64::from(h) * scale) as u32).max(1);
+        img_vis = image::imageops::resize(&img_vis, w_new, h_new, FilterType::Nearest);
+    }
+    let (w, h) = img_vis.dimensions();
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" shape-rendering="crispEdges">"#,
+        w = w,
+        h = h
+    )
+    .unwrap();
+
+    // rects which may be extended downward, keyed by (x, width).
+    let mut opens = HashMap::<(u32, u32), SvgRect>::new();
+
+    for y in 0..h {
+        let mut nexts = HashMap::<(u32, u32), SvgRect>::with_capacity(opens.len());
+
+        let mut x = 0;
+        while x < w {
+            let rgb = rgb_of(&img_vis, x, y);
+            let len = (x..w)
+                .take_while(|&xx| rgb_of(&img_vis, xx, y) == rgb)
+                .count() as u32;
+
+            let rect = match opens.remove(&(x, len)) {
+                Some(mut rect) if rect.rgb == rgb => {
+                    rect.h += 1;
+                    rect
+                }
+                Some(rect) => {
+                    rect.write_to(&mut svg);
+                    SvgRect::new(x, y, len, rgb)
+                }
+                None => SvgRect::new(x, y, len, rgb),
+            };
+            nexts.insert((x, len), rect);
+
+            x += len;
+        }
+
+        for rect in opens.values() {
+            rect.write_to(&mut svg);
+        }
+        opens = nexts;
+    }
+    for rect in opens.values() {
+        rect.write_to(&mut svg);
+    }
+
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+fn rgb_of(img: &RgbaImage, x: u32, y: u32) -> [u8; 3] {
+    let [r, g, b, _] = img.get_pixel(x, y).0;
+    [r, g, b]
+}
+
+#[derive(Debug)]
+struct SvgRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    rgb: [u8; 3],
+}
+
+impl SvgRect {
+    fn new(x: u32, y: u32, w: u32, rgb: [u8; 3]) -> Self {
+        Self { x, y, w, h: 1, rgb }
+    }
+
+    fn write_to(&self, svg: &mut String) {
+        let [r, g, b] = self.rgb;
+        writeln!(
+            svg,
+            r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#{:02X}{:02X}{:02X}"/>"##,
+            self.x, self.y, self.w, self.h, r, g, b
+        )
+        .unwrap();
+    }
+}
+
+/// Returns the default display color of `chunk`.
 pub const fn color_of_chunk(chunk: QoiChunk) -> [u8; 3] {
+    let [r, g, b, _] = color_of_chunk_rgba(chunk);
+    [r, g, b]
+}
+
+/// Returns the default display color of `chunk` as opaque RGBA, ready to put into images.
+pub const fn color_of_chunk_rgba(chunk: QoiChunk) -> [u8; 4] {
+    const COLORS: &[[u8; 4]] = &[
+        [0xFF, 0xFF, 0x00, 0xFF], // Index
+        [0xC0, 0xC0, 0xC0, 0xFF], // Run8
+        [0x80, 0x80, 0x80, 0xFF], // Run16
+        [0x00, 0xFF, 0xFF, 0xFF], // Diff8
+        [0x00, 0xC0, 0xC0, 0xFF], // Diff16
+        [0x00, 0x80, 0x80, 0xFF], // Diff24
+        [0xFF, 0x00, 0x00, 0xFF], // Color1
+        [0xC0, 0x00, 0x00, 0xFF], // Color2
+        [0x80, 0x00, 0x00, 0xFF], // Color3
+        [0x40, 0x00, 0x00, 0xFF], // Color4
+    ];
+
+    COLORS[chunk as usize]
+}
+
+/// Colors of `VisMode::ChunkBlocks`, cycled by chunk index.
+const CHUNK_BLOCK_COLORS: [[u8; 3]; 8] = [
+    [0xE6, 0x19, 0x4B],
+    [0x3C, 0xB4, 0x4B],
+    [0xFF, 0xE1, 0x19],
+    [0x43, 0x63, 0xD8],
+    [0xF5, 0x82, 0x31],
+    [0x91, 0x1E, 0xB4],
+    [0x42, 0xD4, 0xF4],
+    [0xF0, 0x32, 0xE6],
+];
+
+/// Returns the `VisMode::ChangedChannels` color of `QoiPixel::channels_changed()` mask `mask`.
+fn color_of_channel_mask(mask: u8) -> [u8; 3] {
+    if mask & 0b0001 != 0 {
+        return [0xFF, 0xFF, 0xFF];
+    }
+    let level = |bit: u8| if mask & bit != 0 { 0xFF } else { 0 };
+    [level(0b1000), level(0b0100), level(0b0010)]
+}
+
+/// Returns the color of the family of `chunk` (Index: yellow, Run: gray, Diff: cyan, Color: red),
+/// darkened by a quarter for each byte more than the smallest chunk in the family.
+pub const fn color_of_chunk_family(chunk: QoiChunk) -> [u8; 3] {
+    let (base, len_min) = match chunk.family() {
+        ChunkFamily::Index => ([0xFF, 0xFF, 0x00], 1),
+        ChunkFamily::Run => ([0xC0, 0xC0, 0xC0], 1),
+        ChunkFamily::Diff => ([0x00, 0xFF, 0xFF], 1),
+        ChunkFamily::Color => ([0xFF, 0x00, 0x00], 2),
+    };
+    let level = 4 - (chunk.byte_len() - len_min) as u16;
+
+    let mut rgb = [0; 3];
+    let mut i = 0;
+    while i < 3 {
+        rgb[i] = (base[i] as u16 * level / 4) as u8;
+        i += 1;
+    }
+    rgb
+}
+
+/// Returns the default display color of `chunk` in dark mode, lighter than `color_of_chunk()`.
+pub const fn color_of_chunk_dark(chunk: QoiChunk) -> [u8; 3] {
     const COLORS: &[[u8; 3]] = &[
-        [0xFF, 0xFF, 0x00], // Index
-        [0xC0, 0xC0, 0xC0], // Run8
-        [0x80, 0x80, 0x80], // Run16
-        [0x00, 0xFF, 0xFF], // Diff8
-        [0x00, 0xC0, 0xC0], // Diff16
-        [0x00, 0x80, 0x80], // Diff24
-        [0xFF, 0x00, 0x00], // Color1
-        [0xC0, 0x00, 0x00], // Color2
-        [0x80, 0x00, 0x00], // Color3
-        [0x40, 0x00, 0x00], // Color4
+        [0xFF, 0xFF, 0x60], // Index
+        [0xE0, 0xE0, 0xE0], // Run8
+        [0xA0, 0xA0, 0xA0], // Run16
+        [0x60, 0xFF, 0xFF], // Diff8
+        [0x30, 0xE0, 0xE0], // Diff16
+        [0x20, 0xB0, 0xB0], // Diff24
+        [0xFF, 0x50, 0x50], // Color1
+        [0xE0, 0x30, 0x30], // Color2
+        [0xB0, 0x20, 0x20], // Color3
+        [0x80, 0x18, 0x18], // Color4
     ];
 
     COLORS[chunk as usize]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn static_image(pixels: &[[u8; 4]]) -> StaticImage {
+        let buf: Vec<u8> = pixels.iter().flatten().copied().collect();
+        let img = RgbaImage::from_vec(pixels.len() as u32, 1, buf).unwrap();
+        StaticImage::new("test", img, "", 0)
+    }
+
+    #[test]
+    fn test_visualize_diff() {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+        let img_a = static_image(&[BLACK, BLACK, [5, 0, 0, 255], BLACK]); // Run8, Run8, Diff16, Diff16
+        let img_b = static_image(&[BLACK, BLACK, [1, 0, 0, 255], BLACK]); // Run8, Run8, Diff8, Diff8
+        let img_c = static_image(&[BLACK; 4]); // Run8 * 4
+
+        let config = VisConfig::default();
+
+        let diff = visualize_diff(&img_a, &img_b, &config).unwrap();
+        assert_eq!(diff.get_pixel(0, 0).0, [0x00, 0xFF, 0x00, 0xFF]);
+        assert_eq!(diff.get_pixel(2, 0).0, [0xFF, 0x80, 0x00, 0xFF]);
+
+        let diff = visualize_diff(&img_a, &img_c, &config).unwrap();
+        assert_eq!(diff.get_pixel(1, 0).0, [0x00, 0xFF, 0x00, 0xFF]);
+        assert_eq!(diff.get_pixel(2, 0).0, [0xFF, 0x00, 0x00, 0xFF]);
+
+        let mut config = VisConfig::default();
+        config.toggle_visibility(QoiChunk::Diff16);
+        let diff = visualize_diff(&img_a, &img_c, &config).unwrap();
+        assert_eq!(diff.get_pixel(2, 0).0, [0x00, 0x00, 0x00, 0xFF]);
+
+        let img_d = static_image(&[BLACK; 3]);
+        assert!(visualize_diff(&img_a, &img_d, &config).is_err());
+    }
+
+    #[test]
+    fn test_visualize_with_borders() {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+        const RED: [u8; 4] = [100, 0, 0, 255];
+
+        // Color1, Run8 * 4, Color1, Index
+        let img = static_image(&[RED, RED, RED, RED, RED, BLACK, RED]);
+        let config = VisConfig::default();
+        let border_config = BorderConfig::default();
+
+        let vis = visualize_with_borders(&img, &config, &border_config);
+        let run_color = darken(config.color(QoiChunk::Run8));
+        let color1 = config.color(QoiChunk::Color1);
+        assert_eq!(&vis.get_pixel(0, 0).0[..3], color1);
+        for x in 1..5 {
+            // 1-pixel high image: every pixel is on the edge.
+            assert_eq!(&vis.get_pixel(x, 0).0[..3], run_color);
+        }
+        assert_eq!(&vis.get_pixel(5, 0).0[..3], color1);
+
+        let mut border_config = BorderConfig::default();
+        border_config.set_min_group_size(5);
+        let vis = visualize_with_borders(&img, &config, &border_config);
+        assert_eq!(vis, visualize(&img, &config));
+    }
+
+    #[test]
+    fn test_visualize_run_length_gradient() {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+        const RED: [u8; 4] = [100, 0, 0, 255];
+
+        // Color1, Run8 * 1, Color1, Run8 * 3
+        let img = static_image(&[RED, RED, BLACK, BLACK, BLACK, BLACK]);
+        assert_eq!(img.run_lengths(), [0, 1, 0, 3, 3, 3]);
+
+        let vis = visualize_run_length_gradient(&img);
+        assert_eq!(vis.get_pixel(0, 0).0, [0, 0, 0, 0xFF]);
+        assert_eq!(vis.get_pixel(1, 0).0, [0, 0xFF, 0, 0xFF]);
+        assert_eq!(vis.get_pixel(3, 0).0, [0, 0, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_local_byte_pressure() {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+        const RED: [u8; 4] = [100, 0, 0, 255];
+
+        // Color1, Run8 * 2, Color1, Run8 * 1
+        let img = static_image(&[RED, RED, RED, BLACK, BLACK]);
+        assert_eq!(local_byte_pressure(&img), [2.5, 3., 3., 3.5, 3.]);
+    }
+
+    #[test]
+    fn test_bitmask_roundtrip() {
+        for mask in 0..1 << QoiChunk::COUNT {
+            let config = VisConfig::from_bitmask(mask);
+            assert_eq!(config.to_bitmask(), mask);
+
+            let fragment = config.to_hash_fragment();
+            assert_eq!(fragment.len(), 3);
+            assert_eq!(VisConfig::from_hash_fragment(&fragment), Some(config));
+        }
+
+        assert_eq!(VisConfig::default().to_bitmask(), 0x3FF);
+        assert_eq!(VisConfig::from_hash_fragment("zzz"), None);
+        assert_eq!(VisConfig::from_hash_fragment("1"), None);
+        assert_eq!(VisConfig::from_hash_fragment("+1a"), None);
+    }
+
+    #[test]
+    fn test_make_only_visible() {
+        let mut config = VisConfig::default();
+        config.make_only_visible(QoiChunk::Diff16);
+
+        for chunk in QoiChunk::iter() {
+            assert_eq!(config.is_visible(chunk), chunk == QoiChunk::Diff16);
+        }
+
+        config.set_visibility(QoiChunk::Run8, true);
+        config.set_visibility(QoiChunk::Run8, true);
+        assert!(config.is_visible(QoiChunk::Run8));
+        config.set_visibility(QoiChunk::Diff16, false);
+        assert!(!config.is_visible(QoiChunk::Diff16));
+    }
+
+    #[test]
+    fn test_config_diff() {
+        let config = VisConfig::default();
+        assert_eq!(config.diff(VisConfig::default()), [None; QoiChunk::COUNT]);
+
+        let mut other = config;
+        other.toggle_visibility(QoiChunk::Run8);
+        let mut expected = [None; QoiChunk::COUNT];
+        expected[QoiChunk::Run8 as usize] = Some(false);
+        assert_eq!(config.diff(other), expected);
+        expected[QoiChunk::Run8 as usize] = Some(true);
+        assert_eq!(other.diff(config), expected);
+    }
+
+    #[test]
+    fn test_config_serde() {
+        let mut config = VisConfig::default();
+        config.set_mode(VisMode::ChannelHeatMap {
+            channel: Channel::G,
+        });
+        config.set_palette(PaletteSet::Print);
+        config.toggle_visibility(QoiChunk::Diff8);
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(serde_json::from_str::<VisConfig>(&json).unwrap(), config);
+    }
+
+    #[test]
+    fn test_visualize_index_slots() {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+        const RED: [u8; 4] = [100, 0, 0, 255];
+
+        // Color1, Color1, Index (slot of RED)
+        let img = static_image(&[RED, BLACK, RED]);
+        let vis = visualize_index_slots(&img);
+        assert_eq!(vis.get_pixel(0, 0).0, [0, 0, 0, 0xFF]);
+        assert_eq!(vis.get_pixel(1, 0).0, [0, 0, 0, 0xFF]);
+        let slot = PixelDict::hash(QoiPixel::from(RED));
+        let [r, g, b] = hue_color(f64::from(slot) / 64.);
+        assert_eq!(vis.get_pixel(2, 0).0, [r, g, b, 0xFF]);
+
+        assert_eq!(hue_color(0.), [0xFF, 0, 0]);
+        assert_eq!(hue_color(1. / 3.), [0, 0xFF, 0]);
+        assert_eq!(hue_color(2. / 3.), [0, 0, 0xFF]);
+    }
+
+    #[test]
+    fn test_overlay_chunk_borders() {
+        use QoiChunk::*;
+
+        // 3x2: Diff8 Diff16 Index / Diff8 Diff8 Run8
+        let chunks = [Diff8, Diff16, Index, Diff8, Diff8, Run8];
+        let borders = |mode| {
+            let mut img = RgbaImage::from_pixel(3, 2, Rgba([200, 200, 200, 0xFF]));
+            overlay_chunk_borders(&mut img, &chunks, mode, CHUNK_BORDER_COLOR);
+            img.pixels().map(|px| px.0[0] != 200).collect::<Vec<_>>()
+        };
+
+        assert_eq!(borders(ChunkBorderMode::None), [false; 6]);
+        assert_eq!(
+            borders(ChunkBorderMode::SameType),
+            [false, true, true, false, true, true]
+        );
+        assert_eq!(
+            borders(ChunkBorderMode::SameFamily),
+            [false, false, true, false, false, true]
+        );
+        assert_eq!(
+            borders(ChunkBorderMode::SameCost),
+            [false, true, true, false, true, false]
+        );
+
+        let mut img = RgbaImage::from_pixel(1, 1, Rgba([200, 200, 200, 0xFF]));
+        blend_over(img.get_pixel_mut(0, 0), CHUNK_BORDER_COLOR);
+        assert_eq!(img.get_pixel(0, 0).0, [99, 99, 99, 0xFF]);
+
+        for mode in ChunkBorderMode::iter() {
+            assert_eq!(ChunkBorderMode::from_name(mode.name()), Some(mode));
+        }
+    }
+
+    /// Wall-clock timing, so only with `--release --features bench` (see `pixel::benches`).
+    #[cfg(feature = "bench")]
+    #[test]
+    fn test_overlay_chunk_borders_speed() {
+        use rand::{Rng, SeedableRng};
+
+        const SIDE: u32 = 512;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let all: Vec<_> = QoiChunk::iter().collect();
+        let chunks: Vec<_> = (0..SIDE * SIDE)
+            .map(|_| all[rng.gen_range(0, all.len())])
+            .collect();
+        let mut img = RgbaImage::new(SIDE, SIDE);
+
+        let start = std::time::Instant::now();
+        overlay_chunk_borders(
+            &mut img,
+            &chunks,
+            ChunkBorderMode::SameFamily,
+            CHUNK_BORDER_COLOR,
+        );
+        let elapsed = start.elapsed();
+        assert!(elapsed.as_millis() < 50, "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_visualize_index_distance() {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+        const RED: [u8; 4] = [100, 0, 0, 255];
+
+        // Color1, Color1, Index (distance 2), Run8, Index (distance 3)
+        let img = static_image(&[RED, BLACK, RED, RED, BLACK]);
+        let vis = visualize_index_distance(&img);
+        assert_eq!(vis.get_pixel(0, 0).0, [0, 0, 0, 0xFF]);
+        assert_eq!(vis.get_pixel(1, 0).0, [0, 0, 0, 0xFF]);
+        assert_eq!(vis.get_pixel(2, 0).0, [0x80, 0, 0x7F, 0xFF]);
+        assert_eq!(vis.get_pixel(3, 0).0, [0, 0, 0, 0xFF]);
+        assert_eq!(vis.get_pixel(4, 0).0, [0xFF, 0, 0, 0xFF]);
+
+        // Index into the zero-initialized dict (distance 1), Run8
+        let img = static_image(&[[0; 4], [0; 4]]);
+        assert_eq!(img.chunks(), [QoiChunk::Index, QoiChunk::Run8]);
+        let vis = visualize_index_distance(&img);
+        assert_eq!(vis.get_pixel(0, 0).0, [0, 0, 0xFF, 0xFF]);
+        assert_eq!(vis.get_pixel(1, 0).0, [0, 0, 0, 0xFF]);
+    }
+
+    #[test]
+    fn test_visualize_index_efficiency() {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+        const RED: [u8; 4] = [100, 0, 0, 255];
+        // same dict slot as `BLACK`.
+        const DARK_RED: [u8; 4] = [64, 0, 0, 255];
+
+        let img = static_image(&[RED, BLACK, DARK_RED, BLACK, RED, BLACK]);
+        assert_eq!(
+            img.chunks()[3..],
+            [QoiChunk::Color1, QoiChunk::Index, QoiChunk::Index]
+        );
+
+        let efficiency = index_efficiency(&img);
+        let slot = |px| usize::from(PixelDict::hash(QoiPixel::from(px)));
+        assert_eq!(efficiency[slot(RED)], 0.5);
+        assert_eq!(efficiency[slot(BLACK)], 0.25);
+
+        let vis = visualize_index_efficiency(&img);
+        assert_eq!(vis.get_pixel(3, 0).0, [0, 0, 0, 0xFF]);
+        assert_eq!(vis.get_pixel(4, 0).0, [0, 0xFF, 0, 0xFF]);
+        assert_eq!(vis.get_pixel(5, 0).0, [0x7F, 0x80, 0, 0xFF]);
+    }
+
+    #[test]
+    fn test_set_dark_palette() {
+        const CUSTOM: [u8; 3] = [1, 2, 3];
+
+        let mut config = VisConfig::default();
+        config.set_color(QoiChunk::Run8, CUSTOM);
+
+        config.set_dark_palette(true);
+        assert_eq!(
+            config.color(QoiChunk::Index),
+            color_of_chunk_dark(QoiChunk::Index)
+        );
+        assert_eq!(config.color(QoiChunk::Run8), CUSTOM);
+
+        config.set_dark_palette(false);
+        assert_eq!(
+            config.color(QoiChunk::Index),
+            color_of_chunk(QoiChunk::Index)
+        );
+        assert_eq!(config.color(QoiChunk::Run8), CUSTOM);
+    }
+
+    #[test]
+    fn test_visualize_diff_eligibility() {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+        const RED: [u8; 4] = [100, 0, 0, 255];
+
+        // encoded as Run8, Color1, Index, Index. index selection is ignored, and the deltas of
+        // +-100 are too large for any diff, so the last two are Color1 as well.
+        let img = static_image(&[BLACK, RED, BLACK, RED]);
+
+        let mut config = VisConfig::default();
+        config.set_mode(VisMode::DiffEligibility);
+        let vis = visualize(&img, &config);
+
+        let expected = [
+            QoiChunk::Run8,
+            QoiChunk::Color1,
+            QoiChunk::Color1,
+            QoiChunk::Color1,
+        ];
+        for (x, chunk) in expected.into_iter().enumerate() {
+            assert_eq!(vis.get_pixel(x as u32, 0).0, color_of_chunk_rgba(chunk));
+        }
+    }
+
+    #[test]
+    fn test_chunk_label_text() {
+        let text = |mode: ChunkLabelMode, max_chars| mode.text(QoiChunk::Diff16, max_chars);
+
+        assert_eq!(text(ChunkLabelMode::None, 10), None);
+        assert_eq!(text(ChunkLabelMode::Abbreviation, 10).unwrap(), "D6");
+        assert_eq!(text(ChunkLabelMode::FullName, 10).unwrap(), "DIFF_16");
+        assert_eq!(text(ChunkLabelMode::FullName, 3).unwrap(), "DIF");
+        assert_eq!(text(ChunkLabelMode::ByteCost, 10).unwrap(), "2");
+
+        for mode in ChunkLabelMode::iter() {
+            assert_eq!(ChunkLabelMode::from_name(mode.name()), Some(mode));
+        }
+    }
+
+    #[test]
+    fn test_overlay_grid() {
+        let mut img = RgbaImage::from_pixel(5, 5, Rgba([0, 0, 0, 0xFF]));
+        overlay_grid(&mut img, 4, [0xFF, 0xFF, 0xFF, 0xFF]);
+
+        let lines: Vec<_> = (0..5).map(|x| img.get_pixel(x, 2).0[0]).collect();
+        assert_eq!(lines, [0xFF, 0, 0, 0, 0xFF]);
+        assert_eq!(img.get_pixel(2, 4).0, [0xFF, 0xFF, 0xFF, 0xFF]);
+
+        let mut img = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 0xFF]));
+        overlay_grid(&mut img, 2, [0xFF, 0, 0, 0x80]);
+        assert_eq!(img.get_pixel(0, 1).0, [0x80, 0, 0, 0xFF]);
+        assert_eq!(img.get_pixel(1, 1).0, [0, 0, 0, 0xFF]);
+
+        overlay_grid(&mut img, 0, [0xFF, 0, 0, 0xFF]);
+        assert_eq!(img.get_pixel(1, 1).0, [0, 0, 0, 0xFF]);
+    }
+
+    #[test]
+    fn test_blend_images() {
+        let a = RgbaImage::from_pixel(2, 1, Rgba([0, 0x10, 0xFF, 0xFF]));
+        let b = RgbaImage::from_pixel(2, 1, Rgba([0xFF, 0x10, 0, 0]));
+
+        assert_eq!(blend_images(&a, &b, 0.), a);
+        assert_eq!(blend_images(&a, &b, 1.), b);
+        assert_eq!(
+            blend_images(&a, &b, 0.5).get_pixel(1, 0).0,
+            [0x80, 0x10, 0x80, 0x80]
+        );
+        assert_eq!(blend_images(&a, &b, 2.), b);
+    }
+
+    #[test]
+    fn test_create_legend_image() {
+        let mut config = VisConfig::default();
+        config.make_all_invisible();
+        assert_eq!(create_legend_image(&config).dimensions(), (24, 0));
+
+        config.toggle_visibility(QoiChunk::Run8);
+        config.toggle_visibility(QoiChunk::Color4);
+        let legend = create_legend_image(&config);
+        assert_eq!(legend.dimensions(), (24, 48));
+
+        let [r, g, b] = config.color(QoiChunk::Color4);
+        assert_eq!(legend.get_pixel(12, 36).0, [r, g, b, 0xFF]);
+        assert_eq!(legend.get_pixel(12, 24).0, [0, 0, 0, 0xFF]);
+        assert_eq!(legend.get_pixel(23, 36).0, [0, 0, 0, 0xFF]);
+    }
+
+    #[test]
+    fn test_visualize_chunk_family() {
+        assert_eq!(color_of_chunk_family(QoiChunk::Diff8), [0x00, 0xFF, 0xFF]);
+        assert_eq!(color_of_chunk_family(QoiChunk::Diff24), [0x00, 0x7F, 0x7F]);
+        assert_eq!(color_of_chunk_family(QoiChunk::Color1), [0xFF, 0x00, 0x00]);
+        assert_eq!(color_of_chunk_family(QoiChunk::Color4), [0x3F, 0x00, 0x00]);
+
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+        const RED: [u8; 4] = [100, 0, 0, 255];
+
+        // Run8, Color1, Color1, Index
+        let img = static_image(&[BLACK, RED, BLACK, RED]);
+
+        let mut config = VisConfig::default();
+        config.set_mode(VisMode::Family);
+        config.toggle_visibility(QoiChunk::Index);
+        let vis = visualize(&img, &config);
+
+        assert_eq!(vis.get_pixel(0, 0).0, [0xC0, 0xC0, 0xC0, 0xFF]);
+        assert_eq!(vis.get_pixel(1, 0).0, [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(vis.get_pixel(3, 0).0, [0x00, 0x00, 0x00, 0xFF]);
+
+        config.set_invisible_color([128, 128, 128]);
+        let vis = visualize(&img, &config);
+        assert_eq!(vis.get_pixel(3, 0).0, [128, 128, 128, 0xFF]);
+    }
+
+    #[test]
+    fn test_visualize_changed_channels() {
+        // unchanged, R, R+G, A
+        let img = static_image(&[[0, 0, 0, 255], [9, 0, 0, 255], [0, 9, 0, 255], [0, 9, 0, 0]]);
+
+        let mut config = VisConfig::default();
+        config.set_mode(VisMode::ChangedChannels);
+        let vis = visualize(&img, &config);
+
+        let expected = [
+            [0x00, 0x00, 0x00, 0xFF],
+            [0xFF, 0x00, 0x00, 0xFF],
+            [0xFF, 0xFF, 0x00, 0xFF],
+            [0xFF, 0xFF, 0xFF, 0xFF],
+        ];
+        for (x, rgba) in expected.into_iter().enumerate() {
+            assert_eq!(vis.get_pixel(x as u32, 0).0, rgba);
+        }
+    }
+
+    #[test]
+    fn test_visualize_column_chart() {
+        // column 0: Run8, Color1. column 1: Color1, Index.
+        let img = RgbaImage::from_fn(2, 2, |x, _| Rgba([50 * x as u8, 0, 0, 255]));
+        let img = StaticImage::new("test", img, "", 0);
+        let palette = chunk_colors(PaletteSet::Default);
+
+        let chart = visualize_column_chart(&img, &palette, 4);
+        assert_eq!(chart.dimensions(), (2, 4));
+        let column = |x| -> Vec<_> { (0..4).map(|y| chart.get_pixel(x, y).0).collect() };
+        let color = |chunk: QoiChunk| {
+            let [r, g, b] = palette[chunk as usize];
+            [r, g, b, 0xFF]
+        };
+        let (index, run8, color1) = (
+            color(QoiChunk::Index),
+            color(QoiChunk::Run8),
+            color(QoiChunk::Color1),
+        );
+        assert_eq!(column(0), [run8, run8, color1, color1]);
+        assert_eq!(column(1), [index, index, color1, color1]);
+    }
+
+    #[test]
+    fn test_visualize_chunk_blocks() {
+        // Run8 x2, Color1, Run8, Diff8
+        let img = static_image(&[
+            [0, 0, 0, 255],
+            [0, 0, 0, 255],
+            [50, 0, 0, 255],
+            [50, 0, 0, 255],
+            [51, 0, 0, 255],
+        ]);
+
+        let mut config = VisConfig::default();
+        config.set_mode(VisMode::ChunkBlocks);
+        let vis = visualize(&img, &config);
+
+        let colors: Vec<_> = vis.pixels().map(|px| [px[0], px[1], px[2]]).collect();
+        assert_eq!(
+            colors,
+            [
+                CHUNK_BLOCK_COLORS[0],
+                CHUNK_BLOCK_COLORS[0],
+                CHUNK_BLOCK_COLORS[1],
+                CHUNK_BLOCK_COLORS[2],
+                CHUNK_BLOCK_COLORS[3],
+            ]
+        );
+    }
+}
@@ -1,41 +1,768 @@
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+
+use image::{Rgba, RgbaImage};
+use seed::futures::channel::mpsc;
+use seed::futures::StreamExt;
 use seed::{prelude::*, *};
-use strum::IntoEnumIterator;
+use strum::{EnumCount, IntoEnumIterator};
+use strum_macros::EnumIter;
 use wasm_bindgen::JsCast;
-use web_sys::{DataTransfer, DragEvent, HtmlCanvasElement, HtmlInputElement};
+use web_sys::{DataTransfer, DragEvent, HtmlCanvasElement, HtmlInputElement, KeyboardEvent};
 
-use crate::qoi::QoiChunk;
-use crate::static_image::StaticImage;
+use crate::benchmark::{run_benchmark, BenchResult};
+use crate::codec::{self, QoiColorspace};
+use crate::export;
+use crate::format_compare::FormatSizes;
+use crate::optimize::{reorder_pixels_for_compression, ScanOrder};
+use crate::palette::PaletteSet;
+use crate::pixel::{batch_hash, PixelDiff, QoiPixel, QOI_INITIAL_PIXEL};
+use crate::preprocess::{FlipDir, Preprocess, RotateAngle};
+use crate::qoi::{
+    best_case_filesize, chunk_at_byte_offset, chunk_byte_offset, chunk_sequence_similarity,
+    expected_filesize_for_image_class, filesize_with_dict_clear, most_expensive_pixel,
+    most_expensive_pixels, opcode_description, qoi_analyze_chunked, qoi_analyze_compare,
+    qoi_analyze_with_pixels, qoi_compare_chunks, simulate_with_initial_dict, wasted_dict_writes,
+    AnalyzerConfig, AnalyzerSnapshot, ChunkDiff, ChunkFamily, CompareResult, DiffAnalyzer,
+    QoiChunk, RunComparison, QOI_END_MARKER, QOI_HEADER_LEN, QOI_MAGIC,
+};
+use crate::report::export_html_report;
+use crate::sensitivity::{analyze_sensitivity, state_at, Sensitivity};
+use crate::session::{Session, SESSION_FILENAME};
+use crate::static_image::{encode_png, StaticImage, DOMINANT_COLOR_MAX};
+use crate::stats::{most_likely_next, ChunkBaseline, ChunkDistribution, ChunkEntropy};
+use crate::trace::{trace_before, TraceEntry};
 use crate::util;
-use crate::vis::{color_of_chunk, visualize, VisConfig};
+use crate::vis::{
+    blend_images, overlay_chunk_borders, overlay_grid, visualize, visualize_column_chart,
+    visualize_diff, visualize_run_length_gradient, visualize_svg, visualize_with_borders,
+    BorderConfig, Channel, ChunkBorderMode, ChunkLabelMode, VisConfig, VisMode, CHUNK_BORDER_COLOR,
+};
 
 #[derive(Debug)]
 struct Model {
     img: StaticImage,
+    multi_frame: Vec<StaticImage>,
+    /// Images loaded in this session, oldest first, with their analyses.
+    analysis_history: Vec<StaticImage>,
+    context_menu: Option<ContextMenu>,
+    /// Thumbnails of `multi_frame` for the frame strip.
+    frame_thumbnails: Vec<StaticImage>,
+    frame: usize,
+    progress: Option<f32>,
+    sensitivity: SensitivityState,
+    byte_jump: Option<ByteJump>,
+    /// `(x, y, pixel, chunk)` of `most_expensive_pixel()` in the current image.
+    most_expensive: Option<(u32, u32, QoiPixel, QoiChunk)>,
+    /// `most_expensive_pixels()` in the current image.
+    expensive_pixels: Vec<(u32, u32, QoiPixel, QoiChunk)>,
+    /// Pixel index located from the expensive pixel list.
+    located_pixel: Option<usize>,
+    dict_clear: Option<DictClear>,
+    alpha_threshold: Option<AlphaThreshold>,
+    /// QOI size of each `BAND_ROWS`-row band analyzed independently.
+    band_sizes: Option<Vec<usize>>,
+    show_borders: bool,
+    show_run_lengths: bool,
+    border_config: BorderConfig,
+    /// Analysis time re-measured by the user, in milliseconds.
+    analysis_time_remeasured: Option<f64>,
+    /// Last benchmark result of the current image.
+    benchmark: Option<BenchResult>,
+    benchmark_running: bool,
+    /// Encoder parameters experiment, with the resulting QOI file size of the current image.
+    analyzer_config: AnalyzerConfig,
+    analyzer_config_filesize: usize,
+    /// Number of pixels whose chunk type differs under `analyzer_config`.
+    analyzer_config_changed: usize,
+    /// Number of entries of the chunk sequence compressed by `rle_compress_chunks()`.
+    chunk_rle_len: usize,
+    /// Comparison with a custom initial pixel.
+    initial_compare: Option<(QoiPixel, CompareResult)>,
+    /// Result of the scan order experiment.
+    reorder: Option<(ScanOrder, StaticImage)>,
+    /// Halves of the current image, analyzed independently.
+    halves: Option<(StaticImage, StaticImage)>,
+    /// Position of the timeline scrubber. Pixels from here are not encoded yet.
+    scrub: Option<usize>,
+    /// Animation advancing the scrubber every frame, if running.
+    playback: Option<Playback>,
+    /// Pixels encoded per frame of the playback.
+    playback_speed: usize,
+    /// Incremented on every start of the playback so that frames scheduled before are ignored.
+    playback_generation: u32,
+    /// Encoding decisions just before the scrubber position.
+    trace_log: VecDeque<TraceEntry>,
+    /// Pixel index of the trace entry clicked last.
+    trace_selected: Option<usize>,
+    background: Background,
+    reference: Option<StaticImage>,
+    /// (similarity, chunk diff) of the reference to the current image, if dimensions match.
+    reference_diff: Option<(f64, ChunkDiff)>,
+    /// QOI file size of the current image encoded starting from the final dict of the reference.
+    reference_warm_filesize: Option<usize>,
+    /// Loaded session, whose statistics are shown instead of the missing pixels.
+    session: Option<Session>,
+    /// QOI encode-decode roundtrip of the current image, if shown.
+    decode_preview: Option<DecodePreview>,
+    /// Comparison with the image before the last preprocessing.
+    before_after: Option<BeforeAfter>,
+    /// Slideshow showing one chunk type at a time, if running.
+    chunk_cycle: Option<ChunkCycle>,
+    /// Incremented on every start, pause and resume of the chunk cycle so that steps scheduled
+    /// before are ignored.
+    chunk_cycle_generation: u32,
     config: VisConfig,
+    config_history: Vec<VisConfig>,
+    /// Visibility changes by the last undo, highlighted in the sidebar for a moment.
+    undo_flash: [Option<bool>; QoiChunk::COUNT],
+    /// Incremented on every undo so that clears scheduled by earlier undos are ignored.
+    undo_flash_generation: u32,
+    grayscale: bool,
+    /// Whether the visualization canvas is fullscreen, also updated when the browser exits it.
+    is_fullscreen: bool,
+    /// Whether the original image panel is expanded.
+    show_original: bool,
+    /// Divider position of the comparison slider as a fraction of the width, or `None` for
+    /// side-by-side.
+    comparison_slider: Option<f64>,
+    comparison_dragging: bool,
+    /// Width of the sidebar in CSS pixels, in `SIDEBAR_WIDTH_MIN..=SIDEBAR_WIDTH_MAX`.
+    sidebar_width: u32,
+    sidebar_resizing: bool,
+    /// Whether the sidebar drawer is open on narrow screens.
+    sidebar_open: bool,
+    shortcuts_enabled: bool,
+    show_help: bool,
+    auto_resize: bool,
+    /// Dimensions of the next blank image, each in `1..=BLANK_IMAGE_SIDE_MAX`.
+    blank_size: (u32, u32),
+    highlight_wasted: bool,
+    dark_mode: bool,
+    viewport: Option<Viewport>,
+    /// Display scale of the visualization canvas.
+    zoom: u32,
+    /// Pixel under the cursor on the visualization canvas.
+    hovered_chunk: Option<(u32, u32, QoiChunk)>,
+    /// (spatial entropy, compression ratio) of loaded images.
+    scatter_points: Vec<(f64, f64)>,
+    aggregate: AggregateStats,
+    /// Notifications shown in the top-right corner, oldest first.
+    toasts: VecDeque<Toast>,
     refs: Refs,
 }
 
+/// User-facing notification, dismissed automatically.
+#[derive(Clone, Debug, PartialEq)]
+struct Toast {
+    message: String,
+    kind: ToastKind,
+    /// `util::now_ms()` after which the toast is dismissed.
+    expires_at: f64,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ToastKind {
+    Success,
+    Warning,
+    Error,
+}
+
+impl ToastKind {
+    fn class_name(self) -> &'static str {
+        match self {
+            Self::Success => "toast-success",
+            Self::Warning => "toast-warning",
+            Self::Error => "toast-error",
+        }
+    }
+}
+
+/// Visible region of the visualization canvas, in fractions of its size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Viewport {
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+}
+
+/// How long toasts are shown.
+const TOAST_DURATION_MS: f64 = 4000.;
+
+/// Interval of checking expired toasts.
+const TOAST_CHECK_INTERVAL_MS: u32 = 500;
+
+/// Files larger than this trigger a warning about slow analysis, unless auto-resize is enabled.
+const LARGE_FILE_BYTES: u64 = 8 << 20;
+
+/// Maximum side length of images when auto-resize is enabled.
+const AUTO_RESIZE_SIDE: u32 = 1024;
+
+/// Height of the per-column chunk type chart below the visualization.
+const COLUMN_CHART_HEIGHT: u32 = 200;
+
+/// Maximum side length of blank images created in the header.
+const BLANK_IMAGE_SIDE_MAX: u32 = 1024;
+
+/// Images larger than this in either dimension get a minimap.
+const MINIMAP_THRESHOLD: u32 = 512;
+
+/// Downscale factor of the minimap.
+const MINIMAP_SCALE: u32 = 8;
+
+/// Selectable display scales of the visualization canvas.
+const ZOOM_LEVELS: [u32; 5] = [1, 2, 4, 8, 16];
+
+/// Chunk labels are drawn over pixels at this zoom or more.
+const LABEL_ZOOM_MIN: u32 = 8;
+
+/// Chunk labels are not drawn if the zoomed canvas is larger than this in either dimension.
+const LABEL_CANVAS_SIDE_MAX: u32 = 4096;
+
+/// Range of the sidebar width in CSS pixels.
+const SIDEBAR_WIDTH_MIN: u32 = 150;
+const SIDEBAR_WIDTH_MAX: u32 = 600;
+const SIDEBAR_WIDTH_DEFAULT: u32 = 240;
+
+/// `localStorage` key of the sidebar width.
+const SIDEBAR_WIDTH_STORAGE_KEY: &str = "qoi-visualizer-sidebar-width";
+
+/// `localStorage` key of the dark mode preference.
+const DARK_MODE_STORAGE_KEY: &str = "qoi-visualizer-dark-mode";
+
+/// Maximum number of undoable `VisConfig` changes.
+const CONFIG_HISTORY_MAX: usize = 100;
+
+/// Maximum number of images kept in the analysis history.
+const ANALYSIS_HISTORY_MAX: usize = 20;
+
+/// Number of encoding decisions kept in the trace log.
+const TRACE_LOG_MAX: usize = 50;
+
+/// Selectable spacings of the coordinate grid, in image pixels.
+const GRID_SPACINGS: [u32; 4] = [8, 16, 32, 64];
+
+/// Height of bands in the band analysis.
+const BAND_ROWS: u32 = 64;
+
+/// Maximum side length of thumbnails in the frame strip.
+const FRAME_THUMBNAIL_SIDE: u32 = 48;
+
+/// Number of pixels listed as the most expensive ones.
+const EXPENSIVE_PIXELS_MAX: usize = 5;
+
+/// Number of trace log entries shown.
+const TRACE_LOG_SHOWN: usize = 20;
+
+/// Number of timed runs of the benchmark.
+const BENCHMARK_ITERATIONS: u32 = 10;
+
+/// Delay before starting the benchmark, in milliseconds.
+const BENCHMARK_DELAY_MS: u32 = 50;
+
+/// Duration of each chunk type in the chunk cycle, in milliseconds.
+const CHUNK_CYCLE_MS: u32 = 2000;
+
+/// Selectable numbers of pixels encoded per frame of the timeline playback.
+const PLAYBACK_SPEEDS: [usize; 4] = [1, 10, 100, 1000];
+
+/// Number of frames over which the playback FPS is measured.
+const PLAYBACK_FPS_WINDOW: u32 = 30;
+
+/// Duration of the sidebar highlight after undo, in milliseconds.
+const UNDO_FLASH_MS: u32 = 600;
+
+/// (key, description) of keyboard shortcuts.
+const SHORTCUTS: &[(&str, &str)] = &[
+    ("Space", "re-visualize"),
+    ("a", "make all chunks visible"),
+    ("n", "make all chunks invisible"),
+    ("1-9, 0", "toggle visibility of the 1st-10th chunk"),
+    ("z", "undo"),
+    ("g", "toggle grayscale"),
+    ("f", "toggle fullscreen visualization"),
+    ("?", "toggle this help"),
+];
+
+/// Values copied by the context menu of sidebar chunk rows.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, EnumIter)]
+enum ContextMenuAction {
+    Color,
+    Count,
+    Percentage,
+}
+
+impl ContextMenuAction {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Color => "Copy color as #RRGGBB",
+            Self::Count => "Copy count as text",
+            Self::Percentage => "Copy percentage as text",
+        }
+    }
+}
+
+/// Open context menu of a sidebar chunk row.
+#[derive(Debug)]
+struct ContextMenu {
+    chunk: QoiChunk,
+    /// Client coordinates of the right click.
+    x: i32,
+    y: i32,
+}
+
+/// Background of the image displays, to make transparency visible.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Background {
+    White,
+    Black,
+    Checkerboard,
+}
+
+impl Background {
+    fn next(self) -> Self {
+        match self {
+            Self::White => Self::Black,
+            Self::Black => Self::Checkerboard,
+            Self::Checkerboard => Self::White,
+        }
+    }
+
+    fn class_name(self) -> &'static str {
+        match self {
+            Self::White => "bg-white",
+            Self::Black => "bg-black",
+            Self::Checkerboard => "bg-checkerboard",
+        }
+    }
+}
+
+/// Result of "jump to byte offset".
+#[derive(Debug)]
+struct ByteJump {
+    offset: usize,
+    /// `(chunk, first pixel)` containing the byte.
+    found: Option<(QoiChunk, usize)>,
+}
+
+/// Result of "simulate dict clear".
+#[derive(Debug)]
+struct DictClear {
+    row: u32,
+    filesize: usize,
+}
+
+/// Result of "alpha threshold" before applying it.
+#[derive(Debug)]
+struct AlphaThreshold {
+    threshold: u8,
+    filesize: usize,
+    color4_count: usize,
+}
+
+/// Statistics accumulated over images loaded by the user in this session.
+#[derive(Debug, Default)]
+struct AggregateStats {
+    total_pixels: usize,
+    total_filesize_qoi: usize,
+    total_filesize_orig: usize,
+    accumulated_histogram: [usize; QoiChunk::COUNT],
+    image_count: usize,
+    /// Normalized histogram of each image, for outlier detection.
+    distributions: Vec<ChunkDistribution>,
+    /// `StaticImage::analysis_timestamp()` of the last added image.
+    last_analysis_timestamp: Option<f64>,
+}
+
+impl AggregateStats {
+    fn add(&mut self, img: &StaticImage) {
+        self.total_pixels += img.pixel_count();
+        self.total_filesize_qoi += img.filesize_qoi();
+        self.total_filesize_orig += img.filesize_orig();
+        for (acc, &count) in self.accumulated_histogram.iter_mut().zip(img.histogram()) {
+            *acc += count;
+        }
+        self.image_count += 1;
+        self.distributions.push(img.histogram_normalized());
+        self.last_analysis_timestamp = Some(img.analysis_timestamp());
+    }
+}
+
+/// State of the sensitivity analysis tool.
+#[derive(Debug, Default)]
+struct SensitivityState {
+    pos: Option<(u32, u32)>,
+    rgba: [u8; 4],
+    result: Sensitivity,
+}
+
 #[derive(Debug, Default)]
 struct Refs {
     input_file: ElRef<HtmlInputElement>,
+    input_session: ElRef<HtmlInputElement>,
     canvas: ElRef<HtmlCanvasElement>,
+    canvas_diff: ElRef<HtmlCanvasElement>,
+    canvas_minimap: ElRef<HtmlCanvasElement>,
+    canvas_labels: ElRef<HtmlCanvasElement>,
+    canvas_reorder: ElRef<HtmlCanvasElement>,
+    canvas_decoded: ElRef<HtmlCanvasElement>,
+    canvas_column_chart: ElRef<HtmlCanvasElement>,
+    canvas_before_after: ElRef<HtmlCanvasElement>,
+}
+
+/// State of the chunk type slideshow.
+#[derive(Debug)]
+struct ChunkCycle {
+    /// Index of the shown chunk type in `QoiChunk::iter()`.
+    idx: usize,
+    paused: bool,
+    /// Config restored when the cycle stops.
+    config_saved: VisConfig,
+}
+
+impl ChunkCycle {
+    fn chunk(&self) -> QoiChunk {
+        QoiChunk::iter()
+            .nth(self.idx)
+            .expect("index should be < QoiChunk::COUNT")
+    }
+}
+
+/// Timing of the timeline playback.
+#[derive(Debug)]
+struct Playback {
+    started_ms: f64,
+    frames: u32,
+    /// Time spent on encoding and drawing in the last frame.
+    frame_ms: f64,
+    /// Timestamp where the current FPS window started.
+    fps_window_start_ms: f64,
+    /// FPS over the last completed window.
+    fps: Option<f64>,
+}
+
+impl Playback {
+    fn new(now_ms: f64) -> Self {
+        Self {
+            started_ms: now_ms,
+            frames: 0,
+            frame_ms: 0.,
+            fps_window_start_ms: now_ms,
+            fps: None,
+        }
+    }
+}
+
+/// Duration of the crossfade between the before/after visualizations.
+const BEFORE_AFTER_FADE_MS: f64 = 400.;
+
+/// Crossfade between the analysis of the image before and after the last preprocessing.
+#[derive(Debug)]
+struct BeforeAfter {
+    before: StaticImage,
+    /// Visualizations of `before` and the current image.
+    vis: (RgbaImage, RgbaImage),
+    /// 0.0 shows `before`, 1.0 shows the current image.
+    alpha: f32,
+    /// (start timestamp, whether fading to after) of the running fade.
+    fade: Option<(f64, bool)>,
+}
+
+impl BeforeAfter {
+    /// Visualization to draw at the current alpha. Images of different sizes are not blended.
+    fn blended(&self) -> RgbaImage {
+        let (before, after) = &self.vis;
+        if before.dimensions() == after.dimensions() {
+            blend_images(before, after, self.alpha)
+        } else if self.alpha >= 0.5 {
+            after.clone()
+        } else {
+            before.clone()
+        }
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        let (before, after) = &self.vis;
+        if self.alpha >= 0.5 {
+            after.dimensions()
+        } else {
+            before.dimensions()
+        }
+    }
+}
+
+/// Alpha of a fade started at `start_ms` toward after (or before) at `now_ms`.
+fn fade_alpha(start_ms: f64, to_after: bool, now_ms: f64) -> f32 {
+    let t = ((now_ms - start_ms) / BEFORE_AFTER_FADE_MS).clamp(0., 1.) as f32;
+    if to_after {
+        t
+    } else {
+        1. - t
+    }
+}
+
+/// Result of encoding the current image to QOI and decoding it back.
+#[derive(Debug)]
+struct DecodePreview {
+    /// Decoded image with mismatched pixels in `MISMATCH_COLOR`, or the decode error.
+    decoded: Result<RgbaImage, String>,
+    /// Pixels differing from the original.
+    mismatches: Vec<(u32, u32)>,
+    /// Number of mismatched pixels for each chunk type encoding them.
+    mismatch_histogram: [usize; QoiChunk::COUNT],
+}
+
+impl DecodePreview {
+    fn new(img: &RgbaImage) -> Self {
+        const MISMATCH_COLOR: Rgba<u8> = Rgba([0xFF, 0x00, 0x00, 0xFF]);
+
+        let mut decoded = match codec::decode(&codec::encode(img, QoiColorspace::Srgb)) {
+            Ok(decoded) if decoded.dimensions() == img.dimensions() => decoded,
+            Ok(decoded) => {
+                return Self {
+                    decoded: Err(format!("decoded size is {:?}", decoded.dimensions())),
+                    mismatches: Vec::new(),
+                    mismatch_histogram: [0; QoiChunk::COUNT],
+                }
+            }
+            Err(e) => {
+                return Self {
+                    decoded: Err(e.to_string()),
+                    mismatches: Vec::new(),
+                    mismatch_histogram: [0; QoiChunk::COUNT],
+                }
+            }
+        };
+
+        let (_, pairs, _) = qoi_analyze_with_pixels(img);
+        let width = img.width() as usize;
+        let mut mismatches = Vec::new();
+        let mut mismatch_histogram = [0; QoiChunk::COUNT];
+        for (idx, (chunk, px)) in pairs.into_iter().enumerate() {
+            let (x, y) = ((idx % width) as u32, (idx / width) as u32);
+            if decoded.get_pixel(x, y).0 != [px.r(), px.g(), px.b(), px.a()] {
+                mismatches.push((x, y));
+                mismatch_histogram[chunk as usize] += 1;
+            }
+        }
+        for &(x, y) in &mismatches {
+            decoded.put_pixel(x, y, MISMATCH_COLOR);
+        }
+
+        Self {
+            decoded: Ok(decoded),
+            mismatches,
+            mismatch_histogram,
+        }
+    }
 }
 
 #[derive(Debug)]
 enum Msg {
     InputFileChanged,
+    LoadDataUrl(String),
+    SetBlankWidth(String),
+    SetBlankHeight(String),
+    NewBlankImage,
     FileDropped(DataTransfer),
-    UpdateImage(StaticImage),
+    Progress(f32),
+    /// Loading a file failed with the error message.
+    LoadFailed(String),
+    UpdateImage(Box<StaticImage>),
+    UpdateFrames(Vec<StaticImage>),
+    SelectFrame(usize),
+    SetVisMode(VisMode),
+    SetPalette(PaletteSet),
     ToggleChunkVisibility(QoiChunk),
+    SetChunkVisibility(QoiChunk, bool),
+    SetChunkColor(QoiChunk, String),
+    SetInvisibleColor([u8; 3]),
     MakeAllChunksVisible,
     MakeAllChunksInvisible,
     Visualize,
+    DownloadSvg,
+    DownloadPng,
+    DownloadChunkSequence,
+    DownloadReport,
+    SelectPixel(i32, i32),
+    SetSensitivityChannel(usize, u8),
+    ClearSensitivity,
+    JumpToByte(String),
+    SimulateDictClear(String),
+    AnalyzeBands,
+    Scrub(String),
+    ToggleBorders,
+    CompareInitialPixel(String),
+    MeasureAnalysisTime,
+    StartBenchmark,
+    RunBenchmark,
+    SetRun8Max(u16),
+    SetDictSize(usize),
+    ToggleBorderedFamily(ChunkFamily),
+    SetBorderColor(Option<String>),
+    SetGridSpacing(Option<u32>),
+    SetChunkLabelMode(ChunkLabelMode),
+    SetChunkBorderMode(ChunkBorderMode),
+    RestoreHistory(usize),
+    OpenContextMenu(QoiChunk, i32, i32),
+    ContextMenu(QoiChunk, ContextMenuAction),
+    CloseContextMenu,
+    ClearHistory,
+    SaveSession,
+    LoadSessionFile,
+    RestoreSession(Box<Session>),
+    SetBorderMinGroupSize(String),
+    ClearScrub,
+    TogglePlayback,
+    SetPlaybackSpeed(usize),
+    /// Advances the playback with the frame timestamp, unless `Model::playback_generation` has
+    /// changed since scheduled.
+    PlaybackFrame(u32, f64),
+    SelectTraceEntry(usize),
+    LocatePixel(usize),
+    ToggleBackground,
+    ToggleOriginal,
+    ToggleComparisonSlider,
+    StartComparisonDrag,
+    StartResizeSidebar,
+    /// Mouse X in client coordinates.
+    ResizeSidebar(i32),
+    StopResizeSidebar,
+    ToggleSidebarDrawer,
+    /// Mouse moved to client X coordinate on the visualization.
+    DragComparison(i32),
+    EndComparisonDrag,
+    SetReference,
+    CaptureVisualization,
+    ClearReference,
+    KeyDown(String),
+    SetShortcutsEnabled(bool),
+    Undo,
+    /// Clears the undo flash, unless `Model::undo_flash_generation` has changed since scheduled.
+    ClearUndoFlash(u32),
+    ToggleGrayscale,
+    ToggleFullscreen,
+    FullscreenChanged(bool),
+    DismissExpiredToasts,
+    ToggleHelp,
+    Print,
+    ToggleAutoResize,
+    ToggleWastedHighlight,
+    ToggleDecodePreview,
+    ToggleChunkCycle,
+    ToggleChunkCyclePause,
+    /// Advances the chunk cycle, unless `Model::chunk_cycle_generation` has changed since
+    /// scheduled.
+    ChunkCycleStep(u32),
+    ToggleDarkMode,
+    UpdateViewport,
+    PanMinimap(f64, f64),
+    ResetAggregate,
+    SetZoom(u32),
+    TryReorder,
+    DownloadCsv {
+        full: bool,
+    },
+    ClearReorder,
+    CompareHalves {
+        vertical: bool,
+    },
+    ClearHalves,
+    CanvasHover(i32, i32),
+    CanvasLeave,
+    Preprocess(Preprocess),
+    StartBeforeAfter,
+    BeforeAfterAlpha(f32),
+    PreviewAlphaThreshold(String),
+    ToggleRunLengths,
 }
 
-fn init(_: Url, orders: &mut impl Orders<Msg>) -> Model {
+fn init(url: Url, orders: &mut impl Orders<Msg>) -> Model {
+    let dark_mode = load_dark_mode();
+    let mut config = url
+        .hash()
+        .and_then(|fragment| VisConfig::from_hash_fragment(fragment))
+        .unwrap_or_default();
+    config.set_dark_palette(dark_mode);
+    apply_dark_mode(dark_mode);
+
+    let img = StaticImage::default();
+    let scatter_points = vec![scatter_point(&img)];
+    let filesize_qoi = img.filesize_qoi();
+    let chunk_rle_len = export::rle_compress_chunks(img.chunks()).len();
+    let most_expensive = most_expensive_pixel(img.image(), img.chunks());
+    let expensive_pixels = most_expensive_pixels(img.image(), img.chunks(), EXPENSIVE_PIXELS_MAX);
+    let trace_log = trace_before(&img, img.pixel_count(), TRACE_LOG_MAX);
+
     let model = Model {
-        img: StaticImage::default(),
-        config: VisConfig::default(),
+        img,
+        multi_frame: Vec::new(),
+        analysis_history: Vec::new(),
+        context_menu: None,
+        frame_thumbnails: Vec::new(),
+        frame: 0,
+        progress: None,
+        sensitivity: SensitivityState::default(),
+        byte_jump: None,
+        most_expensive,
+        expensive_pixels,
+        located_pixel: None,
+        dict_clear: None,
+        alpha_threshold: None,
+        band_sizes: None,
+        scrub: None,
+        playback: None,
+        playback_speed: PLAYBACK_SPEEDS[0],
+        playback_generation: 0,
+        trace_log,
+        trace_selected: None,
+        reorder: None,
+        halves: None,
+        show_borders: false,
+        show_run_lengths: false,
+        initial_compare: None,
+        analysis_time_remeasured: None,
+        benchmark: None,
+        benchmark_running: false,
+        analyzer_config: AnalyzerConfig::default(),
+        analyzer_config_filesize: filesize_qoi,
+        analyzer_config_changed: 0,
+        chunk_rle_len,
+        border_config: BorderConfig::default(),
+        background: Background::Checkerboard,
+        reference: None,
+        reference_diff: None,
+        reference_warm_filesize: None,
+        session: None,
+        decode_preview: None,
+        before_after: None,
+        chunk_cycle: None,
+        chunk_cycle_generation: 0,
+        config,
+        config_history: Vec::new(),
+        undo_flash: [None; QoiChunk::COUNT],
+        undo_flash_generation: 0,
+        grayscale: false,
+        is_fullscreen: false,
+        show_original: true,
+        comparison_slider: None,
+        comparison_dragging: false,
+        sidebar_width: load_sidebar_width(),
+        sidebar_resizing: false,
+        sidebar_open: false,
+        shortcuts_enabled: true,
+        show_help: false,
+        auto_resize: false,
+        blank_size: (64, 64),
+        highlight_wasted: false,
+        dark_mode,
+        viewport: None,
+        zoom: 1,
+        hovered_chunk: None,
+        scatter_points,
+        aggregate: AggregateStats::default(),
+        toasts: VecDeque::new(),
         refs: Refs::default(),
     };
 
@@ -51,11 +778,123 @@ fn init(_: Url, orders: &mut impl Orders<Msg>) -> Model {
             ev.stop_propagation();
             ev.data_transfer().map(Msg::FileDropped)
         }))
+        .stream(streams::window_event(Ev::KeyDown, |ev| {
+            let ev = ev.dyn_into::<KeyboardEvent>().unwrap();
+            // leave browser shortcuts (Ctrl+R etc.) to the browser. Shift is needed for '?'.
+            if ev.ctrl_key() || ev.meta_key() || ev.alt_key() {
+                return None;
+            }
+            if ev.key() == " " && !is_text_input(ev.target()) {
+                // prevent scrolling.
+                ev.prevent_default();
+            }
+            Some(Msg::KeyDown(ev.key()))
+        }))
+        .stream(streams::window_event(Ev::from("focusin"), |ev| {
+            Msg::SetShortcutsEnabled(!is_text_input(ev.target()))
+        }))
+        .stream(streams::window_event(Ev::from("focusout"), |_| {
+            Msg::SetShortcutsEnabled(true)
+        }))
+        .stream(streams::window_event(Ev::Click, |_| Msg::CloseContextMenu))
+        .stream(streams::window_event(Ev::MouseUp, |_| {
+            Msg::EndComparisonDrag
+        }))
+        .stream(streams::window_event(Ev::MouseUp, |_| {
+            Msg::StopResizeSidebar
+        }))
+        .stream(streams::window_event(Ev::MouseMove, |ev| {
+            let ev = ev.dyn_into::<web_sys::MouseEvent>().unwrap();
+            Msg::ResizeSidebar(ev.client_x())
+        }))
+        .stream(streams::window_event(Ev::from("fullscreenchange"), |_| {
+            Msg::FullscreenChanged(document().fullscreen_element().is_some())
+        }))
+        .stream(streams::window_event(Ev::Scroll, |_| Msg::UpdateViewport))
+        .stream(streams::window_event(Ev::Resize, |_| Msg::UpdateViewport))
+        .stream(streams::interval(TOAST_CHECK_INTERVAL_MS, || {
+            Msg::DismissExpiredToasts
+        }))
         .after_next_render(|_| Msg::Visualize);
 
     model
 }
 
+/// Returns the saved dark mode preference, or the system preference if not saved.
+fn load_dark_mode() -> bool {
+    let storage = window().local_storage().ok().flatten();
+    if let Some(value) =
+        storage.and_then(|storage| storage.get_item(DARK_MODE_STORAGE_KEY).ok().flatten())
+    {
+        return value == "true";
+    }
+
+    window()
+        .match_media("(prefers-color-scheme: dark)")
+        .ok()
+        .flatten()
+        .is_some_and(|mql| mql.matches())
+}
+
+/// Returns the saved sidebar width, or the default if not saved.
+fn load_sidebar_width() -> u32 {
+    window()
+        .local_storage()
+        .ok()
+        .flatten()
+        .and_then(|storage| storage.get_item(SIDEBAR_WIDTH_STORAGE_KEY).ok().flatten())
+        .and_then(|value| value.parse::<u32>().ok())
+        .map_or(SIDEBAR_WIDTH_DEFAULT, |width| {
+            width.clamp(SIDEBAR_WIDTH_MIN, SIDEBAR_WIDTH_MAX)
+        })
+}
+
+fn save_sidebar_width(width: u32) {
+    let storage = match window().local_storage() {
+        Ok(Some(storage)) => storage,
+        _ => return,
+    };
+    if let Err(e) = storage.set_item(SIDEBAR_WIDTH_STORAGE_KEY, &width.to_string()) {
+        log!(format!("cannot save sidebar width: {:?}", e));
+    }
+}
+
+fn save_dark_mode(dark_mode: bool) {
+    let storage = match window().local_storage() {
+        Ok(Some(storage)) => storage,
+        _ => return,
+    };
+    if let Err(e) = storage.set_item(DARK_MODE_STORAGE_KEY, &dark_mode.to_string()) {
+        log!(format!("cannot save dark mode preference: {:?}", e));
+    }
+}
+
+/// Sets the color scheme class of the root element.
+fn apply_dark_mode(dark_mode: bool) {
+    if let Some(root) = document().document_element() {
+        root.set_class_name(if dark_mode { "dark" } else { "" });
+    }
+}
+
+/// Returns whether `target` is an element accepting text input.
+fn is_text_input(target: Option<web_sys::EventTarget>) -> bool {
+    let target = match target {
+        Some(target) => target,
+        None => return false,
+    };
+
+    if target.has_type::<web_sys::HtmlTextAreaElement>() {
+        return true;
+    }
+    match target.dyn_ref::<HtmlInputElement>() {
+        Some(input) => !matches!(
+            input.type_().as_str(),
+            "button" | "checkbox" | "color" | "file" | "radio" | "range"
+        ),
+        None => false,
+    }
+}
+
 fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
     match msg {
         Msg::InputFileChanged => {
@@ -66,10 +905,46 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                 return;
             }
 
-            orders.perform_cmd(async move {
-                let file = &files[0];
-                load_file(file).await.map(Msg::UpdateImage)
-            });
+            start_loading(files, model, orders);
+        }
+
+        Msg::LoadDataUrl(data_url) => match StaticImage::from_data_url(&data_url) {
+            Ok(img) => {
+                orders.send_msg(Msg::UpdateImage(Box::new(img)));
+            }
+            Err(e) => {
+                log!(format!("cannot load Data URL: {}", e));
+                push_toast(
+                    model,
+                    ToastKind::Error,
+                    format!("Could not load image: {}", e),
+                );
+            }
+        },
+
+        Msg::SetBlankWidth(s) => match parse_blank_side(&s) {
+            Some(width) => model.blank_size.0 = width,
+            None => log!(format!("invalid width '{}'", s)),
+        },
+
+        Msg::SetBlankHeight(s) => match parse_blank_side(&s) {
+            Some(height) => model.blank_size.1 = height,
+            None => log!(format!("invalid height '{}'", s)),
+        },
+
+        Msg::NewBlankImage => {
+            let (width, height) = model.blank_size;
+            // opaque black, same as the initial previous pixel of QOI.
+            let buf = [0, 0, 0, 255].repeat((width * height) as usize);
+            let name = format!("blank_{}x{}", width, height);
+            match StaticImage::from_rgba_bytes(name, width, height, buf) {
+                Ok(img) => {
+                    orders.send_msg(Msg::UpdateImage(Box::new(img)));
+                }
+                Err(e) => {
+                    log!(format!("cannot create blank image: {}", e));
+                }
+            }
         }
 
         Msg::FileDropped(dt) => {
@@ -82,31 +957,145 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                 return;
             }
 
-            orders.perform_cmd(async move {
-                let file = &files[0];
-                load_file(file).await.map(Msg::UpdateImage)
-            });
+            start_loading(files, model, orders);
+        }
+
+        Msg::Progress(progress) => {
+            // ignore reports arriving after the loading finished.
+            if model.progress.is_some() {
+                model.progress = Some(progress);
+            }
+        }
+
+        Msg::LoadFailed(e) => {
+            model.progress = None;
+            push_toast(
+                model,
+                ToastKind::Error,
+                format!("Could not load image: {}", e),
+            );
         }
 
         Msg::UpdateImage(img) => {
+            model.progress = None;
+            model.img = *img;
+            let message = format!("Image loaded successfully: {}", model.img.name());
+            push_toast(model, ToastKind::Success, message);
+            push_analysis_history(model);
+            model.scatter_points.push(scatter_point(&model.img));
+            model.aggregate.add(&model.img);
+            model.multi_frame.clear();
+            model.frame_thumbnails.clear();
+            model.frame = 0;
+            if model.img.has_variable_alpha() {
+                model.config.set_mode(VisMode::ChannelHeatMap {
+                    channel: Channel::A,
+                });
+            }
+            on_image_changed(model);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::UpdateFrames(frames) => {
+            let img = match frames.first() {
+                Some(img) => img.clone(),
+                None => return,
+            };
+            model.progress = None;
+            model.img = img;
+            let message = format!(
+                "Image loaded successfully: {} ({} frames)",
+                model.img.name(),
+                frames.len()
+            );
+            push_toast(model, ToastKind::Success, message);
+            push_analysis_history(model);
+            model
+                .scatter_points
+                .extend(frames.iter().map(scatter_point));
+            for frame in &frames {
+                model.aggregate.add(frame);
+            }
+            model.multi_frame = frames;
+            update_frame_thumbnails(model);
+            model.frame = 0;
+            on_image_changed(model);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SelectFrame(frame) => {
+            let img = match model.multi_frame.get(frame) {
+                Some(img) => img.clone(),
+                None => return,
+            };
             model.img = img;
+            model.frame = frame;
+            on_image_changed(model);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SetVisMode(mode) => {
+            push_config_history(model);
+            model.config.set_mode(mode);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SetPalette(palette) => {
+            push_config_history(model);
+            model.config.set_palette(palette);
+            model.config.set_dark_palette(model.dark_mode);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SetChunkVisibility(chunk, visible) => {
+            if model.config.is_visible(chunk) == visible {
+                return;
+            }
+            push_config_history(model);
+            model.config.set_visibility(chunk, visible);
 
             orders.after_next_render(|_| Msg::Visualize);
         }
 
         Msg::ToggleChunkVisibility(chunk) => {
+            push_config_history(model);
             model.config.toggle_visibility(chunk);
 
             orders.after_next_render(|_| Msg::Visualize);
         }
 
+        Msg::SetChunkColor(chunk, hex) => {
+            let px = match QoiPixel::from_css_hex(&hex) {
+                Some(px) => px,
+                None => return,
+            };
+            push_config_history(model);
+            model.config.set_color(chunk, [px.r(), px.g(), px.b()]);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SetInvisibleColor(rgb) => {
+            push_config_history(model);
+            model.config.set_invisible_color(rgb);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
         Msg::MakeAllChunksVisible => {
+            push_config_history(model);
             model.config.make_all_visible();
 
             orders.after_next_render(|_| Msg::Visualize);
         }
 
         Msg::MakeAllChunksInvisible => {
+            push_config_history(model);
             model.config.make_all_invisible();
 
             orders.after_next_render(|_| Msg::Visualize);
@@ -114,150 +1103,4199 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
 
         Msg::Visualize => {
             draw_vis(model);
+            model.viewport = compute_viewport(model);
+
+            let fragment = model.config.to_hash_fragment();
+            let url = Url::current();
+            if url.hash() != Some(&fragment) {
+                url.set_hash(fragment).go_and_replace();
+            }
         }
-    }
-}
 
-async fn load_file(file: &gloo_file::File) -> Option<StaticImage> {
-    match StaticImage::from_blob(file.name(), file).await {
-        Ok(img) => {
-            log!(format!("loaded image '{}'", file.name()));
-            Some(img)
+        Msg::UpdateViewport => {
+            let viewport = compute_viewport(model);
+            if viewport == model.viewport {
+                orders.skip();
+            }
+            model.viewport = viewport;
         }
-        Err(e) => {
-            log!(format!("cannot load image '{}': {}", file.name(), e));
-            None
+
+        Msg::PanMinimap(fx, fy) => {
+            let x = ((fx * f64::from(model.img.width())) as u32).min(model.img.width() - 1);
+            let y = ((fy * f64::from(model.img.height())) as u32).min(model.img.height() - 1);
+            scroll_to_pixel(
+                model,
+                (y as usize) * (model.img.width() as usize) + (x as usize),
+            );
         }
-    }
-}
 
-fn draw_vis(model: &Model) {
-    let img_vis = visualize(&model.img, &model.config);
-    let image_data = util::create_image_data(&img_vis).unwrap();
+        Msg::DownloadSvg => {
+            let svg = visualize_svg(&model.img, &model.config);
+            let blob = gloo_file::Blob::new_with_options(svg.as_str(), Some("image/svg+xml"));
+            let filename = format!("{}_vis.svg", file_stem(model.img.name()));
+            if let Err(e) = util::download_blob(&filename, &blob) {
+                log!(format!("cannot download '{}': {}", filename, e));
+            }
+        }
 
-    let canvas = model.refs.canvas.get().unwrap();
-    let ctx = canvas_context_2d(&canvas);
+        Msg::DownloadPng => {
+            let png = model.img.to_png_bytes();
+            let blob = gloo_file::Blob::new_with_options(png.as_slice(), Some("image/png"));
+            let filename = format!("{}_reexport.png", file_stem(model.img.name()));
+            if let Err(e) = util::download_blob(&filename, &blob) {
+                log!(format!("cannot download '{}': {}", filename, e));
+            }
+        }
 
-    ctx.put_image_data(&image_data, 0., 0.).unwrap();
-}
+        Msg::DownloadChunkSequence => {
+            let mut buf = Vec::<u8>::new();
+            export::write_compact_sequence(model.img.chunks(), &mut buf)
+                .expect("writing into memory should not fail");
+            let blob =
+                gloo_file::Blob::new_with_options(buf.as_slice(), Some("application/octet-stream"));
+            let filename = format!("{}_chunks.bin", file_stem(model.img.name()));
+            if let Err(e) = util::download_blob(&filename, &blob) {
+                log!(format!("cannot download '{}': {}", filename, e));
+            }
+        }
 
-fn view(model: &Model) -> Node<Msg> {
-    div![view_header(model), view_sidebar(model), view_vis(model)]
-}
+        Msg::DownloadCsv { full } => {
+            let mut buf = Vec::<u8>::new();
+            let row_max = (!full).then_some(export::CSV_ROW_MAX);
+            let truncated = export::write_csv(&model.img, row_max, &mut buf)
+                .expect("writing into memory should not fail");
+            if truncated {
+                push_toast(
+                    model,
+                    ToastKind::Warning,
+                    format!(
+                        "CSV export is capped at {} pixels. Use \"Export full\" for all pixels.",
+                        export::CSV_ROW_MAX
+                    ),
+                );
+            }
+            let blob = gloo_file::Blob::new_with_options(buf.as_slice(), Some("text/csv"));
+            let filename = format!("{}_pixels.csv", file_stem(model.img.name()));
+            if let Err(e) = util::download_blob(&filename, &blob) {
+                log!(format!("cannot download '{}': {}", filename, e));
+            }
+        }
 
-fn view_header(model: &Model) -> Vec<Node<Msg>> {
-    nodes![
-        div![
-            label![
-                attrs! {
-                    At::For => "input-file",
-                },
-                "Open (or drop) image file: ",
-            ],
-            input![
-                el_ref(&model.refs.input_file),
-                attrs! {
-                    At::Id => "input-file",
+        Msg::DownloadReport => {
+            let html = export_html_report(&model.img, &model.config);
+            let blob = gloo_file::Blob::new_with_options(html.as_str(), Some("text/html"));
+            let filename = format!("{}_qoi_report.html", file_stem(model.img.name()));
+            if let Err(e) = util::download_blob(&filename, &blob) {
+                log!(format!("cannot download '{}': {}", filename, e));
+            }
+        }
+
+        Msg::SelectPixel(x, y) => {
+            let (x, y) = match (u32::try_from(x), u32::try_from(y)) {
+                (Ok(x), Ok(y)) if x < model.img.width() && y < model.img.height() => (x, y),
+                _ => return,
+            };
+            model.sensitivity.pos = Some((x, y));
+            model.sensitivity.rgba = model.img.image().get_pixel(x, y).0;
+            update_sensitivity(model);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SetSensitivityChannel(ch, value) => {
+            model.sensitivity.rgba[ch] = value;
+            update_sensitivity(model);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ToggleBackground => {
+            model.background = model.background.next();
+        }
+
+        Msg::ToggleOriginal => {
+            model.show_original = !model.show_original;
+        }
+
+        Msg::ToggleComparisonSlider => {
+            model.comparison_slider = match model.comparison_slider {
+                Some(_) => None,
+                None => Some(0.5),
+            };
+            model.comparison_dragging = false;
+        }
+
+        Msg::StartComparisonDrag => {
+            model.comparison_dragging = true;
+        }
+
+        Msg::StartResizeSidebar => {
+            model.sidebar_resizing = true;
+        }
+
+        Msg::ResizeSidebar(client_x) => {
+            if !model.sidebar_resizing {
+                orders.skip();
+                return;
+            }
+            // the sidebar starts at the left edge of the window.
+            let width = u32::try_from(client_x).unwrap_or(0);
+            model.sidebar_width = width.clamp(SIDEBAR_WIDTH_MIN, SIDEBAR_WIDTH_MAX);
+        }
+
+        Msg::StopResizeSidebar => {
+            if !model.sidebar_resizing {
+                orders.skip();
+                return;
+            }
+            model.sidebar_resizing = false;
+            save_sidebar_width(model.sidebar_width);
+        }
+
+        Msg::ToggleSidebarDrawer => {
+            model.sidebar_open = !model.sidebar_open;
+        }
+
+        Msg::DragComparison(client_x) => {
+            if !model.comparison_dragging {
+                orders.skip();
+                return;
+            }
+            let canvas = match model.refs.canvas.get() {
+                Some(canvas) => canvas,
+                None => return,
+            };
+            let rect = canvas.get_bounding_client_rect();
+            if rect.width() <= 0. {
+                return;
+            }
+            let pos = (f64::from(client_x) - rect.left()) / rect.width();
+            model.comparison_slider = Some(pos.clamp(0., 1.));
+        }
+
+        Msg::EndComparisonDrag => {
+            if !model.comparison_dragging {
+                orders.skip();
+                return;
+            }
+            model.comparison_dragging = false;
+        }
+
+        Msg::SetReference => {
+            model.reference = Some(model.img.clone());
+            update_reference_diff(model);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::CaptureVisualization => {
+            let canvas = match model.refs.canvas.get() {
+                Some(canvas) => canvas,
+                None => return,
+            };
+            let name = format!("{} (visualization)", file_stem(model.img.name()));
+            let scale = canvas_scale(model);
+            let img = util::image_from_canvas(&canvas)
+                .map(|img| {
+                    // undo the enlargement by `canvas_scale()`.
+                    RgbaImage::from_fn(img.width() / scale, img.height() / scale, |x, y| {
+                        *img.get_pixel(x * scale, y * scale)
+                    })
+                })
+                .and_then(|img| encode_png(&img))
+                .and_then(|png| StaticImage::from_bytes(name, &png));
+            match img {
+                Ok(img) => {
+                    orders.send_msg(Msg::UpdateImage(Box::new(img)));
+                }
+                Err(e) => log!(format!("cannot capture the visualization: {}", e)),
+            }
+        }
+
+        Msg::ClearReference => {
+            model.reference = None;
+            model.reference_diff = None;
+            model.reference_warm_filesize = None;
+        }
+
+        Msg::KeyDown(key) => {
+            // keys without actions don't change anything.
+            orders.skip();
+            if !model.shortcuts_enabled {
+                return;
+            }
+            let msg = match key.as_str() {
+                " " => Msg::Visualize,
+                "a" => Msg::MakeAllChunksVisible,
+                "n" => Msg::MakeAllChunksInvisible,
+                "z" => Msg::Undo,
+                "g" => Msg::ToggleGrayscale,
+                "f" | "F" => Msg::ToggleFullscreen,
+                "?" => Msg::ToggleHelp,
+                "Escape" if model.show_help => Msg::ToggleHelp,
+                _ => {
+                    // '1'-'9' and '0' toggle chunk 0-9.
+                    let digit = match key.parse::<usize>() {
+                        Ok(digit) if key.len() == 1 => digit,
+                        _ => return,
+                    };
+                    let idx = (digit + 9) % 10;
+                    match QoiChunk::iter().nth(idx) {
+                        Some(chunk) => Msg::ToggleChunkVisibility(chunk),
+                        None => return,
+                    }
+                }
+            };
+            orders.send_msg(msg);
+        }
+
+        Msg::SetShortcutsEnabled(enabled) => {
+            model.shortcuts_enabled = enabled;
+        }
+
+        Msg::Undo => {
+            if let Some(config) = model.config_history.pop() {
+                model.undo_flash = model.config.diff(config);
+                model.config = config;
+                model.undo_flash_generation += 1;
+                let generation = model.undo_flash_generation;
+                orders.perform_cmd(cmds::timeout(UNDO_FLASH_MS, move || {
+                    Msg::ClearUndoFlash(generation)
+                }));
+
+                orders.after_next_render(|_| Msg::Visualize);
+            }
+        }
+
+        Msg::ClearUndoFlash(generation) => {
+            if generation == model.undo_flash_generation {
+                model.undo_flash = [None; QoiChunk::COUNT];
+            }
+        }
+
+        Msg::TryReorder => {
+            let (order, img) = reorder_pixels_for_compression(model.img.image());
+            let name = format!("{} ({})", model.img.name(), order.name());
+            // not displayed as an image, so no URL.
+            let img = StaticImage::new(name, img, "", model.img.filesize_orig());
+            model.reorder = Some((order, img));
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ClearReorder => {
+            model.reorder = None;
+        }
+
+        Msg::CompareHalves { vertical } => {
+            model.halves = if vertical {
+                model.img.split_vertically()
+            } else {
+                model.img.split_horizontally()
+            };
+        }
+
+        Msg::ClearHalves => {
+            model.halves = None;
+        }
+
+        Msg::SetZoom(zoom) => {
+            model.zoom = zoom;
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::CanvasHover(x, y) => {
+            let hovered = match (u32::try_from(x), u32::try_from(y)) {
+                (Ok(x), Ok(y)) if x < model.img.width() && y < model.img.height() => {
+                    let idx = (y as usize) * (model.img.width() as usize) + (x as usize);
+                    Some((x, y, model.img.chunks()[idx]))
+                }
+                _ => None,
+            };
+            if hovered == model.hovered_chunk {
+                orders.skip();
+            }
+            model.hovered_chunk = hovered;
+        }
+
+        Msg::CanvasLeave => {
+            model.hovered_chunk = None;
+        }
+
+        Msg::ResetAggregate => {
+            model.aggregate = AggregateStats::default();
+        }
+
+        Msg::Preprocess(p) => {
+            let suffix = p.name_suffix();
+            let before = model.img.clone();
+            model.img = model.img.apply_preprocess(p).with_suffix(&suffix);
+            model.multi_frame = std::mem::take(&mut model.multi_frame)
+                .into_iter()
+                .map(|frame| frame.with_preprocess(p).with_suffix(&suffix))
+                .collect();
+            update_frame_thumbnails(model);
+            on_image_changed(model);
+            model.before_after = Some(BeforeAfter {
+                vis: (
+                    visualize(&before, &model.config),
+                    visualize(&model.img, &model.config),
+                ),
+                before,
+                alpha: 1.,
+                fade: None,
+            });
+
+            orders.after_next_render(|_| Msg::Visualize);
+            orders.after_next_render(|_| Msg::BeforeAfterAlpha(1.));
+        }
+
+        Msg::StartBeforeAfter => {
+            let ba = match model.before_after.as_mut() {
+                Some(ba) => ba,
+                None => return,
+            };
+            // the config may have changed since the last fade.
+            ba.vis = (
+                visualize(&ba.before, &model.config),
+                visualize(&model.img, &model.config),
+            );
+            let to_after = ba.alpha < 0.5;
+            if ba.vis.0.dimensions() == ba.vis.1.dimensions() {
+                let start = util::now_ms();
+                ba.fade = Some((start, to_after));
+                orders.after_next_render(move |info| {
+                    Msg::BeforeAfterAlpha(fade_alpha(start, to_after, info.timestamp))
+                });
+            } else {
+                // resize the canvas before drawing.
+                let alpha = if to_after { 1. } else { 0. };
+                ba.alpha = alpha;
+                ba.fade = None;
+                orders.after_next_render(move |_| Msg::BeforeAfterAlpha(alpha));
+            }
+        }
+
+        Msg::BeforeAfterAlpha(alpha) => {
+            let ba = match model.before_after.as_mut() {
+                Some(ba) => ba,
+                None => return,
+            };
+            ba.alpha = alpha;
+            put_image(&model.refs.canvas_before_after, &ba.blended());
+            match ba.fade {
+                Some((start, to_after)) if alpha != if to_after { 1. } else { 0. } => {
+                    orders.after_next_render(move |info| {
+                        Msg::BeforeAfterAlpha(fade_alpha(start, to_after, info.timestamp))
+                    });
+                }
+                _ => ba.fade = None,
+            }
+        }
+
+        Msg::PreviewAlphaThreshold(s) => {
+            model.alpha_threshold = s.parse().ok().map(|threshold| {
+                let img = model.img.apply_alpha_threshold(threshold);
+                AlphaThreshold {
+                    threshold,
+                    filesize: img.filesize_qoi(),
+                    color4_count: img.histogram()[QoiChunk::Color4 as usize],
+                }
+            });
+        }
+
+        Msg::ToggleGrayscale => {
+            model.grayscale = !model.grayscale;
+        }
+
+        Msg::ToggleFullscreen => {
+            if model.is_fullscreen {
+                document().exit_fullscreen();
+                return;
+            }
+            let canvas = match model.refs.canvas.get() {
+                Some(canvas) => canvas,
+                None => return,
+            };
+            // `model.is_fullscreen` is updated by the `fullscreenchange` event.
+            if let Err(e) = canvas.request_fullscreen() {
+                log!(format!("cannot enter fullscreen: {:?}", e));
+            }
+        }
+
+        Msg::FullscreenChanged(is_fullscreen) => {
+            model.is_fullscreen = is_fullscreen;
+        }
+
+        Msg::DismissExpiredToasts => {
+            let now = util::now_ms();
+            let len = model.toasts.len();
+            model.toasts.retain(|toast| toast.expires_at > now);
+            if model.toasts.len() == len {
+                orders.skip();
+            }
+        }
+
+        Msg::ToggleHelp => {
+            model.show_help = !model.show_help;
+        }
+
+        Msg::Print => {
+            if let Err(e) = window().print() {
+                log!(format!("cannot print: {:?}", e));
+            }
+        }
+
+        Msg::ToggleDarkMode => {
+            model.dark_mode = !model.dark_mode;
+            model.config.set_dark_palette(model.dark_mode);
+            apply_dark_mode(model.dark_mode);
+            save_dark_mode(model.dark_mode);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ToggleRunLengths => {
+            model.show_run_lengths = !model.show_run_lengths;
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ToggleWastedHighlight => {
+            model.highlight_wasted = !model.highlight_wasted;
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ToggleChunkCycle => {
+            match model.chunk_cycle.take() {
+                Some(cycle) => model.config = cycle.config_saved,
+                None => {
+                    let cycle = ChunkCycle {
+                        idx: 0,
+                        paused: false,
+                        config_saved: model.config,
+                    };
+                    model.config.make_only_visible(cycle.chunk());
+                    model.chunk_cycle = Some(cycle);
+                    model.chunk_cycle_generation += 1;
+                    let generation = model.chunk_cycle_generation;
+                    orders.perform_cmd(cmds::timeout(CHUNK_CYCLE_MS, move || {
+                        Msg::ChunkCycleStep(generation)
+                    }));
+                }
+            }
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ToggleChunkCyclePause => {
+            if let Some(cycle) = &mut model.chunk_cycle {
+                // invalidates the pending step.
+                model.chunk_cycle_generation += 1;
+                cycle.paused = !cycle.paused;
+                if !cycle.paused {
+                    let generation = model.chunk_cycle_generation;
+                    orders.perform_cmd(cmds::timeout(CHUNK_CYCLE_MS, move || {
+                        Msg::ChunkCycleStep(generation)
+                    }));
+                }
+            }
+        }
+
+        Msg::ChunkCycleStep(generation) => {
+            let cycle = match &mut model.chunk_cycle {
+                Some(cycle) if model.chunk_cycle_generation == generation && !cycle.paused => cycle,
+                _ => return,
+            };
+            cycle.idx = (cycle.idx + 1) % QoiChunk::COUNT;
+            model.config.make_only_visible(cycle.chunk());
+            orders.perform_cmd(cmds::timeout(CHUNK_CYCLE_MS, move || {
+                Msg::ChunkCycleStep(generation)
+            }));
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ToggleDecodePreview => {
+            model.decode_preview = match model.decode_preview {
+                Some(_) => None,
+                None => Some(DecodePreview::new(model.img.image())),
+            };
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ToggleAutoResize => {
+            model.auto_resize = !model.auto_resize;
+            if !model.auto_resize {
+                return;
+            }
+
+            // downscaled images cannot be restored. they apply to the next load.
+            if model.img.width() > AUTO_RESIZE_SIDE || model.img.height() > AUTO_RESIZE_SIDE {
+                model.img = model.img.resize_for_analysis(AUTO_RESIZE_SIDE);
+                for frame in &mut model.multi_frame {
+                    *frame = frame.resize_for_analysis(AUTO_RESIZE_SIDE);
+                }
+                update_frame_thumbnails(model);
+                on_image_changed(model);
+
+                orders.after_next_render(|_| Msg::Visualize);
+            }
+        }
+
+        Msg::JumpToByte(s) => {
+            let offset = match s.parse() {
+                Ok(offset) => offset,
+                Err(_) => {
+                    model.byte_jump = None;
+                    update_expensive_pixels(model);
+                    orders.after_next_render(|_| Msg::Visualize);
+                    return;
+                }
+            };
+            let found = chunk_at_byte_offset(model.img.chunks(), offset);
+            model.byte_jump = Some(ByteJump { offset, found });
+
+            if let Some((_, idx)) = found {
+                scroll_to_pixel(model, idx);
+            }
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SimulateDictClear(s) => {
+            model.dict_clear = s.parse().ok().map(|row| DictClear {
+                row,
+                filesize: filesize_with_dict_clear(model.img.image(), row),
+            });
+        }
+
+        Msg::AnalyzeBands => {
+            model.band_sizes = Some(
+                qoi_analyze_chunked(model.img.image(), BAND_ROWS)
+                    .map(|res| res.filesize)
+                    .collect(),
+            );
+        }
+
+        Msg::Scrub(s) => {
+            let pos = match s.parse::<usize>() {
+                Ok(pos) => pos.min(model.img.pixel_count()),
+                Err(_) => return,
+            };
+            model.scrub = Some(pos);
+            update_trace_log(model);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::CompareInitialPixel(hex) => {
+            model.initial_compare = QoiPixel::from_css_hex(&hex).map(|px| {
+                let res = qoi_analyze_compare(model.img.image(), QOI_INITIAL_PIXEL, px);
+                (px, res)
+            });
+        }
+
+        Msg::MeasureAnalysisTime => {
+            model.analysis_time_remeasured = Some(util::measure_analysis_time(&model.img));
+        }
+
+        Msg::StartBenchmark => {
+            if model.benchmark_running {
+                return;
+            }
+            model.benchmark_running = true;
+
+            // let the browser paint the spinner before blocking. `after_next_render()` is too early.
+            orders.perform_cmd(cmds::timeout(BENCHMARK_DELAY_MS, || Msg::RunBenchmark));
+        }
+
+        Msg::RunBenchmark => {
+            model.benchmark = Some(run_benchmark(model.img.image(), BENCHMARK_ITERATIONS));
+            model.benchmark_running = false;
+        }
+
+        Msg::SetRun8Max(run8_max) => {
+            model.analyzer_config.run8_max = run8_max;
+            update_analyzer_config_filesize(model);
+        }
+
+        Msg::SetDictSize(dict_size) => {
+            model.analyzer_config.dict_size = dict_size;
+            update_analyzer_config_filesize(model);
+        }
+
+        Msg::ToggleBorders => {
+            model.show_borders = !model.show_borders;
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::OpenContextMenu(chunk, x, y) => {
+            model.context_menu = Some(ContextMenu { chunk, x, y });
+        }
+
+        Msg::ContextMenu(chunk, action) => {
+            let idx = chunk as usize;
+            let text = match action {
+                ContextMenuAction::Color => {
+                    let [r, g, b] = model.config.color(chunk);
+                    format!("#{:02X}{:02X}{:02X}", r, g, b)
+                }
+                ContextMenuAction::Count => model.img.histogram()[idx].to_string(),
+                ContextMenuAction::Percentage => {
+                    format!("{:.2}", 100. * model.img.histogram_normalized()[idx])
+                }
+            };
+            if let Err(e) = util::copy_to_clipboard(&text) {
+                log!(format!("cannot copy to clipboard: {}", e));
+            }
+        }
+
+        Msg::CloseContextMenu => {
+            if model.context_menu.take().is_none() {
+                orders.skip();
+            }
+        }
+
+        Msg::RestoreHistory(i) => {
+            let img = match model.analysis_history.get(i) {
+                Some(img) => img.clone(),
+                None => return,
+            };
+            model.img = img;
+            model.multi_frame.clear();
+            model.frame_thumbnails.clear();
+            model.frame = 0;
+            on_image_changed(model);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ClearHistory => {
+            model.analysis_history.clear();
+        }
+
+        Msg::SaveSession => {
+            let win = window();
+            let scroll = (win.scroll_x().unwrap_or(0.), win.scroll_y().unwrap_or(0.));
+            let session = Session::new(&model.img, model.config, model.zoom, scroll);
+            let blob = gloo_file::Blob::new_with_options(
+                session.to_json().as_str(),
+                Some("application/json"),
+            );
+            if let Err(e) = util::download_blob(SESSION_FILENAME, &blob) {
+                log!(format!("cannot download '{}': {}", SESSION_FILENAME, e));
+            }
+        }
+
+        Msg::LoadSessionFile => {
+            let input_session = model.refs.input_session.get().unwrap();
+            let files = input_session.files().unwrap();
+            let files = gloo_file::FileList::from(files);
+            if files.is_empty() {
+                return;
+            }
+
+            orders.perform_cmd(async move {
+                let file = &files[0];
+                let res = gloo_file::futures::read_as_text(file)
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| Session::from_json(&json));
+                match res {
+                    Ok(session) => Some(Msg::RestoreSession(Box::new(session))),
+                    Err(e) => {
+                        log!(format!("cannot load session '{}': {}", file.name(), e));
+                        None
+                    }
+                }
+            });
+        }
+
+        Msg::RestoreSession(session) => {
+            // pixels are not saved. analyze a blank image of the same size as a placeholder.
+            let width = session.width.clamp(1, BLANK_IMAGE_SIDE_MAX);
+            let height = session.height.clamp(1, BLANK_IMAGE_SIDE_MAX);
+            let buf = [0, 0, 0, 255].repeat((width * height) as usize);
+            let name = format!("{} (placeholder)", session.image_name);
+            match StaticImage::from_rgba_bytes(name, width, height, buf) {
+                Ok(img) => {
+                    model.img = img;
+                    model.multi_frame.clear();
+                    model.frame_thumbnails.clear();
+                    model.frame = 0;
+                    on_image_changed(model);
+                }
+                Err(e) => {
+                    log!(format!("cannot create placeholder image: {}", e));
+                }
+            }
+
+            push_config_history(model);
+            model.config = session.config;
+            model.config.set_dark_palette(model.dark_mode);
+            if ZOOM_LEVELS.contains(&session.zoom) {
+                model.zoom = session.zoom;
+            }
+            let (scroll_x, scroll_y) = session.scroll;
+            model.session = Some(*session);
+
+            orders.after_next_render(move |_| {
+                window().scroll_to_with_x_and_y(scroll_x, scroll_y);
+                Msg::Visualize
+            });
+        }
+
+        Msg::SetChunkLabelMode(mode) => {
+            model.config.set_chunk_label_mode(mode);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SetChunkBorderMode(mode) => {
+            model.config.set_show_chunk_borders(mode);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SetGridSpacing(spacing) => {
+            model.config.set_grid_spacing(spacing);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ToggleBorderedFamily(family) => {
+            model.border_config.toggle_bordered(family);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SetBorderColor(hex) => {
+            let color = match hex {
+                Some(hex) => match QoiPixel::from_css_hex(&hex) {
+                    Some(px) => Some([px.r(), px.g(), px.b()]),
+                    None => return,
+                },
+                None => None,
+            };
+            model.border_config.set_color(color);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SetBorderMinGroupSize(s) => {
+            let size = match s.parse() {
+                Ok(size) => size,
+                Err(_) => return,
+            };
+            model.border_config.set_min_group_size(size);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::TogglePlayback => {
+            if model.playback.take().is_none() {
+                if model.scrub.is_none_or(|pos| pos >= model.img.pixel_count()) {
+                    model.scrub = Some(0);
+                }
+                model.playback = Some(Playback::new(util::now_ms()));
+                model.playback_generation += 1;
+                let generation = model.playback_generation;
+                orders
+                    .after_next_render(move |info| Msg::PlaybackFrame(generation, info.timestamp));
+            }
+        }
+
+        Msg::SetPlaybackSpeed(speed) => {
+            model.playback_speed = speed;
+        }
+
+        Msg::PlaybackFrame(generation, timestamp) => {
+            if model.playback.is_none() || generation != model.playback_generation {
+                return;
+            }
+            let start = util::now_ms();
+            let pixel_count = model.img.pixel_count();
+            let pos = (model.scrub.unwrap_or(0) + model.playback_speed).min(pixel_count);
+            model.scrub = Some(pos);
+            update_trace_log(model);
+            draw_vis(model);
+            let frame_ms = util::now_ms() - start;
+
+            let playback = model.playback.as_mut().expect("playback should be running");
+            playback.frame_ms = frame_ms;
+            playback.frames += 1;
+            if playback.frames % PLAYBACK_FPS_WINDOW == 0 {
+                let window_ms = (timestamp - playback.fps_window_start_ms).max(f64::EPSILON);
+                playback.fps = Some(1000. * f64::from(PLAYBACK_FPS_WINDOW) / window_ms);
+                playback.fps_window_start_ms = timestamp;
+            }
+
+            if pos == pixel_count {
+                model.playback = None;
+            } else {
+                orders
+                    .after_next_render(move |info| Msg::PlaybackFrame(generation, info.timestamp));
+            }
+        }
+
+        Msg::ClearScrub => {
+            model.playback = None;
+            model.scrub = None;
+            update_trace_log(model);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::SelectTraceEntry(idx) => {
+            model.trace_selected = Some(idx);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::LocatePixel(idx) => {
+            model.located_pixel = Some(idx);
+            scroll_to_pixel(model, idx);
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+
+        Msg::ClearSensitivity => {
+            model.sensitivity = SensitivityState::default();
+
+            orders.after_next_render(|_| Msg::Visualize);
+        }
+    }
+}
+
+/// Scrolls the window so that pixel `idx` of the visualization is at the center.
+fn scroll_to_pixel(model: &Model, idx: usize) {
+    let canvas = match model.refs.canvas.get() {
+        Some(canvas) => canvas,
+        None => return,
+    };
+    let width = model.img.width() as usize;
+    let zoom = f64::from(model.zoom);
+    let x = zoom * (idx % width) as f64;
+    let y = zoom * (idx / width) as f64;
+
+    let rect = canvas.get_bounding_client_rect();
+    let win = window();
+    let inner_width = win
+        .inner_width()
+        .ok()
+        .and_then(|w| w.as_f64())
+        .unwrap_or(0.);
+    let inner_height = win
+        .inner_height()
+        .ok()
+        .and_then(|h| h.as_f64())
+        .unwrap_or(0.);
+
+    win.scroll_by_with_x_and_y(
+        rect.left() + x - inner_width / 2.,
+        rect.top() + y - inner_height / 2.,
+    );
+}
+
+/// Computes the region of the visualization canvas visible in the window.
+fn compute_viewport(model: &Model) -> Option<Viewport> {
+    let canvas = model.refs.canvas.get()?;
+    let rect = canvas.get_bounding_client_rect();
+    if rect.width() <= 0. || rect.height() <= 0. {
+        return None;
+    }
+
+    let win = window();
+    let inner_width = win.inner_width().ok()?.as_f64()?;
+    let inner_height = win.inner_height().ok()?.as_f64()?;
+
+    let left = (-rect.left()).clamp(0., rect.width());
+    let top = (-rect.top()).clamp(0., rect.height());
+    let right = (inner_width - rect.left()).clamp(left, rect.width());
+    let bottom = (inner_height - rect.top()).clamp(top, rect.height());
+
+    Some(Viewport {
+        left: left / rect.width(),
+        top: top / rect.height(),
+        width: (right - left) / rect.width(),
+        height: (bottom - top) / rect.height(),
+    })
+}
+
+fn push_analysis_history(model: &mut Model) {
+    if model.analysis_history.len() == ANALYSIS_HISTORY_MAX {
+        model.analysis_history.remove(0);
+    }
+    model.analysis_history.push(model.img.clone());
+}
+
+fn push_config_history(model: &mut Model) {
+    if model.config_history.len() == CONFIG_HISTORY_MAX {
+        model.config_history.remove(0);
+    }
+    model.config_history.push(model.config);
+}
+
+fn scatter_point(img: &StaticImage) -> (f64, f64) {
+    (img.spatial_entropy(), img.compression_ratio())
+}
+
+/// Resets states depending on the current image.
+fn on_image_changed(model: &mut Model) {
+    model.sensitivity = SensitivityState::default();
+    model.byte_jump = None;
+    model.dict_clear = None;
+    model.alpha_threshold = None;
+    model.band_sizes = None;
+    model.scrub = None;
+    model.initial_compare = None;
+    model.analysis_time_remeasured = None;
+    model.reorder = None;
+    model.halves = None;
+    model.benchmark = None;
+    model.session = None;
+    model.before_after = None;
+    update_analyzer_config_filesize(model);
+    model.chunk_rle_len = export::rle_compress_chunks(model.img.chunks()).len();
+    update_trace_log(model);
+    update_reference_diff(model);
+    if model.decode_preview.is_some() {
+        model.decode_preview = Some(DecodePreview::new(model.img.image()));
+    }
+}
+
+fn update_reference_diff(model: &mut Model) {
+    model.reference_diff = model
+        .reference
+        .as_ref()
+        .filter(|reference| {
+            reference.width() == model.img.width() && reference.height() == model.img.height()
+        })
+        .map(|reference| {
+            let (a, b) = (reference.chunks(), model.img.chunks());
+            (chunk_sequence_similarity(a, b), qoi_compare_chunks(a, b))
+        });
+    model.reference_warm_filesize = model
+        .reference
+        .as_ref()
+        .map(|reference| simulate_with_initial_dict(model.img.image(), reference.dict()).filesize);
+}
+
+fn update_trace_log(model: &mut Model) {
+    let pos = model.scrub.unwrap_or_else(|| model.img.pixel_count());
+    model.trace_log = trace_before(&model.img, pos, TRACE_LOG_MAX);
+    model.trace_selected = None;
+}
+
+fn update_frame_thumbnails(model: &mut Model) {
+    model.frame_thumbnails = model
+        .multi_frame
+        .iter()
+        .map(|frame| frame.thumbnail(FRAME_THUMBNAIL_SIDE))
+        .collect();
+}
+
+fn update_expensive_pixels(model: &mut Model) {
+    model.most_expensive = most_expensive_pixel(model.img.image(), model.img.chunks());
+    model.expensive_pixels =
+        most_expensive_pixels(model.img.image(), model.img.chunks(), EXPENSIVE_PIXELS_MAX);
+    model.located_pixel = None;
+}
+
+fn update_analyzer_config_filesize(model: &mut Model) {
+    if model.analyzer_config == AnalyzerConfig::default() {
+        model.analyzer_config_filesize = model.img.filesize_qoi();
+        model.analyzer_config_changed = 0;
+        return;
+    }
+
+    let mut diff = DiffAnalyzer::new(AnalyzerConfig::default(), model.analyzer_config);
+    let mut changed = 0;
+    for &Rgba(rgba) in model.img.image().pixels() {
+        changed += diff
+            .push(QoiPixel::from(rgba))
+            .filter(|(primary, reference)| primary != reference)
+            .count();
+    }
+    let (_, filesize, rest) = diff.finalize();
+    changed += rest
+        .iter()
+        .filter(|(primary, reference)| primary != reference)
+        .count();
+
+    model.analyzer_config_filesize = filesize;
+    model.analyzer_config_changed = changed;
+}
+
+fn update_sensitivity(model: &mut Model) {
+    let (x, y) = match model.sensitivity.pos {
+        Some(pos) => pos,
+        None => return,
+    };
+    let idx = (y as usize) * (model.img.width() as usize) + (x as usize);
+    let px_new = QoiPixel::from(model.sensitivity.rgba);
+
+    model.sensitivity.result = analyze_sensitivity(&model.img, idx, px_new);
+}
+
+fn start_loading(files: gloo_file::FileList, model: &mut Model, orders: &mut impl Orders<Msg>) {
+    const PROGRESS_CHANNEL_CAP: usize = 16;
+
+    if !model.auto_resize && files[0].size() > LARGE_FILE_BYTES {
+        push_toast(
+            model,
+            ToastKind::Warning,
+            "Large image; analysis may be slow. Enabling auto-resize makes it faster.",
+        );
+    }
+
+    let (tx, rx) = mpsc::channel(PROGRESS_CHANNEL_CAP);
+    model.progress = Some(0.);
+    let max_side = model.auto_resize.then_some(AUTO_RESIZE_SIDE);
+
+    orders
+        .stream(rx.map(Msg::Progress))
+        .perform_cmd(async move {
+            let file = &files[0];
+            load_file(file, max_side, tx).await
+        });
+}
+
+async fn load_file(
+    file: &gloo_file::File,
+    max_side: Option<u32>,
+    progress: mpsc::Sender<f32>,
+) -> Msg {
+    let res = if file.raw_mime_type() == "image/gif" {
+        StaticImage::load_gif_frames(file.name(), file, max_side, progress)
+            .await
+            .map(Msg::UpdateFrames)
+    } else {
+        StaticImage::from_blob(file.name(), file, max_side, progress)
+            .await
+            .map(|img| Msg::UpdateImage(Box::new(img)))
+    };
+
+    match res {
+        Ok(msg) => {
+            log!(format!("loaded image '{}'", file.name()));
+            msg
+        }
+        Err(e) => {
+            log!(format!("cannot load image '{}': {}", file.name(), e));
+            Msg::LoadFailed(e.to_string())
+        }
+    }
+}
+
+fn push_toast(model: &mut Model, kind: ToastKind, message: impl Into<String>) {
+    model.toasts.push_back(Toast {
+        message: message.into(),
+        kind,
+        expires_at: util::now_ms() + TOAST_DURATION_MS,
+    });
+}
+
+fn file_stem(name: &str) -> &str {
+    std::path::Path::new(name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(name)
+}
+
+fn draw_vis(model: &Model) {
+    const SENSITIVITY_COLOR: Rgba<u8> = Rgba([0xFF, 0x80, 0x00, 0xFF]);
+    const WASTED_COLOR: Rgba<u8> = Rgba([0xFF, 0x00, 0xFF, 0xFF]);
+
+    const NOT_ENCODED_COLOR: Rgba<u8> = Rgba([0x40, 0x40, 0x40, 0xFF]);
+    const GRID_COLOR: [u8; 4] = [0x80, 0x80, 0x80, 0xC0];
+
+    let mut img_vis = if model.show_run_lengths {
+        visualize_run_length_gradient(&model.img)
+    } else if model.show_borders {
+        visualize_with_borders(&model.img, &model.config, &model.border_config)
+    } else {
+        visualize(&model.img, &model.config)
+    };
+    overlay_chunk_borders(
+        &mut img_vis,
+        model.img.chunks(),
+        model.config.show_chunk_borders(),
+        CHUNK_BORDER_COLOR,
+    );
+    let width = img_vis.width() as usize;
+    if let Some(pos) = model.scrub {
+        for px in img_vis.pixels_mut().skip(pos) {
+            *px = NOT_ENCODED_COLOR;
+        }
+    }
+    if model.highlight_wasted {
+        for idx in wasted_dict_writes(model.img.image(), model.img.chunks()) {
+            img_vis.put_pixel((idx % width) as u32, (idx / width) as u32, WASTED_COLOR);
+        }
+    }
+    for &idx in &model.sensitivity.result.changed {
+        let x = (idx % width) as u32;
+        let y = (idx / width) as u32;
+        img_vis.put_pixel(x, y, SENSITIVITY_COLOR);
+    }
+    if let Some((_, idx)) = model.byte_jump.as_ref().and_then(|jump| jump.found) {
+        draw_marker(&mut img_vis, (idx % width) as u32, (idx / width) as u32);
+    }
+    if let Some(idx) = model.located_pixel {
+        draw_marker(&mut img_vis, (idx % width) as u32, (idx / width) as u32);
+    }
+    if let Some(idx) = model.trace_selected {
+        draw_marker(&mut img_vis, (idx % width) as u32, (idx / width) as u32);
+    }
+    // the grid is drawn after scaling to keep lines 1 pixel wide.
+    match (canvas_scale(model), model.config.grid_spacing()) {
+        (1, None) => put_image(&model.refs.canvas, &img_vis),
+        (scale, grid_spacing) => {
+            let mut img_canvas = util::nearest_neighbor_scale(&img_vis, scale);
+            if let Some(spacing) = grid_spacing {
+                overlay_grid(&mut img_canvas, spacing * scale, GRID_COLOR);
+            }
+            put_image(&model.refs.canvas, &img_canvas);
+        }
+    }
+    if has_chunk_labels(model) {
+        draw_chunk_labels(model, &img_vis);
+    }
+
+    if has_minimap(&model.img) {
+        let (w, h) = img_vis.dimensions();
+        let img_mini = image::imageops::thumbnail(
+            &img_vis,
+            (w / MINIMAP_SCALE).max(1),
+            (h / MINIMAP_SCALE).max(1),
+        );
+        put_image(&model.refs.canvas_minimap, &img_mini);
+    }
+
+    put_image(
+        &model.refs.canvas_column_chart,
+        &visualize_column_chart(&model.img, model.config.colors(), COLUMN_CHART_HEIGHT),
+    );
+
+    if let Some((_, img)) = &model.reorder {
+        put_image(&model.refs.canvas_reorder, &visualize(img, &model.config));
+    }
+
+    if let Some(DecodePreview {
+        decoded: Ok(decoded),
+        ..
+    }) = &model.decode_preview
+    {
+        put_image(&model.refs.canvas_decoded, decoded);
+    }
+
+    if let Some(reference) = &model.reference {
+        if let Ok(img_diff) = visualize_diff(reference, &model.img, &model.config) {
+            put_image(&model.refs.canvas_diff, &img_diff);
+        }
+    }
+}
+
+/// Draws a square outline around pixel (x, y).
+fn draw_marker(img: &mut RgbaImage, x: u32, y: u32) {
+    const MARKER_COLOR: Rgba<u8> = Rgba([0xFF, 0x00, 0xFF, 0xFF]);
+    const MARKER_RADIUS: i64 = 3;
+
+    let (x, y) = (i64::from(x), i64::from(y));
+    for dy in -MARKER_RADIUS..=MARKER_RADIUS {
+        for dx in -MARKER_RADIUS..=MARKER_RADIUS {
+            if dx.abs() != MARKER_RADIUS && dy.abs() != MARKER_RADIUS {
+                continue;
+            }
+            if let (Ok(xx), Ok(yy)) = (u32::try_from(x + dx), u32::try_from(y + dy)) {
+                if xx < img.width() && yy < img.height() {
+                    img.put_pixel(xx, yy, MARKER_COLOR);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the scale of the visualization canvas pixels. The canvas is drawn enlarged to keep
+/// pixel boundaries sharp where CSS `image-rendering` is not honored, unless it gets too large.
+fn canvas_scale(model: &Model) -> u32 {
+    let fits = model.img.width() * model.zoom <= LABEL_CANVAS_SIDE_MAX
+        && model.img.height() * model.zoom <= LABEL_CANVAS_SIDE_MAX;
+    if model.zoom >= 2 && fits {
+        model.zoom
+    } else {
+        1
+    }
+}
+
+fn has_chunk_labels(model: &Model) -> bool {
+    model.config.chunk_label_mode() != ChunkLabelMode::None
+        && model.zoom >= LABEL_ZOOM_MIN
+        && model.img.width() * model.zoom <= LABEL_CANVAS_SIDE_MAX
+        && model.img.height() * model.zoom <= LABEL_CANVAS_SIDE_MAX
+}
+
+/// Draws the chunk type of each pixel as text over the zoomed visualization `img_vis`.
+fn draw_chunk_labels(model: &Model, img_vis: &RgbaImage) {
+    let canvas = match model.refs.canvas_labels.get() {
+        Some(canvas) => canvas,
+        None => return,
+    };
+    let ctx = canvas_context_2d(&canvas);
+
+    ctx.clear_rect(
+        0.,
+        0.,
+        f64::from(canvas.width()),
+        f64::from(canvas.height()),
+    );
+    let font_px = if model.zoom >= 16 { 8 } else { 6 };
+    ctx.set_font(&format!("{}px monospace", font_px));
+    // monospace glyphs are about 0.6 em wide.
+    let max_chars = ((f64::from(model.zoom) / (0.6 * f64::from(font_px))) as usize).max(1);
+    let mode = model.config.chunk_label_mode();
+    ctx.set_text_align("center");
+    ctx.set_text_baseline("middle");
+
+    let width = model.img.width() as usize;
+    for (idx, chunk) in model.img.chunks().iter().enumerate() {
+        let x = (idx % width) as u32;
+        let y = (idx / width) as u32;
+        // contrast with the drawn color, which may differ from the chunk color.
+        let luma = QoiPixel::from(img_vis.get_pixel(x, y).0).luminance();
+        let text_color = if luma > 128_000 { "black" } else { "white" };
+
+        if let Some(text) = mode.text(*chunk, max_chars) {
+            ctx.set_fill_style(&JsValue::from_str(text_color));
+            util::overlay_text_on_canvas(&ctx, &text, x, y, model.zoom);
+        }
+    }
+}
+
+fn view_zoom(model: &Model) -> Node<Msg> {
+    div![
+        "Zoom: ",
+        ZOOM_LEVELS.iter().map(|&zoom| {
+            button![
+                IF!(zoom == model.zoom => attrs! {At::Disabled => ""}),
+                format!("{}x", zoom),
+                ev(Ev::Click, move |_| Msg::SetZoom(zoom)),
+            ]
+        }),
+    ]
+}
+
+/// The visualization canvas, with the chunk label overlay at high zoom.
+fn view_vis_canvas(model: &Model) -> Node<Msg> {
+    let (w, h) = (model.img.width(), model.img.height());
+    let zoom = model.zoom;
+    let scale = canvas_scale(model);
+
+    div![
+        style! {
+            St::Position => "relative",
+            St::Display => "inline-block",
+            St::LineHeight => 0,
+        },
+        IF!(model.comparison_dragging => mouse_ev(Ev::MouseMove, |ev| Msg::DragComparison(ev.client_x()))),
+        canvas![
+            el_ref(&model.refs.canvas),
+            C![model.background.class_name()],
+            IF!(model.grayscale => style! {St::Filter => "grayscale(100%)"}),
+            attrs! {
+                At::Width => px(w * scale),
+                At::Height => px(h * scale),
+            },
+            if model.is_fullscreen {
+                style! {
+                    St::Width => percent(100),
+                    St::Height => percent(100),
+                    "object-fit" => "contain",
+                    "image-rendering" => "pixelated",
+                }
+            } else {
+                style! {
+                    St::Width => px(w * zoom),
+                    St::Height => px(h * zoom),
+                    "image-rendering" => "pixelated",
+                }
+            },
+            mouse_ev(Ev::Click, move |ev| Msg::SelectPixel(
+                ev.offset_x() / zoom as i32,
+                ev.offset_y() / zoom as i32
+            )),
+            mouse_ev(Ev::MouseMove, move |ev| Msg::CanvasHover(
+                ev.offset_x() / zoom as i32,
+                ev.offset_y() / zoom as i32
+            )),
+            mouse_ev(Ev::MouseLeave, |_| Msg::CanvasLeave),
+        ],
+        model
+            .comparison_slider
+            .map(|pos| view_comparison_overlay(model, pos)),
+        IF!(has_chunk_labels(model) => canvas![
+            el_ref(&model.refs.canvas_labels),
+            attrs! {
+                At::Width => px(w * zoom),
+                At::Height => px(h * zoom),
+            },
+            style! {
+                St::Position => "absolute",
+                St::Left => 0,
+                St::Top => 0,
+                St::PointerEvents => "none",
+            },
+        ]),
+    ]
+}
+
+/// Chunk type fractions of each column, aligned with the visualization canvas.
+fn view_column_chart(model: &Model) -> Node<Msg> {
+    div![
+        attrs! {At::Title => "chunk type fractions of each column"},
+        canvas![
+            el_ref(&model.refs.canvas_column_chart),
+            attrs! {
+                At::Width => px(model.img.width()),
+                At::Height => px(COLUMN_CHART_HEIGHT),
+            },
+            style! {
+                St::Width => px(model.img.width() * model.zoom),
+                St::Height => px(COLUMN_CHART_HEIGHT),
+                "image-rendering" => "pixelated",
+            },
+        ],
+    ]
+}
+
+/// Original image clipped to the left of the divider at `pos` (fraction of the width), and the
+/// draggable divider.
+fn view_comparison_overlay(model: &Model, pos: f64) -> Vec<Node<Msg>> {
+    let (w, h) = (model.img.width(), model.img.height());
+    let zoom = model.zoom;
+    let percent = 100. * pos;
+
+    vec![
+        img![
+            C![model.background.class_name()],
+            attrs! {At::Src => model.img.url()},
+            style! {
+                St::Position => "absolute",
+                St::Left => 0,
+                St::Top => 0,
+                St::Width => px(w * zoom),
+                St::Height => px(h * zoom),
+                St::PointerEvents => "none",
+                "image-rendering" => "pixelated",
+                "clip-path" => format!("inset(0 {:.2}% 0 0)", 100. - percent),
+            },
+        ],
+        div![
+            attrs! {At::Title => "drag to compare the original (left) with the visualization (right)"},
+            style! {
+                St::Position => "absolute",
+                St::Left => format!("{:.2}%", percent),
+                St::Top => 0,
+                St::Width => px(4),
+                St::Height => "100%",
+                St::MarginLeft => px(-2),
+                St::BackgroundColor => "white",
+                St::BoxShadow => "0 0 2px black",
+                St::Cursor => "ew-resize",
+            },
+            mouse_ev(Ev::MouseDown, |ev| {
+                // prevent text selection while dragging.
+                ev.prevent_default();
+                Msg::StartComparisonDrag
+            }),
+        ],
+    ]
+}
+
+fn view_before_after(model: &Model, ba: &BeforeAfter) -> Node<Msg> {
+    let (w, h) = ba.dimensions();
+    let (hist_before, hist_after) = (
+        ba.before.histogram_normalized(),
+        model.img.histogram_normalized(),
+    );
+    let t = f64::from(ba.alpha);
+
+    let bars = QoiChunk::iter().map(|chunk| {
+        let idx = chunk as usize;
+        let percent = 100. * (hist_before[idx] + t * (hist_after[idx] - hist_before[idx]));
+        let [r, g, b] = model.config.color(chunk);
+        div![
+            style! {St::Display => "flex", St::AlignItems => "center", St::FontSize => em(0.8)},
+            span![style! {St::Width => em(6)}, chunk.name()],
+            div![style! {
+                St::Width => format!("{:.3}%", 0.6 * percent),
+                St::Height => px(10),
+                St::BackgroundColor => format!("rgb({},{},{})", r, g, b),
+            }],
+            span![format!(" {:.1} %", percent)],
+        ]
+    });
+
+    div![
+        button![
+            if ba.alpha >= 0.5 { "After" } else { "Before" },
+            attrs! {At::Title => "Toggle between the analysis before and after the last preprocessing"},
+            ev(Ev::Click, |_| Msg::StartBeforeAfter),
+        ],
+        div![canvas![
+            el_ref(&model.refs.canvas_before_after),
+            attrs! {At::Width => w, At::Height => h},
+            style! {St::MaxWidth => percent(100)},
+        ]],
+        bars,
+    ]
+}
+
+fn put_image(canvas: &ElRef<HtmlCanvasElement>, img: &RgbaImage) {
+    let canvas = match canvas.get() {
+        Some(canvas) => canvas,
+        None => return,
+    };
+    let image_data = util::create_image_data(img).unwrap();
+
+    let ctx = canvas_context_2d(&canvas);
+
+    ctx.put_image_data(&image_data, 0., 0.).unwrap();
+}
+
+fn view(model: &Model) -> Node<Msg> {
+    div![
+        view_print_header(model),
+        view_header(model),
+        view_sidebar(model),
+        view_vis(model),
+        view_help(model),
+        view_status_bar(model),
+        view_toasts(model),
+        button![
+            C!["sidebar-toggle"],
+            if model.sidebar_open {
+                "close panel"
+            } else {
+                "open panel"
+            },
+            ev(Ev::Click, |_| Msg::ToggleSidebarDrawer),
+        ],
+        model.context_menu.as_ref().map(view_context_menu),
+    ]
+}
+
+fn view_context_menu(menu: &ContextMenu) -> Node<Msg> {
+    let chunk = menu.chunk;
+    let items = ContextMenuAction::iter().map(|action| {
+        div![
+            style! {
+                St::Padding => "2px 8px",
+                St::Cursor => "pointer",
+            },
+            action.label(),
+            ev(Ev::Click, move |_| Msg::ContextMenu(chunk, action)),
+        ]
+    });
+
+    div![
+        style! {
+            St::Position => "fixed",
+            St::Left => px(menu.x),
+            St::Top => px(menu.y),
+            St::ZIndex => 10,
+            St::BackgroundColor => "#FFFFFF",
+            St::Color => "#000000",
+            St::Border => "1px solid #808080",
+            St::BoxShadow => "2px 2px 4px rgba(0, 0, 0, 0.3)",
+        },
+        div![
+            style! {
+                St::Padding => "2px 8px",
+                St::FontWeight => "bold",
+            },
+            chunk.name(),
+        ],
+        items,
+    ]
+}
+
+fn view_status_bar(model: &Model) -> Node<Msg> {
+    let text = match model.hovered_chunk {
+        Some((x, y, chunk)) => {
+            let idx = (y as usize) * (model.img.width() as usize) + (x as usize);
+            let [r, g, b, a] = model.img.image().get_pixel(x, y).0;
+            let offset = chunk_byte_offset(model.img.chunks(), idx)
+                .map_or_else(|| "-".to_owned(), |offset| offset.to_string());
+            format!(
+                "Pixel ({}, {}): {} | RGBA({},{},{},{}) | byte cost: {} | offset: {}",
+                x,
+                y,
+                chunk.name(),
+                r,
+                g,
+                b,
+                a,
+                chunk.byte_len(),
+                offset
+            )
+        }
+        None => "Hover over a pixel for details".to_owned(),
+    };
+
+    div![id!("status-bar"), text]
+}
+
+fn view_toasts(model: &Model) -> Node<Msg> {
+    div![
+        id!("toasts"),
+        model
+            .toasts
+            .iter()
+            .map(|toast| div![C!["toast", toast.kind.class_name()], &toast.message]),
+    ]
+}
+
+fn view_help(model: &Model) -> Node<Msg> {
+    let rows = SHORTCUTS
+        .iter()
+        .map(|(key, description)| tr![td![kbd![key]], td![description]]);
+
+    dialog![
+        IF!(model.show_help => attrs! {At::Open => ""}),
+        h3!["Keyboard shortcuts"],
+        table![tbody![rows]],
+        button!["close", ev(Ev::Click, |_| Msg::ToggleHelp)],
+    ]
+}
+
+fn view_header(model: &Model) -> Vec<Node<Msg>> {
+    nodes![
+        div![
+            C!["no-print"],
+            label![
+                attrs! {
+                    At::For => "input-file",
+                },
+                "Open (or drop) image file: ",
+            ],
+            input![
+                el_ref(&model.refs.input_file),
+                attrs! {
+                    At::Id => "input-file",
                     At::Type => "file",
                 },
-                ev(Ev::Change, |_| Msg::InputFileChanged),
+                ev(Ev::Change, |_| Msg::InputFileChanged),
+            ],
+            " ",
+            label![attrs! {At::For => "input-data-url"}, "or paste Data URL: "],
+            input![
+                attrs! {
+                    At::Id => "input-data-url",
+                    At::Type => "text",
+                    At::Placeholder => "data:image/png;base64,...",
+                },
+                input_ev(Ev::Change, Msg::LoadDataUrl),
+            ],
+            " ",
+            view_blank_image(model),
+            " ",
+            label![
+                input![
+                    attrs! {
+                        At::Type => "checkbox",
+                        At::Checked => model.auto_resize.as_at_value(),
+                    },
+                    ev(Ev::Change, |_| Msg::ToggleAutoResize),
+                ],
+                format!("Auto-resize to {}px", AUTO_RESIZE_SIDE),
+            ],
+            " ",
+            button![
+                attrs! {At::Title => "toggle dark mode"},
+                if model.dark_mode {
+                    "\u{2600}"
+                } else {
+                    "\u{1F319}"
+                },
+                ev(Ev::Click, |_| Msg::ToggleDarkMode),
+            ],
+            " ",
+            button!["?", ev(Ev::Click, |_| Msg::ToggleHelp)],
+            " ",
+            button![
+                attrs! {At::Title => "print the statistics and both images"},
+                "Print",
+                ev(Ev::Click, |_| Msg::Print),
+            ],
+        ],
+        model.progress.map(view_progress),
+        IF!(model.multi_frame.len() > 1 => view_frame_selector(model)),
+        hr![],
+    ]
+}
+
+/// Image name and analysis time, shown only when printing.
+fn view_print_header(model: &Model) -> Node<Msg> {
+    // `analysis_timestamp()` is relative to the page load, so convert it to the wall clock.
+    let analyzed_ms = js_sys::Date::now() - 1000. * model.img.analysis_age_secs();
+    let analyzed = js_sys::Date::new(&JsValue::from_f64(analyzed_ms))
+        .to_locale_string("default", &JsValue::UNDEFINED);
+
+    div![
+        C!["print-only"],
+        h2![model.img.name()],
+        p![format!("Analyzed at {}", String::from(analyzed))],
+    ]
+}
+
+fn view_blank_image(model: &Model) -> Node<Msg> {
+    let (width, height) = model.blank_size;
+    let side_input = |id: &str, side: u32, msg: fn(String) -> Msg| {
+        input![
+            style! {St::Width => "4em"},
+            attrs! {
+                At::Id => id,
+                At::Type => "number",
+                At::Min => 1,
+                At::Max => BLANK_IMAGE_SIDE_MAX,
+                At::Value => side,
+            },
+            input_ev(Ev::Change, msg),
+        ]
+    };
+
+    span![
+        "or ",
+        label![attrs! {At::For => "input-blank-width"}, "Width "],
+        side_input("input-blank-width", width, Msg::SetBlankWidth),
+        " ",
+        label![attrs! {At::For => "input-blank-height"}, "Height "],
+        side_input("input-blank-height", height, Msg::SetBlankHeight),
+        " ",
+        button!["New blank image", ev(Ev::Click, |_| Msg::NewBlankImage)],
+    ]
+}
+
+fn view_progress(progress: f32) -> Node<Msg> {
+    let stage = if progress < 0.3 {
+        "Reading"
+    } else if progress < 0.6 {
+        "Decoding"
+    } else {
+        "Analyzing"
+    };
+
+    div![
+        progress![attrs! {
+            At::Max => 1,
+            At::Value => progress,
+        }],
+        format!(" {} ({:.0} %)", stage, 100. * progress),
+    ]
+}
+
+fn view_frame_selector(model: &Model) -> Node<Msg> {
+    const SPARKLINE_WIDTH: f64 = 200.;
+    const SPARKLINE_HEIGHT: f64 = 32.;
+
+    let frame_count = model.multi_frame.len();
+    let frame_prev = model.frame.saturating_sub(1);
+    let frame_next = (model.frame + 1).min(frame_count - 1);
+
+    // compression ratio of each frame (QOI size / raw RGBA size).
+    let ratios: Vec<_> = model
+        .multi_frame
+        .iter()
+        .map(StaticImage::compression_ratio)
+        .collect();
+    let ratio_max = ratios.iter().copied().fold(f64::EPSILON, f64::max);
+    let points: Vec<_> = ratios
+        .iter()
+        .enumerate()
+        .map(|(i, ratio)| {
+            let x = SPARKLINE_WIDTH * (i as f64) / ((frame_count - 1) as f64);
+            let y = SPARKLINE_HEIGHT * (1. - ratio / ratio_max);
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect();
+
+    let thumbnails = model.frame_thumbnails.iter().enumerate().map(|(i, thumb)| {
+        img![
+            attrs! {
+                At::Src => thumb.url(),
+                At::Title => format!(
+                    "frame {}: {} (ratio {:.3})",
+                    i + 1,
+                    thumb.dimensions_label(),
+                    thumb.compression_ratio()
+                ),
+            },
+            style! {
+                St::Cursor => "pointer",
+                St::Margin => px(1),
+                St::Outline => if i == model.frame { "2px solid red" } else { "none" },
+            },
+            ev(Ev::Click, move |_| Msg::SelectFrame(i)),
+        ]
+    });
+
+    div![
+        div![
+            style! {
+                St::OverflowX => "auto",
+                St::WhiteSpace => "nowrap",
+            },
+            thumbnails,
+        ],
+        div![svg![
+            attrs! {
+                At::Width => px(SPARKLINE_WIDTH),
+                At::Height => px(SPARKLINE_HEIGHT),
+                At::ViewBox => format!("0 0 {} {}", SPARKLINE_WIDTH, SPARKLINE_HEIGHT),
+            },
+            polyline![attrs! {
+                At::Points => points.join(" "),
+                At::Fill => "none",
+                At::Stroke => "black",
+            }],
+        ]],
+        div![
+            button!["prev", ev(Ev::Click, move |_| Msg::SelectFrame(frame_prev))],
+            " ",
+            input![
+                attrs! {
+                    At::Type => "range",
+                    At::Min => 0,
+                    At::Max => frame_count - 1,
+                    At::Value => model.frame,
+                },
+                input_ev(Ev::Input, |s| s.parse().ok().map(Msg::SelectFrame)),
+            ],
+            " ",
+            button!["next", ev(Ev::Click, move |_| Msg::SelectFrame(frame_next))],
+            format!(
+                " frame {} / {} (ratio {:.3})",
+                model.frame + 1,
+                frame_count,
+                ratios[model.frame]
+            ),
+        ],
+    ]
+}
+
+fn view_sidebar(model: &Model) -> Node<Msg> {
+    let baseline = ChunkBaseline::new(&model.aggregate.distributions);
+    let outliers = baseline.outliers(&model.img.histogram_normalized());
+
+    let table_rows: Vec<_> = QoiChunk::iter()
+        .map(|chunk| {
+            let idx = chunk as usize;
+            let id_str = format!("checkbox-visible-{}", idx);
+            let count = model.img.histogram()[idx];
+            let percent = 100. * model.img.histogram_normalized()[idx];
+            let [r, g, b] = model.config.color(chunk);
+            let present = model.img.chunk_present(chunk);
+            let percent_str = if present {
+                format!("{: >5.2} %", percent)
+            } else {
+                "\u{2014}".to_owned()
+            };
+            let flash =
+                model.undo_flash[idx].map(|visible| if visible { "#80E080" } else { "#E08080" });
+            tr![
+                IF!(!present => style! {St::Opacity => 0.4}),
+                mouse_ev(Ev::ContextMenu, move |ev| {
+                    ev.prevent_default();
+                    Msg::OpenContextMenu(chunk, ev.client_x(), ev.client_y())
+                }),
+                flash.map(|color| style! {St::BackgroundColor => color}),
+                IF!(!present => attrs! {At::Title => absent_reason(chunk)}),
+                td![input![
+                    id!(&id_str),
+                    attrs! {
+                        At::Type => "checkbox",
+                    },
+                    IF!(model.config.is_visible(chunk) => attrs! {
+                        At::Checked => "",
+                    }),
+                    ev(Ev::Change, move |ev| {
+                        let checked = ev.target()?.dyn_into::<HtmlInputElement>().ok()?.checked();
+                        Some(Msg::SetChunkVisibility(chunk, checked))
+                    }),
+                ]],
+                td![
+                    style! {
+                        St::TextAlign => "right",
+                        St::WhiteSpace => "nowrap",
+                    },
+                    label![attrs! {At::For => &id_str}, percent_str]
+                ],
+                td![input![
+                    attrs! {
+                        At::Type => "color",
+                        At::Value => format!("#{:02x}{:02x}{:02x}", r, g, b),
+                    },
+                    input_ev(Ev::Input, move |hex| Msg::SetChunkColor(chunk, hex)),
+                ]],
+                td![
+                    label![
+                        attrs! {
+                            At::For => &id_str,
+                            At::Title => chunk_structure_str(chunk),
+                        },
+                        chunk.name()
+                    ],
+                    IF!(outliers[idx] => span![
+                        style! {St::Color => "#E0B000"},
+                        attrs! {At::Title => format!(
+                            "unusual frequency (typical: {:.1} % \u{b1} {:.1} %)",
+                            100. * baseline.mean[idx],
+                            100. * baseline.std_dev[idx]
+                        )},
+                        " \u{26a0}",
+                    ]),
+                ],
+                td![
+                    style! {
+                        St::TextAlign => "right",
+                        St::WhiteSpace => "nowrap",
+                    },
+                    attrs! {At::Title => "bit efficiency (data bits / all bits)"},
+                    format!("{:.0} %", 100. * chunk.bit_efficiency()),
+                ],
+                td![
+                    style! {
+                        St::TextAlign => "right",
+                        St::WhiteSpace => "nowrap",
+                    },
+                    attrs! {At::Title => "theoretical minimum bits (+ coding overhead bits)"},
+                    format!(
+                        "{} (+{})",
+                        chunk.theoretical_minimum_bits(),
+                        chunk.coding_overhead_bits()
+                    ),
+                ],
+                td![
+                    style! {
+                        St::TextAlign => "right",
+                        St::WhiteSpace => "nowrap",
+                    },
+                    saving_str(4 * count, model.img.bytes_by_chunk()[idx]),
+                ],
+            ]
+        })
+        .collect();
+
+    let saving_total = saving_str(
+        4 * model.img.pixel_count(),
+        model.img.bytes_by_chunk().iter().sum(),
+    );
+
+    div![
+        id!("sidebar"),
+        IF!(model.sidebar_open => C!["open"]),
+        style! {St::Width => px(model.sidebar_width)},
+        div![
+            C!["resize-handle"],
+            mouse_ev(Ev::MouseDown, |ev| {
+                // prevent text selection while dragging.
+                ev.prevent_default();
+                Msg::StartResizeSidebar
+            }),
+        ],
+        IF!(has_minimap(&model.img) => view_minimap(model)),
+        IF!(has_minimap(&model.img) => view_visible_chunks(model)),
+        view_bytes_bar(model),
+        view_vis_mode(model),
+        view_borders(model),
+        view_chunk_border_mode(model),
+        view_grid_spacing(model),
+        view_chunk_label_mode(model),
+        view_palette_select(model),
+        div![
+            button!["check all", ev(Ev::Click, |_| Msg::MakeAllChunksVisible)],
+            " ",
+            button![
+                "uncheck all",
+                ev(Ev::Click, |_| Msg::MakeAllChunksInvisible),
+            ],
+            " ",
+            button![
+                attrs! {At::Title => "show each chunk type in turn"},
+                if model.chunk_cycle.is_some() {
+                    "Stop cycle"
+                } else {
+                    "Cycle chunks"
+                },
+                ev(Ev::Click, |_| Msg::ToggleChunkCycle),
+            ],
+        ],
+        model.chunk_cycle.as_ref().map(view_chunk_cycle),
+        view_invisible_color(model),
+        table![
+            tbody![table_rows],
+            tfoot![tr![
+                td![attrs! {At::ColSpan => 6}, "Total (excluding header)"],
+                td![
+                    style! {
+                        St::TextAlign => "right",
+                        St::WhiteSpace => "nowrap",
+                    },
+                    saving_total,
+                ],
+            ]],
+        ],
+        view_sidebar_info(model),
+        view_expensive_pixels(model),
+        view_spec_reference(),
+        view_analyzer_config(model),
+        view_preprocess(model),
+        view_analysis_history(model),
+        view_session(model),
+    ]
+}
+
+/// Table row placing the QOI size between the best and worst cases for the dimensions.
+fn view_byte_cost_bounds(model: &Model) -> Node<Msg> {
+    let pixel_count = model.img.pixel_count();
+    let best = QoiChunk::best_case_byte_cost(pixel_count);
+    let worst = QoiChunk::worst_case_byte_cost(pixel_count);
+    let actual = model.img.filesize_qoi();
+
+    tr![
+        td![
+            attrs! {At::Title => "all pixels in maximal runs / all pixels QOI_COLOR (5-Bytes)"},
+            "Bounds"
+        ],
+        td![
+            format!(
+                "Best case: {} bytes | Actual: {} | Worst: {} ",
+                best, actual, worst
+            ),
+            meter![attrs! {
+                At::Min => best,
+                At::Max => worst,
+                At::Value => actual,
+            }],
+        ],
+    ]
+}
+
+/// Table row comparing bytes/pixel with the typical range for the guessed image class.
+fn view_expected_ratio(model: &Model) -> Node<Msg> {
+    let class = model.img.image_class();
+    let (min, max) = expected_filesize_for_image_class(class);
+    let ratio = model.img.compression_ratio();
+    let outside = ratio < min || ratio > max;
+
+    tr![
+        td![
+            attrs! {At::Title => "rough range for the image type guessed from runs, colors and smoothness"},
+            "Expected"
+        ],
+        td![
+            IF!(outside => style! {St::Color => "red", St::FontWeight => "bold"}),
+            format!(
+                "Expected for this image type ({}): {:.2}\u{2013}{:.2} bytes/pixel (actual {:.2})",
+                class.name(),
+                4. * min,
+                4. * max,
+                4. * ratio
+            ),
+        ],
+    ]
+}
+
+fn mib_str(bytes: usize) -> String {
+    format!("{:.1} MiB", bytes as f64 / f64::from(1 << 20))
+}
+
+/// Parses a side length of blank images. Returns `None` if out of `1..=BLANK_IMAGE_SIDE_MAX`.
+fn parse_blank_side(s: &str) -> Option<u32> {
+    s.trim()
+        .parse()
+        .ok()
+        .filter(|side| (1..=BLANK_IMAGE_SIDE_MAX).contains(side))
+}
+
+/// Formats an elapsed time like `"30 seconds ago"`.
+fn age_str(secs: f64) -> String {
+    let secs = secs.max(0.) as u64;
+    match secs {
+        0..=59 => format!("{} seconds ago", secs),
+        60..=3599 => format!("{} minutes ago", secs / 60),
+        _ => format!("{} hours ago", secs / 3600),
+    }
+}
+
+fn view_analysis_history(model: &Model) -> Node<Msg> {
+    let rows = model
+        .analysis_history
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, img)| {
+            tr![
+                style! {St::Cursor => "pointer"},
+                attrs! {At::Title => "restore this image"},
+                td![img![
+                    attrs! {At::Src => img.url()},
+                    style! {
+                        St::MaxWidth => px(32),
+                        St::MaxHeight => px(32),
+                    },
+                ]],
+                td![img.name()],
+                td![format!("ratio {:.3}", img.compression_ratio())],
+                td![format!("analyzed {}", age_str(img.analysis_age_secs()))],
+                ev(Ev::Click, move |_| Msg::RestoreHistory(i)),
+            ]
+        });
+
+    details![
+        summary![format!("History ({})", model.analysis_history.len())],
+        table![tbody![rows]],
+        button![
+            IF!(model.analysis_history.is_empty() => attrs! {At::Disabled => ""}),
+            "Clear history",
+            ev(Ev::Click, |_| Msg::ClearHistory),
+        ],
+    ]
+}
+
+fn view_session(model: &Model) -> Node<Msg> {
+    let stats = model.session.as_ref().map(|session| {
+        let rows = QoiChunk::iter()
+            .filter(|&chunk| session.histogram[chunk as usize] > 0)
+            .map(|chunk| {
+                tr![
+                    td![chunk.name()],
+                    td![
+                        style! {St::TextAlign => "right"},
+                        session.histogram[chunk as usize]
+                    ],
+                ]
+            });
+
+        div![
+            p![format!(
+                "Statistics of '{}' ({}x{}): QOI {} bytes, original {} bytes",
+                session.image_name,
+                session.width,
+                session.height,
+                session.filesize_qoi,
+                session.filesize_orig
+            )],
+            table![tbody![rows]],
+        ]
+    });
+
+    details![
+        summary!["Session"],
+        div![
+            button![
+                attrs! {At::Title => "download the config and statistics (no pixel data) as JSON"},
+                "Save session",
+                ev(Ev::Click, |_| Msg::SaveSession),
+            ],
+            " ",
+            label![attrs! {At::For => "input-session"}, "Load session: ",],
+            input![
+                el_ref(&model.refs.input_session),
+                attrs! {
+                    At::Id => "input-session",
+                    At::Type => "file",
+                    At::Accept => ".json,application/json",
+                },
+                ev(Ev::Change, |_| Msg::LoadSessionFile),
+            ],
+        ],
+        stats,
+    ]
+}
+
+fn view_invisible_color(model: &Model) -> Node<Msg> {
+    let [r, g, b] = model.config.invisible_color();
+
+    div![label![
+        "Hidden chunk color: ",
+        input![
+            attrs! {
+                At::Type => "color",
+                At::Value => format!("#{:02x}{:02x}{:02x}", r, g, b),
+            },
+            input_ev(Ev::Input, |hex| {
+                QoiPixel::from_css_hex(&hex)
+                    .map(|px| Msg::SetInvisibleColor([px.r(), px.g(), px.b()]))
+            }),
+        ],
+    ]]
+}
+
+fn view_chunk_cycle(cycle: &ChunkCycle) -> Node<Msg> {
+    div![
+        style! {St::Cursor => "pointer"},
+        attrs! {At::Title => "click to pause/resume"},
+        format!(
+            "Showing: {} ({}/{}){}",
+            cycle.chunk().name(),
+            cycle.idx + 1,
+            QoiChunk::COUNT,
+            if cycle.paused { " [paused]" } else { "" }
+        ),
+        ev(Ev::Click, |_| Msg::ToggleChunkCyclePause),
+    ]
+}
+
+fn view_palette_select(model: &Model) -> Node<Msg> {
+    let options = PaletteSet::iter().map(|palette| {
+        option![
+            attrs! {
+                At::Value => palette.name(),
+                At::Selected => (palette == model.config.palette()).as_at_value(),
+            },
+            palette.name(),
+        ]
+    });
+
+    div![
+        label![attrs! {At::For => "select-palette"}, "Palette: "],
+        select![
+            id!("select-palette"),
+            attrs! {At::Title => "customized colors are reset"},
+            options,
+            input_ev(Ev::Change, |name| PaletteSet::from_name(&name)
+                .map(Msg::SetPalette)),
+        ],
+    ]
+}
+
+fn view_spec_reference() -> Node<Msg> {
+    let magic_hex: Vec<_> = QOI_MAGIC.iter().map(|b| format!("{:02X}", b)).collect();
+
+    let rows = QoiChunk::iter().map(|chunk| {
+        tr![
+            td![chunk.name()],
+            td![
+                style! {St::FontFamily => "monospace"},
+                opcode_description(chunk)
+            ],
+        ]
+    });
+
+    details![
+        summary!["QOI Specification"],
+        p![format!(
+            "Header ({} bytes): magic \"qoif\" ({}), width (u32 BE), height (u32 BE), channels (u8), colorspace (u8)",
+            QOI_HEADER_LEN,
+            magic_hex.join(" ")
+        )],
+        table![tbody![rows]],
+        p![format!(
+            "End marker: {:?}. Channel values of diffs wrap around.",
+            QOI_END_MARKER
+        )],
+        p![
+            "This tool follows an early version of the format, which differs from the final ",
+            a![
+                attrs! {At::Href => "https://qoiformat.org", At::Target => "_blank"},
+                "specification"
+            ],
+            ".",
+        ],
+    ]
+}
+
+/// Upper bound of the `AnalyzerConfig::run8_max` slider.
+const RUN8_MAX_SLIDER_MAX: u16 = 128;
+
+/// Choices of `AnalyzerConfig::dict_size`.
+const DICT_SIZES: [usize; 5] = [16, 32, 64, 128, 256];
+
+fn view_analyzer_config(model: &Model) -> Node<Msg> {
+    let run8_max = model.analyzer_config.run8_max;
+    let dict_size = model.analyzer_config.dict_size;
+    let filesize = model.analyzer_config_filesize;
+    let change = (filesize as isize) - (model.img.filesize_qoi() as isize);
+
+    details![
+        summary!["Encoder parameters"],
+        div![
+            label![
+                attrs! {At::Title => "runs up to this length use QOI_RUN_8 (spec: 32)"},
+                "Run8 max ",
+                input![
+                    attrs! {
+                        At::Type => "range",
+                        At::Min => 1,
+                        At::Max => RUN8_MAX_SLIDER_MAX,
+                        At::Value => run8_max,
+                    },
+                    input_ev(Ev::Input, |s| s.parse().ok().map(Msg::SetRun8Max)),
+                ],
+            ],
+            format!(" {}", run8_max),
+        ],
+        div![label![
+            attrs! {At::Title => "number of dict slots (spec: 64). QOI_INDEX is assumed to stay 1 byte"},
+            "Dict size ",
+            select![
+                DICT_SIZES.iter().map(|&size| option![
+                    attrs! {
+                        At::Value => size,
+                        At::Selected => (size == dict_size).as_at_value(),
+                    },
+                    size.to_string(),
+                ]),
+                input_ev(Ev::Change, |s| s.parse().ok().map(Msg::SetDictSize)),
+            ],
+        ],],
+        div![format!("QOI size: {} bytes ({:+} bytes)", filesize, change)],
+        div![format!(
+            "Chunk type changed: {} pixels",
+            model.analyzer_config_changed
+        )],
+    ]
+}
+
+/// Side length of the region cropped around the selected pixel.
+const PREPROCESS_CROP_SIDE: u32 = 64;
+
+fn view_preprocess(model: &Model) -> Node<Msg> {
+    let (w, h) = (model.img.width(), model.img.height());
+    let crop = model.sensitivity.pos.map(|(x, y)| {
+        let half = PREPROCESS_CROP_SIDE / 2;
+        Preprocess::Crop(
+            x.saturating_sub(half),
+            y.saturating_sub(half),
+            PREPROCESS_CROP_SIDE,
+            PREPROCESS_CROP_SIDE,
+        )
+    });
+
+    let buttons = [
+        Some(Preprocess::Grayscale),
+        Some(Preprocess::Quantize(5)),
+        Some(Preprocess::Flip(FlipDir::Horizontal)),
+        Some(Preprocess::Flip(FlipDir::Vertical)),
+        Some(Preprocess::Rotate(RotateAngle::Deg90)),
+        Some(Preprocess::Rotate(RotateAngle::Deg180)),
+        Some(Preprocess::Rotate(RotateAngle::Deg270)),
+        Some(Preprocess::Resize(w.div_ceil(2), h.div_ceil(2))),
+        crop,
+    ]
+    .into_iter()
+    .flatten()
+    .map(|p| button![p.to_string(), ev(Ev::Click, move |_| Msg::Preprocess(p))]);
+
+    let chain = model.img.preprocess_chain();
+
+    div![
+        div!["Preprocess"],
+        div![buttons],
+        IF!(crop.is_none() => div![
+            style! {St::FontSize => em(0.8)},
+            "Click the visualization to crop around a pixel.",
+        ]),
+        view_alpha_threshold(model),
+        model
+            .before_after
+            .as_ref()
+            .map(|ba| view_before_after(model, ba)),
+        IF!(!chain.is_empty() => ol![chain.iter().map(|p| li![p.to_string()])]),
+    ]
+}
+
+fn view_alpha_threshold(model: &Model) -> Node<Msg> {
+    let threshold = model
+        .alpha_threshold
+        .as_ref()
+        .map_or(128, |at| at.threshold);
+    let result = model.alpha_threshold.as_ref().map(|at| {
+        let color4_count = model.img.histogram()[QoiChunk::Color4 as usize];
+        div![
+            format!(
+                "{}: {} \u{2192} {} pixels, QOI size: {} \u{2192} {} bytes ",
+                QoiChunk::Color4.name(),
+                color4_count,
+                at.color4_count,
+                model.img.filesize_qoi(),
+                at.filesize
+            ),
+            button![
+                "apply",
+                ev(Ev::Click, move |_| Msg::Preprocess(
+                    Preprocess::AlphaThreshold(threshold)
+                )),
+            ],
+        ]
+    });
+
+    div![
+        label![
+            attrs! {
+                At::For => "input-alpha-threshold",
+                At::Title => "alpha >= threshold becomes 255, others 0",
+            },
+            "Alpha threshold: ",
+        ],
+        input![
+            attrs! {
+                At::Id => "input-alpha-threshold",
+                At::Type => "range",
+                At::Min => 0,
+                At::Max => 255,
+                At::Value => threshold,
+            },
+            input_ev(Ev::Change, Msg::PreviewAlphaThreshold),
+        ],
+        format!(" {}", threshold),
+        result,
+    ]
+}
+
+/// Explains why `chunk` does not occur in an image.
+fn absent_reason(chunk: QoiChunk) -> &'static str {
+    match chunk {
+        QoiChunk::Index => "No pixel matched a dict entry",
+        QoiChunk::Run8 => "No runs of 1-32 pixels found",
+        QoiChunk::Run16 => "No runs longer than 32 pixels found",
+        QoiChunk::Diff8 => "No pixel differed from the previous one by -2..1 in R, G and B only",
+        QoiChunk::Diff16 => {
+            "No pixel differed from the previous one by -16..15 in R and -8..7 in G, B only"
+        }
+        QoiChunk::Diff24 => "No pixel differed from the previous one by -16..15 in RGBA",
+        QoiChunk::Color1 => "No pixel needed exactly 1 raw channel",
+        QoiChunk::Color2 => "No pixel needed exactly 2 raw channels",
+        QoiChunk::Color3 => "No pixel needed exactly 3 raw channels",
+        QoiChunk::Color4 => "No pixel needed all 4 raw channels",
+    }
+}
+
+fn has_minimap(img: &StaticImage) -> bool {
+    img.width() > MINIMAP_THRESHOLD || img.height() > MINIMAP_THRESHOLD
+}
+
+/// Thumbnail of the visualization with the visible region. Clicking or dragging pans to there.
+fn view_minimap(model: &Model) -> Node<Msg> {
+    fn pan_msg(ev: web_sys::MouseEvent) -> Option<Msg> {
+        let canvas = ev.target()?.dyn_into::<HtmlCanvasElement>().ok()?;
+        let rect = canvas.get_bounding_client_rect();
+        if rect.width() <= 0. || rect.height() <= 0. {
+            return None;
+        }
+        let fx = (f64::from(ev.client_x()) - rect.left()) / rect.width();
+        let fy = (f64::from(ev.client_y()) - rect.top()) / rect.height();
+        Some(Msg::PanMinimap(fx.clamp(0., 1.), fy.clamp(0., 1.)))
+    }
+
+    let w = (model.img.width() / MINIMAP_SCALE).max(1);
+    let h = (model.img.height() / MINIMAP_SCALE).max(1);
+
+    div![
+        style! {
+            St::Position => "relative",
+            St::Display => "inline-block",
+            St::MaxWidth => "100%",
+            St::LineHeight => 0,
+        },
+        canvas![
+            el_ref(&model.refs.canvas_minimap),
+            attrs! {
+                At::Width => px(w),
+                At::Height => px(h),
+            },
+            style! {
+                St::MaxWidth => "100%",
+                St::Cursor => "crosshair",
+            },
+            mouse_ev(Ev::MouseDown, pan_msg),
+            mouse_ev(Ev::MouseMove, |ev| {
+                // pan only while the primary button is pressed.
+                if ev.buttons() & 1 != 0 {
+                    pan_msg(ev)
+                } else {
+                    None
+                }
+            }),
+        ],
+        model.viewport.map(|vp| {
+            div![style! {
+                St::Position => "absolute",
+                St::Left => format!("{:.2}%", 100. * vp.left),
+                St::Top => format!("{:.2}%", 100. * vp.top),
+                St::Width => format!("{:.2}%", 100. * vp.width),
+                St::Height => format!("{:.2}%", 100. * vp.height),
+                St::Outline => "2px solid #FF00FF",
+                St::PointerEvents => "none",
+            }]
+        }),
+    ]
+}
+
+/// Summary of chunks in the visible region of the visualization.
+fn view_visible_chunks(model: &Model) -> Option<Node<Msg>> {
+    let vp = model.viewport?;
+    let (w, h) = (f64::from(model.img.width()), f64::from(model.img.height()));
+    let x0 = (vp.left * w).floor() as u32;
+    let y0 = (vp.top * h).floor() as u32;
+    let x1 = ((vp.left + vp.width) * w).ceil().min(w) as u32;
+    let y1 = ((vp.top + vp.height) * h).ceil().min(h) as u32;
+
+    let rows = model
+        .img
+        .chunks_in_region(x0, y0, x1.saturating_sub(x0), y1.saturating_sub(y0))
+        .ok()?;
+    let mut histogram = [0_usize; QoiChunk::COUNT];
+    for &chunk in rows.iter().copied().flatten() {
+        histogram[chunk as usize] += 1;
+    }
+    let pixel_count: usize = histogram.iter().sum();
+    let top = QoiChunk::iter().max_by_key(|&chunk| histogram[chunk as usize])?;
+    if pixel_count == 0 {
+        return None;
+    }
+
+    Some(div![
+        style! {St::FontSize => em(0.8)},
+        format!(
+            "Visible: {} px, mostly {} ({:.1} %)",
+            pixel_count,
+            top.name(),
+            100. * (histogram[top as usize] as f64) / (pixel_count as f64)
+        ),
+    ])
+}
+
+/// Stacked bar of bytes contributed by each chunk type.
+fn view_bytes_bar(model: &Model) -> Node<Msg> {
+    let bytes_by_chunk = model.img.bytes_by_chunk();
+    let payload: usize = bytes_by_chunk.iter().sum();
+    let fractions = model.img.histogram_bytes_fraction();
+
+    let segments = QoiChunk::iter()
+        .filter(|&chunk| bytes_by_chunk[chunk as usize] > 0)
+        .map(|chunk| {
+            let bytes = bytes_by_chunk[chunk as usize];
+            let percent = 100. * fractions[chunk as usize];
+            let [r, g, b] = model.config.color(chunk);
+            div![
+                attrs! {At::Title => format!("{}: {} bytes ({:.1} %)", chunk.name(), bytes, percent)},
+                style! {
+                    St::Width => format!("{:.3}%", percent),
+                    St::BackgroundColor => format!("rgb({},{},{})", r, g, b),
+                },
+            ]
+        });
+    // header and padding.
+    let overhead = model.img.filesize_qoi() - payload;
+    let overhead_percent = 100. * (1. - fractions.iter().sum::<f64>());
+    let segment_overhead = div![
+        attrs! {At::Title => format!("header and padding: {} bytes", overhead)},
+        style! {
+            St::Width => format!("{:.3}%", overhead_percent),
+            St::BackgroundColor => "white",
+        },
+    ];
+
+    // number of encoded chunks (a run chunk covers multiple pixels).
+    let chunk_count: usize = QoiChunk::iter()
+        .map(|chunk| bytes_by_chunk[chunk as usize] / chunk.byte_len())
+        .sum();
+    let present = || QoiChunk::iter().filter(|&chunk| bytes_by_chunk[chunk as usize] > 0);
+    let mode = present().max_by_key(|&chunk| bytes_by_chunk[chunk as usize]);
+    let len_max = present().map(QoiChunk::byte_len).max();
+    let len_min = present().map(QoiChunk::byte_len).min();
+    let opt_str = |x: Option<usize>| x.map_or_else(|| "-".to_owned(), |x| x.to_string());
+
+    div![
+        div![
+            style! {
+                St::Display => "flex",
+                St::Height => px(16),
+                St::Border => "1px solid black",
+            },
+            segments,
+            segment_overhead,
+        ],
+        table![
+            tr![
+                td!["Bytes per chunk"],
+                td![format!(
+                    "{:.3} (max {}, min {})",
+                    (payload as f64) / (chunk_count.max(1) as f64),
+                    opt_str(len_max),
+                    opt_str(len_min)
+                )],
+            ],
+            tr![td!["Most bytes"], td![mode.map_or("-", QoiChunk::name)],],
+            tr![
+                td!["Chunk data"],
+                td![format!(
+                    "{} bytes (+ {} header/padding)",
+                    payload,
+                    model.img.filesize_qoi() - payload
+                )],
+            ],
+        ],
+    ]
+}
+
+fn view_chunk_border_mode(model: &Model) -> Node<Msg> {
+    let current = model.config.show_chunk_borders();
+    let options = ChunkBorderMode::iter().map(|mode| {
+        option![
+            attrs! {
+                At::Value => mode.name(),
+                At::Selected => (mode == current).as_at_value(),
+            },
+            mode.name(),
+        ]
+    });
+
+    div![
+        label![
+            attrs! {
+                At::For => "select-chunk-border",
+                At::Title => "lines between adjacent pixels in different groups (\"family\" is the most legible)",
+            },
+            "Chunk borders by: ",
+        ],
+        select![
+            id!("select-chunk-border"),
+            options,
+            input_ev(Ev::Change, |name| ChunkBorderMode::from_name(&name)
+                .map(Msg::SetChunkBorderMode)),
+        ],
+    ]
+}
+
+fn view_chunk_label_mode(model: &Model) -> Node<Msg> {
+    let current = model.config.chunk_label_mode();
+    let options = ChunkLabelMode::iter().map(|mode| {
+        option![
+            attrs! {
+                At::Value => mode.name(),
+                At::Selected => (mode == current).as_at_value(),
+            },
+            mode.name(),
+        ]
+    });
+
+    div![
+        label![
+            attrs! {
+                At::For => "select-chunk-label",
+                At::Title => format!("drawn at zoom {}x or more", LABEL_ZOOM_MIN),
+            },
+            "Pixel labels: ",
+        ],
+        select![
+            id!("select-chunk-label"),
+            options,
+            input_ev(Ev::Change, |name| ChunkLabelMode::from_name(&name)
+                .map(Msg::SetChunkLabelMode)),
+        ],
+    ]
+}
+
+fn view_grid_spacing(model: &Model) -> Node<Msg> {
+    let current = model.config.grid_spacing();
+    let options = [None]
+        .into_iter()
+        .chain(GRID_SPACINGS.iter().copied().map(Some))
+        .map(|spacing| {
+            option![
+                attrs! {
+                    At::Value => spacing.map_or_else(String::new, |s| s.to_string()),
+                    At::Selected => (spacing == current).as_at_value(),
+                },
+                spacing.map_or_else(|| "Off".to_owned(), |s| format!("{}px", s)),
+            ]
+        });
+
+    div![
+        label![attrs! {At::For => "select-grid-spacing"}, "Grid: "],
+        select![
+            id!("select-grid-spacing"),
+            options,
+            input_ev(Ev::Change, |s| Msg::SetGridSpacing(s.parse().ok())),
+        ],
+    ]
+}
+
+fn view_borders(model: &Model) -> Node<Msg> {
+    let config = &model.border_config;
+
+    let family_checkboxes = ChunkFamily::iter().map(|family| {
+        label![
+            input![
+                attrs! {
+                    At::Type => "checkbox",
+                    At::Checked => config.is_bordered(family).as_at_value(),
+                },
+                ev(Ev::Change, move |_| Msg::ToggleBorderedFamily(family)),
+            ],
+            family.name(),
+            " ",
+        ]
+    });
+    let [r, g, b] = config.color().unwrap_or([0, 0, 0]);
+
+    div![
+        label![
+            input![
+                attrs! {
+                    At::Type => "checkbox",
+                    At::Checked => model.show_borders.as_at_value(),
+                },
+                ev(Ev::Change, |_| Msg::ToggleBorders),
+            ],
+            "Chunk group borders",
+        ],
+        IF!(model.show_borders => div![
+            div![family_checkboxes],
+            div![
+                "color: ",
+                input![
+                    attrs! {
+                        At::Type => "color",
+                        At::Value => format!("#{:02x}{:02x}{:02x}", r, g, b),
+                    },
+                    input_ev(Ev::Input, |hex| Msg::SetBorderColor(Some(hex))),
+                ],
+                " ",
+                button![
+                    IF!(config.color().is_none() => attrs! {At::Disabled => ""}),
+                    attrs! {At::Title => "chunk color darkened by 40%"},
+                    "auto",
+                    ev(Ev::Click, |_| Msg::SetBorderColor(None)),
+                ],
+            ],
+            div![
+                "min group size: ",
+                input![
+                    attrs! {
+                        At::Type => "number",
+                        At::Min => 1,
+                        At::Value => config.min_group_size(),
+                    },
+                    style! {St::Width => em(5)},
+                    input_ev(Ev::Change, Msg::SetBorderMinGroupSize),
+                ],
+            ],
+        ]),
+    ]
+}
+
+fn view_vis_mode(model: &Model) -> Node<Msg> {
+    let mode = model.config.mode();
+    let is_heat_map = matches!(mode, VisMode::ChannelHeatMap { .. });
+
+    let channel_buttons = Channel::iter().map(|channel| {
+        let mode_new = VisMode::ChannelHeatMap { channel };
+        button![
+            IF!(mode == mode_new => attrs! {At::Disabled => ""}),
+            channel.name(),
+            ev(Ev::Click, move |_| Msg::SetVisMode(mode_new)),
+        ]
+    });
+
+    div![
+        div![
+            button![
+                IF!(mode == VisMode::Chunks => attrs! {At::Disabled => ""}),
+                "chunk types",
+                ev(Ev::Click, |_| Msg::SetVisMode(VisMode::Chunks)),
+            ],
+            " ",
+            button![
+                IF!(mode == VisMode::Family => attrs! {At::Disabled => ""}),
+                attrs! {At::Title => "chunk families (yellow: index, gray: run, cyan: diff, red: color), darker for more bytes"},
+                "chunk families",
+                ev(Ev::Click, |_| Msg::SetVisMode(VisMode::Family)),
+            ],
+            " ",
+            button![
+                IF!(is_heat_map => attrs! {At::Disabled => ""}),
+                "channel heat map",
+                ev(Ev::Click, |_| Msg::SetVisMode(VisMode::ChannelHeatMap {
+                    channel: Channel::R
+                })),
+            ],
+            " ",
+            button![
+                IF!(mode == VisMode::DiffEligibility => attrs! {At::Disabled => ""}),
+                attrs! {At::Title => "chunk types by difference from the previous pixel, ignoring the dict"},
+                "diff eligibility",
+                ev(Ev::Click, |_| Msg::SetVisMode(VisMode::DiffEligibility)),
+            ],
+            " ",
+            button![
+                IF!(mode == VisMode::ChangedChannels => attrs! {At::Disabled => ""}),
+                attrs! {At::Title => "channels differing from the previous pixel (R: red, G: green, B: blue, A: white, mixed additively)"},
+                "changed channels",
+                ev(Ev::Click, |_| Msg::SetVisMode(VisMode::ChangedChannels)),
+            ],
+            " ",
+            button![
+                IF!(mode == VisMode::LocalBytePressure => attrs! {At::Disabled => ""}),
+                attrs! {At::Title => "bytes spent on each pixel and its neighbors in scan order (green: cheap, red: expensive)"},
+                "byte pressure",
+                ev(Ev::Click, |_| Msg::SetVisMode(VisMode::LocalBytePressure)),
+            ],
+            " ",
+            button![
+                IF!(mode == VisMode::IndexSlots => attrs! {At::Disabled => ""}),
+                attrs! {At::Title => "QOI_INDEX pixels by the dict slot they matched (hue: slot 0-63)"},
+                "index slots",
+                ev(Ev::Click, |_| Msg::SetVisMode(VisMode::IndexSlots)),
+            ],
+            " ",
+            button![
+                IF!(mode == VisMode::IndexDistance => attrs! {At::Disabled => ""}),
+                attrs! {At::Title => "QOI_INDEX pixels by how long ago the matched dict entry was written (blue: recently, red: long ago)"},
+                "index distance",
+                ev(Ev::Click, |_| Msg::SetVisMode(VisMode::IndexDistance)),
+            ],
+            " ",
+            button![
+                IF!(mode == VisMode::IndexEfficiency => attrs! {At::Disabled => ""}),
+                attrs! {At::Title => "QOI_INDEX pixels by the hit ratio of the matched dict slot (red: slot mostly wasted, green: hit by half of the accesses or more)"},
+                "index efficiency",
+                ev(Ev::Click, |_| Msg::SetVisMode(VisMode::IndexEfficiency)),
+            ],
+            " ",
+            button![
+                IF!(mode == VisMode::ChunkBlocks => attrs! {At::Disabled => ""}),
+                attrs! {At::Title => "each encoded chunk in one color, cycling through 8 colors, to show chunk boundaries"},
+                "chunk blocks",
+                ev(Ev::Click, |_| Msg::SetVisMode(VisMode::ChunkBlocks)),
+            ],
+            " ",
+            button![
+                IF!(model.show_run_lengths => style! {St::FontWeight => "bold"}),
+                attrs! {At::Title => "run pixels by run length (green: short, blue: long), overriding the mode"},
+                "run lengths",
+                ev(Ev::Click, |_| Msg::ToggleRunLengths),
+            ],
+        ],
+        IF!(is_heat_map => div![channel_buttons]),
+        IF!(mode == VisMode::DiffEligibility => view_diff_ranges()),
+    ]
+}
+
+/// Reference table of channel differences covered by each diff chunk.
+fn view_diff_ranges() -> Node<Msg> {
+    let diffs = [
+        (QoiChunk::Diff8, PixelDiff::Diff8(0)),
+        (QoiChunk::Diff16, PixelDiff::Diff16(0)),
+        (
+            QoiChunk::Diff24,
+            PixelDiff::Diff24 {
+                diff_r: 0,
+                diff_gba: 0,
+            },
+        ),
+    ];
+
+    let rows = diffs.into_iter().map(|(chunk, diff)| {
+        let (r, g, b, a) = diff.max_abs_delta();
+        let range_str = |max: u8| match max {
+            0 => "0".to_owned(),
+            max => format!("-{}..={}", max, max - 1),
+        };
+        tr![
+            td![chunk.name()],
+            td![range_str(r)],
+            td![range_str(g)],
+            td![range_str(b)],
+            td![range_str(a)],
+        ]
+    });
+
+    table![
+        thead![tr![
+            th!["Diff ranges"],
+            th!["R"],
+            th!["G"],
+            th!["B"],
+            th!["A"]
+        ]],
+        tbody![rows],
+    ]
+}
+
+/// Describes bytes saved compared to raw RGBA.
+fn saving_str(bytes_raw: usize, bytes_qoi: usize) -> String {
+    if bytes_qoi <= bytes_raw {
+        format!("Saved: {} bytes", bytes_raw - bytes_qoi)
+    } else {
+        format!("Cost: {} bytes extra", bytes_qoi - bytes_raw)
+    }
+}
+
+fn view_chunk_swatch(rgb: [u8; 3]) -> Node<Msg> {
+    let [r, g, b] = rgb;
+    let color_str = QoiPixel::new(r, g, b, 0xFF).to_css_string();
+
+    svg![
+        attrs! {
+            At::Width => px(24),
+            At::Height => px(24),
+            At::ViewBox => "0 0 24 24",
+        },
+        rect![attrs! {
+            At::Width => 24,
+            At::Height => 24,
+            At::Stroke => "black",
+            At::Fill => color_str,
+        }],
+    ]
+}
+
+fn view_expensive_pixels(model: &Model) -> Node<Msg> {
+    let (x, y, _, chunk) = match model.most_expensive {
+        Some(e) => e,
+        None => return empty![],
+    };
+    let width = model.img.width() as usize;
+
+    let rows = model.expensive_pixels.iter().map(|&(x, y, px, chunk)| {
+        let idx = width * y as usize + x as usize;
+        tr![
+            style! {St::Cursor => "pointer"},
+            IF!(model.located_pixel == Some(idx) => style! {St::FontWeight => "bold"}),
+            td![format!("({}, {})", x, y)],
+            td![div![style! {
+                St::Width => em(1),
+                St::Height => em(1),
+                St::Border => "1px solid black",
+                St::BackgroundColor => px.to_css_string(),
+            }]],
+            td![chunk.name()],
+            ev(Ev::Click, move |_| Msg::LocatePixel(idx)),
+        ]
+    });
+
+    div![
+        div![
+            "Most expensive pixel: ",
+            span![
+                style! {
+                    St::Cursor => "pointer",
+                    St::TextDecoration => "underline",
+                },
+                attrs! {At::Title => "locate in the visualization"},
+                format!("({}, {})", x, y),
+                ev(Ev::Click, move |_| Msg::LocatePixel(
+                    width * y as usize + x as usize
+                )),
+            ],
+            format!(" \u{2014} {}", chunk.name()),
+        ],
+        details![
+            summary!["Most expensive distinct colors"],
+            table![tbody![rows]],
+        ],
+    ]
+}
+
+fn view_sidebar_info(model: &Model) -> Node<Msg> {
+    div![
+        div![
+            model.img.name(),
+            IF!(model.img.has_variable_alpha() => span![
+                style! {
+                    St::MarginLeft => em(0.5),
+                    St::Padding => "0 0.3em",
+                    St::BackgroundColor => "#E0B000",
+                    St::Color => "#000000",
+                },
+                attrs! {At::Title => "some pixels are not fully opaque (see the A channel heat map)"},
+                "Variable alpha detected",
+            ]),
+        ],
+        table![
+            tr![td!["Format"], td![model.img.format_name()]],
+            tr![td!["Original size"], td![model.img.filesize_orig()]],
+            tr![td!["QOI size"], td![model.img.filesize_qoi()]],
+            tr![
+                td![
+                    attrs! {At::Title => "detected from the gAMA chunk of PNG files"},
+                    "Colorspace"
+                ],
+                td![model.img.colorspace().name()],
+            ],
+            tr![
+                td![
+                    attrs! {At::Title => "rough lower bound under optimal pixel ordering"},
+                    "Best case"
+                ],
+                td![best_case_str(
+                    model.img.histogram(),
+                    model.img.filesize_qoi()
+                )],
+            ],
+            view_byte_cost_bounds(model),
+            view_expected_ratio(model),
+            tr![
+                td!["Analysis time"],
+                td![
+                    analysis_time_str(
+                        model
+                            .analysis_time_remeasured
+                            .unwrap_or_else(|| model.img.analysis_time_ms()),
+                        model.img.pixel_count()
+                    ),
+                    " ",
+                    button!["re-measure", ev(Ev::Click, |_| Msg::MeasureAnalysisTime)],
+                ],
+            ],
+            tr![
+                td!["Benchmark"],
+                td![
+                    model.benchmark.map(|res| format!("{} ", res)),
+                    button![
+                        attrs! {At::Disabled => model.benchmark_running.as_at_value()},
+                        "run",
+                        ev(Ev::Click, |_| Msg::StartBenchmark),
+                    ],
+                    IF!(model.benchmark_running => span![C!["spinner"]]),
+                ],
+            ],
+            tr![
+                td!["Wasted dict writes"],
+                td![model.img.wasted_dict_writes()]
+            ],
+            tr![
+                td![
+                    attrs! {At::Title => "pixels which were in the dict before, but evicted"},
+                    "Missed index opportunities"
+                ],
+                td![model.img.missed_index_count()]
+            ],
+            tr![
+                td![
+                    attrs! {At::Title => "hypothetical QOI with a doubled dict"},
+                    "What-if with 128 slots"
+                ],
+                td![format!(
+                    "{} bytes ({:+} bytes)",
+                    model.img.filesize_dict128(),
+                    (model.img.filesize_dict128() as isize) - (model.img.filesize_qoi() as isize)
+                )]
+            ],
+            tr![
+                td![
+                    attrs! {At::Title => "RGBA pixels, chunk sequence, run lengths and snapshots (if taken) of the current image"},
+                    "Image data"
+                ],
+                td![mib_str(model.img.estimated_memory_bytes())],
+            ],
+            util::measure_memory_bytes().map(|bytes| tr![td!["JS heap"], td![mib_str(bytes)]]),
+        ],
+        view_format_sizes(model.img.format_sizes()),
+        view_run_comparison(model.img.run_comparison()),
+        view_palette(model.img.distinct_colors()),
+        view_dominant_colors(model),
+        view_scatter(model),
+        view_aggregate(&model.aggregate),
+        view_byte_jump(model),
+        view_dict_clear(model),
+        view_bands(model),
+        view_initial_compare(model),
+        view_pixel_inspector(model),
+        view_sensitivity(model),
+    ]
+}
+
+/// 3x3 neighborhood of the selected pixel, with chunk types.
+fn view_pixel_inspector(model: &Model) -> Node<Msg> {
+    let (x, y) = match model.sensitivity.pos {
+        Some(pos) => pos,
+        None => return div![],
+    };
+    let img = model.img.image();
+    let (w, h) = img.dimensions();
+
+    let neighbors: Vec<_> = (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| {
+            let nx = x as i64 + dx;
+            let ny = y as i64 + dy;
+            let inside = (0..w as i64).contains(&nx) && (0..h as i64).contains(&ny);
+            let info = inside.then(|| {
+                let (nx, ny) = (nx as u32, ny as u32);
+                let px = img.get_pixel(nx, ny).0;
+                let chunk = model.img.chunks()[(w * ny + nx) as usize];
+                (px, chunk)
+            });
+            (dx, dy, info)
+        })
+        .collect();
+
+    let cells = neighbors.chunks(3).map(|row| {
+        tr![row.iter().map(|&(dx, dy, info)| {
+            let center = dx == 0 && dy == 0;
+            let cell_style = match info {
+                Some(([r, g, b, a], _)) => style! {
+                    St::Background => QoiPixel::new(r, g, b, a).to_css_string(),
+                },
+                None => style! {
+                    St::Background => "repeating-linear-gradient(45deg, #ccc 0 4px, #999 4px 8px)",
+                },
+            };
+            td![
+                cell_style,
+                style! {
+                    St::Width => px(48),
+                    St::Height => px(48),
+                    St::TextAlign => "center",
+                    St::Border => if center { "2px solid red" } else { "1px solid black" },
+                },
+                div![
+                    style! {
+                        St::BackgroundColor => "rgba(255, 255, 255, 0.7)",
+                        St::FontSize => em(0.8),
+                    },
+                    IF!(center => "\u{2192} "),
+                    info.map_or("out", |(_, chunk)| chunk.abbr()),
+                ],
+            ]
+        })]
+    });
+
+    let values = neighbors.iter().filter_map(|&(dx, dy, info)| {
+        let ([r, g, b, a], chunk) = info?;
+        Some(tr![
+            td![format!("({:+}, {:+})", dx, dy)],
+            td![format!("{} {} {} {}", r, g, b, a)],
+            td![chunk.name()],
+        ])
+    });
+
+    let idx = (w * y + x) as usize;
+    let chunks = model.img.chunks();
+    let prediction = chunks.get(idx + 1).map(|&actual| {
+        let predicted = most_likely_next(model.img.transitions(), chunks[idx])
+            .expect("a chunk followed by another should have transitions");
+        div![
+            attrs! {At::Title => "chunk type most often following this one in this image"},
+            format!(
+                "Next chunk: predicted {}, actual {}",
+                predicted.abbr(),
+                actual.abbr()
+            ),
+            IF!(predicted == actual => " \u{2713}"),
+        ]
+    });
+
+    div![
+        div![format!("Pixel inspector ({}, {})", x, y)],
+        table![style! {St::BorderCollapse => "collapse"}, tbody![cells]],
+        table![
+            thead![tr![th!["Offset"], th!["RGBA"], th!["Chunk"]]],
+            tbody![values],
+        ],
+        prediction,
+    ]
+}
+
+fn view_aggregate(aggregate: &AggregateStats) -> Node<Msg> {
+    let body = if aggregate.image_count == 0 {
+        div!["No images loaded yet."]
+    } else {
+        let total_chunks: usize = aggregate.accumulated_histogram.iter().sum();
+        let rows = QoiChunk::iter()
+            .filter(|&chunk| aggregate.accumulated_histogram[chunk as usize] != 0)
+            .map(|chunk| {
+                let count = aggregate.accumulated_histogram[chunk as usize];
+                tr![
+                    td![chunk.name()],
+                    td![
+                        style! {St::TextAlign => "right"},
+                        format!("{:.2} %", 100. * count as f64 / total_chunks as f64),
+                    ],
+                ]
+            });
+        let ratio =
+            aggregate.total_filesize_qoi as f64 / (4 * aggregate.total_pixels).max(1) as f64;
+        let bytes_per_pixel =
+            aggregate.total_filesize_qoi as f64 / aggregate.total_pixels.max(1) as f64;
+
+        div![
+            table![
+                tr![td!["Images"], td![aggregate.image_count]],
+                aggregate.last_analysis_timestamp.map(|timestamp| tr![
+                    td!["Last updated"],
+                    td![age_str((util::now_ms() - timestamp) / 1000.)],
+                ]),
+                tr![td!["Pixels"], td![aggregate.total_pixels]],
+                tr![td!["Original size"], td![aggregate.total_filesize_orig]],
+                tr![td!["QOI size"], td![aggregate.total_filesize_qoi]],
+                tr![td!["QOI / raw RGBA"], td![format!("{:.1} %", 100. * ratio)]],
+                tr![
+                    td!["Bytes per pixel"],
+                    td![format!("{:.3}", bytes_per_pixel)]
+                ],
+            ],
+            table![tbody![rows]],
+        ]
+    };
+
+    details![
+        summary!["Session aggregate"],
+        body,
+        button!["Reset aggregate", ev(Ev::Click, |_| Msg::ResetAggregate)],
+    ]
+}
+
+/// Scatter plot of spatial entropy vs compression ratio over loaded images.
+fn view_scatter(model: &Model) -> Node<Msg> {
+    const PLOT_WIDTH: f64 = 160.;
+    const PLOT_HEIGHT: f64 = 120.;
+    const POINT_RADIUS: f64 = 3.;
+
+    let x_max = model
+        .scatter_points
+        .iter()
+        .map(|&(x, _)| x)
+        .fold(f64::EPSILON, f64::max);
+    let y_max = model
+        .scatter_points
+        .iter()
+        .map(|&(_, y)| y)
+        .fold(f64::EPSILON, f64::max);
+
+    let last = model.scatter_points.len().saturating_sub(1);
+    let circles = model.scatter_points.iter().enumerate().map(|(i, &(x, y))| {
+        circle![attrs! {
+            At::Cx => format!("{:.1}", PLOT_WIDTH * x / x_max),
+            At::Cy => format!("{:.1}", PLOT_HEIGHT * (1. - y / y_max)),
+            At::R => POINT_RADIUS,
+            At::Fill => if i == last { "red" } else { "gray" },
+        }]
+    });
+
+    div![
+        div![format!(
+            "Spatial entropy: {:.2} (avg pixel delta)",
+            model.img.spatial_entropy()
+        )],
+        div!["Compression ratio vs spatial entropy:"],
+        svg![
+            attrs! {
+                At::Width => px(PLOT_WIDTH),
+                At::Height => px(PLOT_HEIGHT),
+                At::ViewBox => format!(
+                    "{r} {r} {} {}",
+                    PLOT_WIDTH + 2. * POINT_RADIUS,
+                    PLOT_HEIGHT + 2. * POINT_RADIUS,
+                    r = -POINT_RADIUS,
+                ),
+            },
+            style! {St::Border => "1px solid gray"},
+            circles,
+        ],
+    ]
+}
+
+fn analysis_time_str(time_ms: f64, pixel_count: usize) -> String {
+    let mpixels_per_sec = (pixel_count as f64) / 1000. / time_ms.max(f64::EPSILON);
+
+    format!("{:.1} ms ({:.1} Mpixels/s)", time_ms, mpixels_per_sec)
+}
+
+fn best_case_str(histogram: &[usize; QoiChunk::COUNT], filesize_qoi: usize) -> String {
+    let best = best_case_filesize(histogram);
+    let percent = 100. * (filesize_qoi.saturating_sub(best) as f64) / (filesize_qoi as f64);
+
+    format!("{} bytes ({:.1}% better than actual)", best, percent)
+}
+
+fn view_palette(distinct_colors: usize) -> Node<Msg> {
+    // PNG palette size.
+    const PALETTE_MAX: usize = 256;
+    // QOI dict size.
+    const DICT_SIZE: usize = 64;
+
+    if distinct_colors > PALETTE_MAX {
+        return div![format!("Distinct colors: {}", distinct_colors)];
+    }
+
+    div![
+        div![
+            style! {
+                St::Display => "inline-block",
+                St::Padding => "2px 6px",
+                St::BackgroundColor => "#FF8000",
+                St::Color => "white",
+                St::FontWeight => "bold",
+            },
+            "Palette image detected",
+        ],
+        div![format!(
+            "Distinct colors: {}. PNG palette mode would be more efficient.",
+            distinct_colors
+        )],
+        IF!(distinct_colors <= DICT_SIZE => div![
+            "All colors would fit in QOI dict simultaneously."
+        ]),
+    ]
+}
+
+/// Most frequent colors, with dict slots of the top ones.
+fn view_dominant_colors(model: &Model) -> Node<Msg> {
+    const SHOWN: usize = 16;
+    const WITH_SLOT: usize = 5;
+
+    // sorted by luminance. slots are shown for the most frequent ones only.
+    let colors = model.img.dominant_colors(SHOWN);
+    let mut frequent = colors.clone();
+    frequent.sort_by_key(|&(color, count)| (Reverse(count), color));
+    let frequent: Vec<_> = frequent
+        .iter()
+        .take(WITH_SLOT)
+        .map(|&(color, _)| color)
+        .collect();
+    let slots: Vec<(QoiPixel, u8)> = frequent
+        .iter()
+        .copied()
+        .zip(batch_hash(&frequent))
+        .collect();
+    let pixel_count = model.img.pixel_count() as f64;
+
+    let cells = colors.iter().map(|&(color, count)| {
+        let slot = slots.iter().find(|&&(c, _)| c == color).map(|&(_, slot)| slot);
+        let collides =
+            slot.is_some_and(|slot| slots.iter().filter(|&&(_, s)| s == slot).count() > 1);
+        div![
+            style! {
+                St::Display => "inline-block",
+                St::Margin => px(2),
+                St::TextAlign => "center",
+                St::FontSize => em(0.8),
+            },
+            div![
+                style! {
+                    St::Width => px(32),
+                    St::Height => px(24),
+                    St::Margin => "0 auto",
+                    St::Border => "1px solid black",
+                    St::BackgroundColor => color.to_css_string(),
+                },
+                attrs! {At::Title => format!("RGBA({}, {}, {}, {})", color.r(), color.g(), color.b(), color.a())},
             ],
+            div![count],
+            div![format!("{:.1}%", 100. * count as f64 / pixel_count)],
+            slot.map(|slot| div![
+                IF!(collides => style! {St::Color => "red"}),
+                IF!(collides => attrs! {At::Title => "shares the dict slot with another dominant color"}),
+                format!("slot {}", slot),
+            ]),
+        ]
+    });
+
+    div![
+        div![
+            attrs! {At::Title => format!(
+                "{} most frequent colors (at most {} are kept)",
+                SHOWN, DOMINANT_COLOR_MAX
+            )},
+            "Dominant colors"
         ],
-        hr![],
+        div![cells]
     ]
 }
 
-fn view_sidebar(model: &Model) -> Node<Msg> {
-    let table_rows: Vec<_> = QoiChunk::iter()
-        .map(|chunk| {
-            let idx = chunk as usize;
-            let id_str = format!("checkbox-visible-{}", idx);
-            let count = model.img.histogram()[idx];
-            let percent = 100. * (count as f64) / (model.img.pixel_count() as f64);
-            let [r, g, b] = color_of_chunk(chunk);
-            let color_str = format!("rgb({},{},{})", r, g, b);
+fn view_initial_compare(model: &Model) -> Node<Msg> {
+    let result = model.initial_compare.as_ref().map(|(px, res)| {
+        let rows = QoiChunk::iter()
+            .filter(|&chunk| res.per_chunk_changes[chunk as usize] != 0)
+            .map(|chunk| {
+                tr![
+                    td![chunk.name()],
+                    td![
+                        style! {St::TextAlign => "right"},
+                        format!("{:+}", res.per_chunk_changes[chunk as usize])
+                    ],
+                ]
+            });
+        div![
+            div![format!(
+                "vs {}: {} pixels same, {} different",
+                px.to_css_string(),
+                res.same,
+                res.different
+            )],
+            table![tbody![rows]],
+        ]
+    });
+
+    div![
+        label![
+            attrs! {
+                At::For => "input-initial-pixel",
+                At::Title => "compare with the spec initial pixel (0, 0, 0, 255)",
+            },
+            "Compare initial pixel: ",
+        ],
+        input![
+            attrs! {
+                At::Id => "input-initial-pixel",
+                At::Type => "text",
+                At::Placeholder => "#RRGGBBAA",
+                At::Size => 10,
+            },
+            input_ev(Ev::Change, Msg::CompareInitialPixel),
+        ],
+        result,
+    ]
+}
+
+fn view_dict_clear(model: &Model) -> Node<Msg> {
+    let result = model.dict_clear.as_ref().map(|clear| {
+        let filesize_qoi = model.img.filesize_qoi();
+        let delta = (clear.filesize as isize) - (filesize_qoi as isize);
+        if clear.row >= model.img.height() {
+            format!("row {} is out of the image", clear.row)
+        } else {
+            format!("{} bytes ({:+} bytes)", clear.filesize, delta)
+        }
+    });
+
+    div![
+        label![
+            attrs! {
+                At::For => "input-dict-clear",
+                At::Title => "not in the spec. re-analyze with the dict cleared before the row",
+            },
+            "Simulate dict clear at row: ",
+        ],
+        input![
+            attrs! {
+                At::Id => "input-dict-clear",
+                At::Type => "number",
+                At::Min => 0,
+                At::Max => model.img.height().saturating_sub(1),
+            },
+            input_ev(Ev::Change, Msg::SimulateDictClear),
+        ],
+        result.map(|result| div![result]),
+    ]
+}
+
+fn view_bands(model: &Model) -> Node<Msg> {
+    let height = model.img.height();
+    let result = model.band_sizes.as_ref().map(|sizes| {
+        let rows = sizes.iter().enumerate().map(|(i, size)| {
+            let y = BAND_ROWS * i as u32;
             tr![
-                td![input![
-                    id!(&id_str),
-                    attrs! {
-                        At::Type => "checkbox",
+                td![format!("rows {}-{}", y, (y + BAND_ROWS).min(height) - 1)],
+                td![style! {St::TextAlign => "right"}, format!("{} bytes", size)],
+            ]
+        });
+        div![
+            div![format!(
+                "Total: {} bytes (whole image: {} bytes)",
+                sizes.iter().sum::<usize>(),
+                model.img.filesize_qoi()
+            )],
+            div![
+                style! {
+                    St::MaxHeight => em(12),
+                    St::OverflowY => "auto",
+                },
+                table![tbody![rows]],
+            ],
+        ]
+    });
+
+    div![
+        button![
+            attrs! {At::Title => "each band is encoded as a separate QOI image"},
+            format!("Analyze in bands of {} rows", BAND_ROWS),
+            ev(Ev::Click, |_| Msg::AnalyzeBands),
+        ],
+        result,
+    ]
+}
+
+fn view_byte_jump(model: &Model) -> Node<Msg> {
+    let width = model.img.width() as usize;
+    let filesize = model.img.filesize_qoi();
+    let result = model.byte_jump.as_ref().map(|jump| match jump.found {
+        _ if jump.offset >= filesize => format!(
+            "byte {} is past the end of file ({} bytes)",
+            jump.offset, filesize
+        ),
+        Some((chunk, idx)) => format!(
+            "byte {} is in {} of pixel ({}, {})",
+            jump.offset,
+            chunk.name(),
+            idx % width,
+            idx / width
+        ),
+        None => format!("byte {} is in header or padding", jump.offset),
+    });
+
+    div![
+        label![attrs! {At::For => "input-jump-byte"}, "Jump to byte: "],
+        input![
+            attrs! {
+                At::Id => "input-jump-byte",
+                At::Type => "number",
+                At::Min => 0,
+                At::Max => filesize - 1,
+            },
+            input_ev(Ev::Change, Msg::JumpToByte),
+        ],
+        result.map(|result| div![result]),
+    ]
+}
+
+fn view_sensitivity(model: &Model) -> Node<Msg> {
+    let (x, y) = match model.sensitivity.pos {
+        Some(pos) => pos,
+        None => return div!["Click the visualization to analyze sensitivity of a pixel."],
+    };
+    let result = &model.sensitivity.result;
+
+    let sliders = ["R", "G", "B", "A"].iter().enumerate().map(|(ch, name)| {
+        let value = model.sensitivity.rgba[ch];
+        tr![
+            td![name],
+            td![input![
+                attrs! {
+                    At::Type => "range",
+                    At::Min => 0,
+                    At::Max => 255,
+                    At::Value => value,
+                },
+                input_ev(Ev::Input, move |s| s
+                    .parse()
+                    .ok()
+                    .map(|value| Msg::SetSensitivityChannel(ch, value))),
+            ]],
+            td![value.to_string()],
+        ]
+    });
+
+    div![
+        div![
+            format!("Sensitivity of pixel ({}, {}) ", x, y),
+            button!["clear", ev(Ev::Click, |_| Msg::ClearSensitivity)],
+        ],
+        table![tbody![sliders]],
+        table![
+            tr![td!["Changed chunks"], td![result.changed.len()]],
+            tr![td!["Byte delta"], td![format!("{:+}", result.byte_delta)]],
+        ],
+    ]
+}
+
+fn view_format_sizes(sizes: &FormatSizes) -> Node<Msg> {
+    let size_max = sizes.png.max(sizes.qoi).max(sizes.webp_estimate).max(1);
+    let qoi_wins = sizes.qoi_wins();
+
+    let rows = [
+        ("PNG", sizes.png, false),
+        ("QOI", sizes.qoi, qoi_wins),
+        ("WebP (estimated)", sizes.webp_estimate, false),
+    ]
+    .into_iter()
+    .map(|(name, size, wins)| {
+        let percent = 100. * (size as f64) / (size_max as f64);
+        tr![
+            td![
+                IF!(wins => style! {St::FontWeight => "bold"}),
+                name,
+                IF!(wins => " \u{2605}"),
+            ],
+            td![div![
+                style! {
+                    St::Width => format!("{:.1}%", percent),
+                    St::BackgroundColor => if wins { "#0080ff" } else { "#808080" },
+                    St::Color => "white",
+                    St::WhiteSpace => "nowrap",
+                },
+                size,
+            ]],
+        ]
+    });
+
+    table![style! {St::Width => "100%"}, tbody![rows],]
+}
+
+fn view_run_comparison(cmp: &RunComparison) -> Node<Msg> {
+    p![
+        attrs! {At::Title => "bytes spent on repetition: QOI runs vs. zero residuals of PNG \"sub\"/\"up\" filters (rough estimate)"},
+        format!(
+            "QOI runs: {} bytes ({} px) | PNG-like runs: {} bytes ({} px)",
+            cmp.qoi_run_bytes, cmp.qoi_run_pixels, cmp.png_estimated_run_bytes, cmp.png_run_pixels
+        ),
+    ]
+}
+
+fn view_vis(model: &Model) -> Node<Msg> {
+    div![
+        id!("vis"),
+        style! {St::MarginLeft => px(model.sidebar_width)},
+        div![
+            p![
+                "Original image: ",
+                button![
+                    if model.show_original {
+                        "Hide original"
+                    } else {
+                        "Show original"
                     },
-                    IF!(model.config.is_visible(chunk) => attrs! {
-                        At::Checked => "",
+                    ev(Ev::Click, |_| Msg::ToggleOriginal),
+                ],
+                " ",
+                button![
+                    IF!(model.comparison_slider.is_some() => style! {St::FontWeight => "bold"}),
+                    attrs! {At::Title => "show the original over the visualization, split by a draggable divider"},
+                    "Comparison slider",
+                    ev(Ev::Click, |_| Msg::ToggleComparisonSlider),
+                ],
+                " ",
+                button![
+                    "Toggle alpha background",
+                    ev(Ev::Click, |_| Msg::ToggleBackground),
+                ],
+                " ",
+                button!["Re-export as PNG", ev(Ev::Click, |_| Msg::DownloadPng)],
+            ],
+            IF!(model.show_original && model.comparison_slider.is_none() => img![
+                C![model.background.class_name()],
+                attrs! {
+                    At::Src => model.img.url(),
+                }
+            ]),
+            // the printed page always shows both images.
+            IF!(!model.show_original || model.comparison_slider.is_some() => img![
+                C![model.background.class_name(), "print-only"],
+                attrs! {
+                    At::Src => model.img.url(),
+                }
+            ]),
+        ],
+        div![
+            p![
+                "Visualization: ",
+                button![
+                    attrs! {At::Title => "fullscreen (f)"},
+                    "\u{26F6}",
+                    ev(Ev::Click, |_| Msg::ToggleFullscreen),
+                ],
+                " ",
+                button!["Download SVG", ev(Ev::Click, |_| Msg::DownloadSvg)],
+                " ",
+                button![
+                    "Export binary",
+                    attrs! {At::Title => "chunk sequence, one chunk per 4 bits"},
+                    ev(Ev::Click, |_| Msg::DownloadChunkSequence),
+                ],
+                " ",
+                button!["Export report", ev(Ev::Click, |_| Msg::DownloadReport)],
+                " ",
+                button![
+                    "Export CSV",
+                    IF!(model.img.pixel_count() > export::CSV_ROW_MAX => attrs! {
+                        At::Title => format!("first {} pixels only", export::CSV_ROW_MAX),
                     }),
-                    ev(Ev::Change, move |_| Msg::ToggleChunkVisibility(chunk)),
-                ]],
-                td![
-                    style! {
-                        St::TextAlign => "right",
-                        St::WhiteSpace => "nowrap",
+                    ev(Ev::Click, |_| Msg::DownloadCsv { full: false }),
+                ],
+                IF!(model.img.pixel_count() > export::CSV_ROW_MAX => vec![
+                    Node::new_text(" "),
+                    button![
+                        "Export full (slow)",
+                        ev(Ev::Click, |_| Msg::DownloadCsv { full: true }),
+                    ],
+                ]),
+                " ",
+                button![
+                    "Use as diff reference",
+                    ev(Ev::Click, |_| Msg::SetReference)
+                ],
+                " ",
+                button![
+                    attrs! {At::Title => "load the visualization as a new image"},
+                    "Analyze visualization",
+                    ev(Ev::Click, |_| Msg::CaptureVisualization)
+                ],
+            ],
+            view_scrubber(model),
+            view_trace_log(model),
+            view_zoom(model),
+            view_vis_canvas(model),
+            view_column_chart(model),
+        ],
+        view_decode_preview(model),
+        model
+            .reference
+            .as_ref()
+            .map(|reference| view_diff(model, reference)),
+        view_reorder(model),
+        view_halves(model),
+        view_transitions(model),
+        view_dict(model),
+        view_chunk_structures(model),
+    ]
+}
+
+fn view_decode_preview(model: &Model) -> Node<Msg> {
+    let body = model
+        .decode_preview
+        .as_ref()
+        .map(|preview| match &preview.decoded {
+            Ok(decoded) => div![
+                match preview.mismatches.first() {
+                    Some((x, y)) => p![
+                        style! {St::Color => "red", St::FontWeight => "bold"},
+                        format!(
+                            "DECODE MISMATCH at ({}, {}) ({} pixels in red)",
+                            x,
+                            y,
+                            preview.mismatches.len()
+                        ),
+                        br![],
+                        QoiChunk::iter()
+                            .filter(|&chunk| preview.mismatch_histogram[chunk as usize] > 0)
+                            .map(|chunk| format!(
+                                "{}: {}",
+                                chunk.name(),
+                                preview.mismatch_histogram[chunk as usize]
+                            ))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ],
+                    None => p!["Identical to the original."],
+                },
+                canvas![
+                    el_ref(&model.refs.canvas_decoded),
+                    C![model.background.class_name()],
+                    attrs! {
+                        At::Width => px(decoded.width()),
+                        At::Height => px(decoded.height()),
                     },
-                    label![attrs! {At::For => &id_str}, format!("{: >5.2} %", percent)]
                 ],
-                td![label![
-                    attrs! {At::For => &id_str},
-                    svg![
-                        attrs! {
-                            At::Width => px(24),
-                            At::Height => px(24),
-                            At::ViewBox => "0 0 24 24",
-                        },
-                        rect![attrs! {
-                            At::Width => 24,
-                            At::Height => 24,
-                            At::Stroke => "black",
-                            At::Fill => color_str,
-                        }],
-                    ]
-                ]],
-                td![label![attrs! {At::For => &id_str}, chunk.name()]],
-            ]
-        })
-        .collect();
+            ],
+            Err(e) => p![
+                style! {St::Color => "red", St::FontWeight => "bold"},
+                format!("DECODE ERROR: {}", e),
+            ],
+        });
 
     div![
-        id!("sidebar"),
+        p![label![
+            input![
+                attrs! {
+                    At::Type => "checkbox",
+                    At::Checked => model.decode_preview.is_some().as_at_value(),
+                },
+                ev(Ev::Change, |_| Msg::ToggleDecodePreview),
+            ],
+            "Decoded image (QOI encode \u{2192} decode)",
+        ]],
+        body,
+    ]
+}
+
+fn chunk_structure_str(chunk: QoiChunk) -> String {
+    format!(
+        "{} bytes: {} tag bits + {} data bits ({} channel bytes)",
+        chunk.byte_len(),
+        chunk.opcode_bits(),
+        chunk.data_bits(),
+        chunk.color_bytes()
+    )
+}
+
+fn view_chunk_structures(model: &Model) -> Node<Msg> {
+    // width of a bit in the bar.
+    const BIT_WIDTH: u32 = 6;
+
+    let rows = QoiChunk::iter().map(|chunk| {
+        let [r, g, b] = model.config.color(chunk);
+        let bits_data = chunk.data_bits();
+        tr![
+            th![chunk.name()],
+            td![div![
+                style! {
+                    St::Display => "flex",
+                    St::Height => px(16),
+                },
+                div![style! {
+                    St::Width => px(BIT_WIDTH * chunk.opcode_bits() as u32),
+                    St::BackgroundColor => "#404040",
+                }],
+                div![style! {
+                    St::Width => px(BIT_WIDTH * bits_data as u32),
+                    St::BackgroundColor => format!("rgb({},{},{})", r, g, b),
+                }],
+            ]],
+            td![chunk_structure_str(chunk)],
+        ]
+    });
+
+    div![
+        p!["Chunk byte structure (dark: tag bits):"],
+        table![tbody![rows]],
+    ]
+}
+
+/// Experiment of feeding pixels in other scan orders.
+fn view_reorder(model: &Model) -> Node<Msg> {
+    let result = model.reorder.as_ref().map(|(order, img)| {
         div![
-            button!["check all", ev(Ev::Click, |_| Msg::MakeAllChunksVisible)],
+            table![
+                tr![
+                    th![],
+                    th!["horizontal (original)"],
+                    th![format!("{} (best)", order.name())]
+                ],
+                tr![
+                    td!["QOI size"],
+                    td![model.img.filesize_qoi()],
+                    td![img.filesize_qoi()]
+                ],
+            ],
+            canvas![
+                el_ref(&model.refs.canvas_reorder),
+                attrs! {
+                    At::Width => px(img.width()),
+                    At::Height => px(img.height()),
+                },
+            ],
+        ]
+    });
+
+    div![
+        p![
+            "Scan order experiment: ",
+            button!["Try reorder", ev(Ev::Click, |_| Msg::TryReorder)],
+            IF!(result.is_some() => button!["clear", ev(Ev::Click, |_| Msg::ClearReorder)]),
+        ],
+        p![
+            style! {St::FontSize => em(0.8)},
+            "Experimental: feeds pixels in vertical or row-interleaved order and keeps the smallest. \
+             The result has a different pixel layout. It is not a lossless transform of the original.",
+        ],
+        result,
+    ]
+}
+
+fn view_halves(model: &Model) -> Node<Msg> {
+    let result = model.halves.as_ref().map(|(a, b)| {
+        let (hist_a, hist_b) = (a.histogram_normalized(), b.histogram_normalized());
+        let rows = QoiChunk::iter().map(|chunk| {
+            let idx = chunk as usize;
+            tr![
+                td![chunk.name()],
+                td![
+                    style! {St::TextAlign => "right"},
+                    format!("{:.2} %", 100. * hist_a[idx])
+                ],
+                td![
+                    style! {St::TextAlign => "right"},
+                    format!("{:.2} %", 100. * hist_b[idx])
+                ],
+            ]
+        });
+
+        table![
+            tr![th![], th![a.name()], th![b.name()]],
+            tr![
+                td!["Size"],
+                td![format!("{}x{}", a.width(), a.height())],
+                td![format!("{}x{}", b.width(), b.height())],
+            ],
+            tr![
+                td!["QOI size"],
+                td![style! {St::TextAlign => "right"}, a.filesize_qoi()],
+                td![style! {St::TextAlign => "right"}, b.filesize_qoi()],
+            ],
+            tr![
+                td!["Compression ratio"],
+                td![
+                    style! {St::TextAlign => "right"},
+                    format!("{:.2} %", 100. * a.compression_ratio())
+                ],
+                td![
+                    style! {St::TextAlign => "right"},
+                    format!("{:.2} %", 100. * b.compression_ratio())
+                ],
+            ],
+            rows,
+        ]
+    });
+
+    div![
+        p![
+            "Compare halves: ",
+            button![
+                IF!(model.img.width() < 2 => attrs! {At::Disabled => ""}),
+                "left / right",
+                ev(Ev::Click, |_| Msg::CompareHalves { vertical: false }),
+            ],
             " ",
             button![
-                "uncheck all",
-                ev(Ev::Click, |_| Msg::MakeAllChunksInvisible),
+                IF!(model.img.height() < 2 => attrs! {At::Disabled => ""}),
+                "top / bottom",
+                ev(Ev::Click, |_| Msg::CompareHalves { vertical: true }),
             ],
+            IF!(result.is_some() => button!["clear", ev(Ev::Click, |_| Msg::ClearHalves)]),
         ],
-        table![tbody![table_rows]],
-        view_sidebar_info(model),
+        result,
     ]
 }
 
-fn view_sidebar_info(model: &Model) -> Node<Msg> {
+fn view_diff(model: &Model, reference: &StaticImage) -> Node<Msg> {
+    let same_dims =
+        reference.width() == model.img.width() && reference.height() == model.img.height();
+
+    div![
+        p![
+            format!(
+                "Chunk diff vs reference '{}' (green: same, orange: same family, red: different): ",
+                reference.name()
+            ),
+            button!["clear", ev(Ev::Click, |_| Msg::ClearReference)],
+        ],
+        if same_dims {
+            canvas![
+                el_ref(&model.refs.canvas_diff),
+                attrs! {
+                    At::Width => px(model.img.width()),
+                    At::Height => px(model.img.height()),
+                },
+            ]
+        } else {
+            p!["Dimensions differ."]
+        },
+        model
+            .reference_diff
+            .as_ref()
+            .map(|(similarity, diff)| view_chunk_diff(*similarity, diff)),
+        model.reference_warm_filesize.map(|filesize| {
+            let change = (filesize as isize) - (model.img.filesize_qoi() as isize);
+            p![
+                attrs! {At::Title => "as if QOI continued the dict from the reference, like video frames"},
+                format!(
+                    "Warm dict (starting from the final dict of the reference): {} bytes ({:+} bytes)",
+                    filesize, change
+                ),
+            ]
+        }),
+    ]
+}
+
+fn view_chunk_diff(similarity: f64, diff: &ChunkDiff) -> Node<Msg> {
+    // number of most frequent changes shown.
+    const SHOWN: usize = 5;
+
+    let mut changes: Vec<_> = QoiChunk::iter()
+        .flat_map(|a| QoiChunk::iter().map(move |b| (a, b)))
+        .map(|(a, b)| (a, b, diff.transitions[a as usize][b as usize]))
+        .filter(|&(_, _, count)| count > 0)
+        .collect();
+    changes.sort_by_key(|&(_, _, count)| Reverse(count));
+
+    let rows = changes.into_iter().take(SHOWN).map(|(a, b, count)| {
+        tr![
+            td![format!("{} \u{2192} {}", a.name(), b.name())],
+            td![style! {St::TextAlign => "right"}, count],
+        ]
+    });
+
+    div![
+        p![format!(
+            "Encoding similarity: {:.2} % ({} of {} pixels differ)",
+            100. * similarity,
+            diff.different,
+            diff.same + diff.different
+        )],
+        table![tbody![rows]],
+    ]
+}
+
+fn view_transitions(model: &Model) -> Node<Msg> {
+    let matrix = model.img.transitions();
+    let ent = ChunkEntropy::new(model.img.histogram(), matrix);
+    let pixel_count = model.img.pixel_count();
+
+    let header = tr![
+        th!["prev \\ next"],
+        QoiChunk::iter().map(|chunk| th![view_chunk_swatch(model.config.color(chunk))]),
+        th!["H(next | prev)"],
+    ];
+    let rows = QoiChunk::iter().map(|chunk| {
+        let row = &matrix[chunk as usize];
+        let h_str = match ent.conditional[chunk as usize] {
+            Some(h) => format!("{:.3}", h),
+            None => "-".to_owned(),
+        };
+        tr![
+            th![chunk.name()],
+            row.iter()
+                .map(|count| td![style! {St::TextAlign => "right"}, count]),
+            td![style! {St::TextAlign => "right"}, h_str],
+        ]
+    });
+
     div![
-        div![model.img.name()],
+        p!["Chunk transitions:"],
+        table![thead![header], tbody![rows]],
         table![
-            tr![td!["Original size"], td![model.img.filesize_orig()]],
-            tr![td!["QOI size"], td![model.img.filesize_qoi()]],
+            tr![
+                td!["Zeroth-order entropy"],
+                td![format!("{:.3} bits/pixel", ent.zeroth_order)]
+            ],
+            tr![
+                td!["First-order entropy"],
+                td![format!("{:.3} bits/pixel", ent.first_order)]
+            ],
+            tr![
+                td!["Context gain"],
+                td![format!("{:.3} bits/pixel", ent.context_gain())]
+            ],
+            tr![
+                td![
+                    attrs! {At::Title => "joint entropy of 2 consecutive chunks divided by 2"},
+                    "Sequence entropy rate"
+                ],
+                td![format!("{:.2} bits", model.img.sequence_entropy_rate())]
+            ],
+        ],
+        p!["Secondary compression of the chunk sequence:"],
+        table![
+            tr![
+                td!["Chunk sequence length"],
+                td![format!("{} entries", pixel_count)]
+            ],
+            tr![
+                td!["Chunk sequence RLE size"],
+                td![format!("{} entries", model.chunk_rle_len)]
+            ],
+            tr![
+                td![
+                    attrs! {At::Title => "lower bounds of entropy coding"},
+                    "Entropy-coded"
+                ],
+                td![format!(
+                    "{:.0} bytes (zeroth order), {:.0} bytes (first order)",
+                    ent.zeroth_order * pixel_count as f64 / 8.,
+                    ent.first_order * pixel_count as f64 / 8.
+                )],
+            ],
         ],
     ]
 }
 
-fn view_vis(model: &Model) -> Node<Msg> {
+fn view_scrubber(model: &Model) -> Node<Msg> {
+    let pixel_count = model.img.pixel_count();
+    let pos = model.scrub.unwrap_or(pixel_count);
+
     div![
-        id!("vis"),
-        div![
-            p!["Original image:"],
-            img![attrs! {
-                At::Src => model.img.url(),
-            }],
+        "Timeline: ",
+        input![
+            attrs! {
+                At::Type => "range",
+                At::Min => 0,
+                At::Max => pixel_count,
+                At::Value => pos,
+            },
+            style! {St::Width => px(400)},
+            input_ev(Ev::Input, Msg::Scrub),
+        ],
+        format!(" pixel {} / {} ", pos, pixel_count),
+        button![
+            IF!(model.scrub.is_none() => attrs! {At::Disabled => ""}),
+            "reset",
+            ev(Ev::Click, |_| Msg::ClearScrub),
+        ],
+        " ",
+        button![
+            if model.playback.is_some() {
+                "pause"
+            } else {
+                "play"
+            },
+            ev(Ev::Click, |_| Msg::TogglePlayback),
+        ],
+        " ",
+        select![
+            PLAYBACK_SPEEDS.iter().map(|&speed| option![
+                attrs! {
+                    At::Value => speed,
+                    At::Selected => (speed == model.playback_speed).as_at_value(),
+                },
+                if speed == 1 {
+                    "1 pixel/frame".to_owned()
+                } else {
+                    format!("{}/frame", speed)
+                },
+            ]),
+            input_ev(Ev::Change, |s| s.parse().ok().map(Msg::SetPlaybackSpeed)),
         ],
+        model.playback.as_ref().map(|playback| view_playback_timing(
+            playback,
+            pixel_count - pos,
+            model.playback_speed
+        )),
+    ]
+}
+
+/// Shows timing of `playback` with `remaining` pixels left at `speed` pixels/frame.
+fn view_playback_timing(playback: &Playback, remaining: usize, speed: usize) -> Node<Msg> {
+    let elapsed_ms = util::now_ms() - playback.started_ms;
+    let eta_ms = if playback.frames == 0 {
+        None
+    } else {
+        let frames_left = remaining.div_ceil(speed);
+        Some(elapsed_ms / f64::from(playback.frames) * frames_left as f64)
+    };
+
+    div![format!(
+        "Frame: {:.2} ms, elapsed: {:.1} s, remaining: {}, {}",
+        playback.frame_ms,
+        elapsed_ms / 1000.,
+        eta_ms.map_or_else(
+            || "\u{2014}".to_owned(),
+            |ms| format!("{:.1} s", ms / 1000.)
+        ),
+        playback.fps.map_or_else(
+            || "\u{2014} fps".to_owned(),
+            |fps| format!("{:.1} fps", fps)
+        ),
+    )]
+}
+
+fn view_trace_log(model: &Model) -> Node<Msg> {
+    let skip = model.trace_log.len().saturating_sub(TRACE_LOG_SHOWN);
+    let rows = model.trace_log.iter().skip(skip).map(|entry| {
+        let idx = entry.idx;
+        tr![
+            style! {St::Cursor => "pointer"},
+            IF!(model.trace_selected == Some(idx) => style! {St::FontWeight => "bold"}),
+            td![style! {St::TextAlign => "right"}, idx],
+            td![format!("({}, {})", entry.x, entry.y)],
+            td![div![style! {
+                St::Width => em(1),
+                St::Height => em(1),
+                St::Border => "1px solid black",
+                St::BackgroundColor => entry.px.to_css_string(),
+            }]],
+            td![entry.chunk.name()],
+            td![&entry.reason],
+            ev(Ev::Click, move |_| Msg::SelectTraceEntry(idx)),
+        ]
+    });
+
+    details![
+        summary![format!(
+            "Encoding trace (last {} pixels before the timeline position)",
+            TRACE_LOG_SHOWN
+        )],
         div![
-            p!["Visualization:"],
-            canvas![
-                el_ref(&model.refs.canvas),
+            style! {
+                St::MaxHeight => em(12),
+                St::OverflowY => "auto",
+            },
+            table![tbody![rows]],
+        ],
+    ]
+}
+
+fn view_dict(model: &Model) -> Node<Msg> {
+    // side length of a slot cell.
+    const CELL_SIDE: u32 = 24;
+
+    let hits = model.img.index_hit_counts();
+    // while scrubbing, show the dict at the scrubber position instead of the final one.
+    let state = model.scrub.map(|pos| state_at(&model.img, pos));
+    let dict = state
+        .as_ref()
+        .map_or(model.img.dict(), AnalyzerSnapshot::dict);
+
+    // the first slot wins ties. no highlight if there are no hits at all.
+    let hottest = (0..64)
+        .rev()
+        .max_by_key(|&i| hits[i])
+        .filter(|&i| hits[i] > 0);
+    let hit_max = hits.iter().copied().max().unwrap_or(0).max(1);
+
+    let rows = (0..8).map(|row| {
+        let cells = (0..8).map(|col| {
+            let i = 8 * row + col;
+            let level = (255 * hits[i] / hit_max) as u8;
+            let slot_px = dict[i as u8];
+            td![
+                style! {
+                    St::Width => px(CELL_SIDE),
+                    St::Height => px(CELL_SIDE),
+                    St::BackgroundColor => if state.is_some() {
+                        slot_px.to_css_string()
+                    } else {
+                        format!("rgb({0},{0},{0})", level)
+                    },
+                    St::Outline => if hottest == Some(i) { "2px solid #FF0000" } else { "none" },
+                },
                 attrs! {
-                    At::Width => px(model.img.width()),
-                    At::Height => px(model.img.height()),
-                }
+                    At::Title => format!("slot {}: {} ({} hits)", i, slot_px.to_css_string(), hits[i]),
+                },
+            ]
+        });
+        tr![cells]
+    });
+
+    let unused = hits.iter().filter(|&&hit| hit == 0).count();
+
+    // slots in write order, freshest first. the stalest one is outlined.
+    let written: Vec<_> = dict.lru_order().collect();
+    let recent = written.iter().enumerate().map(|(rank, &(i, slot_px))| {
+        span![
+            style! {
+                St::Display => "inline-block",
+                St::Width => px(CELL_SIDE / 2),
+                St::Height => px(CELL_SIDE),
+                St::BackgroundColor => slot_px.to_css_string(),
+                St::Outline => IF!(rank + 1 == written.len() => "2px solid #FF00FF"),
+            },
+            attrs! {
+                At::Title => format!("slot {}: {}", i, slot_px.to_css_string()),
+            },
+        ]
+    });
+
+    div![
+        match model.scrub {
+            Some(pos) => p![format!(
+                "Dict at pixel {} (slot colors, red: most hit in the whole image):",
+                pos
+            )],
+            None => p!["Index hits per dict slot (brighter: more hits, red: most hit):"],
+        },
+        table![tbody![rows]],
+        table![tr![td!["Unused slots"], td![format!("{} / 64", unused)]],],
+        p!["Slots by last write (left: freshest, magenta: stalest):"],
+        div![recent],
+        label![
+            input![
+                attrs! {
+                    At::Type => "checkbox",
+                    At::Checked => model.highlight_wasted.as_at_value(),
+                },
+                ev(Ev::Change, |_| Msg::ToggleWastedHighlight),
             ],
+            "Highlight wasted dict writes (magenta)",
         ],
     ]
 }
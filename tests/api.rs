@@ -0,0 +1,52 @@
+//! Tests of the JavaScript API. Run with `wasm-pack test --node`.
+
+#![cfg(target_arch = "wasm32")]
+
+use image::codecs::png::PngEncoder;
+use image::{ColorType, Rgba, RgbaImage};
+use js_sys::Reflect;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use qoi_visualizer::api::{analyze_image_bytes, get_chunk_sequence};
+
+/// 3x1 PNG encoded as Run8, Color1, Color1.
+fn png_bytes() -> Vec<u8> {
+    let img = RgbaImage::from_fn(3, 1, |x, _| Rgba([0, 0, (100 * x) as u8, 255]));
+    let mut buf = Vec::new();
+    PngEncoder::new(&mut buf)
+        .encode(&img, img.width(), img.height(), ColorType::Rgba8)
+        .unwrap();
+    buf
+}
+
+fn get(obj: &JsValue, key: &str) -> JsValue {
+    Reflect::get(obj, &key.into()).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn test_analyze_image_bytes() {
+    let png = png_bytes();
+
+    let summary = analyze_image_bytes(&png).unwrap();
+    assert_eq!(get(&summary, "pixel_count").as_f64(), Some(3.));
+    assert_eq!(
+        get(&summary, "filesize_orig").as_f64(),
+        Some(png.len() as f64)
+    );
+    let histogram = get(&summary, "histogram");
+    assert_eq!(get(&histogram, "QOI_RUN_8").as_f64(), Some(1.));
+    assert_eq!(get(&histogram, "QOI_COLOR (2-Bytes)").as_f64(), Some(2.));
+
+    let err = analyze_image_bytes(b"not an image").unwrap_err();
+    assert!(err.as_string().is_some());
+}
+
+#[wasm_bindgen_test]
+fn test_get_chunk_sequence() {
+    let chunks = get_chunk_sequence(&png_bytes()).unwrap();
+    assert_eq!(chunks.length(), 3);
+
+    let err = get_chunk_sequence(b"not an image").unwrap_err();
+    assert!(err.as_string().is_some());
+}